@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+
+use crate::metadata::EmbeddedMetadata;
+
+/// Lowercase names of generators as they appear in `Software` like fields.
+const KNOWN_GENERATORS: &[&str] = &[
+    "midjourney",
+    "nijijourney",
+    "dall-e",
+    "dall·e",
+    "openai",
+    "stable diffusion",
+    "novelai",
+    "adobe firefly",
+    "comfyui",
+    "automatic1111",
+    "invokeai",
+    "fooocus",
+    "leonardo.ai",
+    "bing image creator",
+    "imagen",
+];
+
+/// Disclosures written into the metadata by generators and platforms that
+/// label their output. Watermarks in the pixels themselves aren't detected.
+const KNOWN_LABELS: &[&str] = &[
+    "synthid",
+    "imagined with ai",
+    "made with ai",
+    "ai generated",
+    "ai-generated",
+];
+
+/// IPTC digital source types describing generated media.
+const AI_SOURCE_TYPES: &[&str] = &[
+    "trainedAlgorithmicMedia",
+    "compositeWithTrainedAlgorithmicMedia",
+];
+
+const SOFTWARE_FIELDS: &[&str] = &["Exif.Software", "Xmp.xmp:CreatorTool", "Png.Software"];
+
+/// Text chunks written by generation front ends next to the image.
+const PARAMETER_FIELDS: &[&str] = &[
+    "Png.parameters",
+    "Png.prompt",
+    "Png.workflow",
+    "Png.Dream",
+    "Png.sd-metadata",
+    "Png.invokeai_metadata",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AigcVerdict {
+    Generated,
+    Likely,
+    NotDetected,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AigcEvidenceSource {
+    C2pa,
+    DigitalSourceType,
+    GenerationParameters,
+    Software,
+    #[serde(alias = "watermark")]
+    Label,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AigcEvidence {
+    pub source: AigcEvidenceSource,
+    pub detail: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AigcDetection {
+    pub verdict: AigcVerdict,
+    pub evidence: Vec<AigcEvidence>,
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Matches whole words only, so "imagen" doesn't match "Imagenomic".
+fn contains_token(haystack: &str, token: &str) -> bool {
+    haystack.match_indices(token).any(|(start, _)| {
+        let before = haystack[..start].chars().next_back();
+        let after = haystack[start + token.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })
+}
+
+/// Finds the C2PA manifest store, carried in JUMBF boxes by JPEG APP11
+/// segments, the PNG `caBX` chunk or the ISO BMFF `uuid` box.
+fn find_c2pa_manifest(bytes: &[u8]) -> Option<&[u8]> {
+    let begin = find_bytes(bytes, b"jumb")?;
+    let manifest = &bytes[begin..];
+    find_bytes(manifest, b"c2pa")?;
+    Some(manifest)
+}
+
+impl AigcDetection {
+    pub fn detect(bytes: &[u8], meta: Option<&EmbeddedMetadata>) -> Self {
+        let mut evidence = Vec::new();
+
+        if let Some(manifest) = find_c2pa_manifest(bytes) {
+            evidence.push(AigcEvidence {
+                source: AigcEvidenceSource::C2pa,
+                detail: "C2PA manifest present".to_string(),
+            });
+            for ty in AI_SOURCE_TYPES {
+                if find_bytes(manifest, ty.as_bytes()).is_some() {
+                    evidence.push(AigcEvidence {
+                        source: AigcEvidenceSource::DigitalSourceType,
+                        detail: format!("C2PA digital source type {}", ty),
+                    });
+                    break;
+                }
+            }
+        }
+
+        if let Some(meta) = meta {
+            Self::inspect_metadata(meta, &mut evidence);
+        }
+
+        let verdict = if evidence.iter().any(|e| {
+            matches!(
+                e.source,
+                AigcEvidenceSource::DigitalSourceType | AigcEvidenceSource::GenerationParameters
+            )
+        }) {
+            AigcVerdict::Generated
+        } else if evidence.iter().any(|e| {
+            matches!(
+                e.source,
+                AigcEvidenceSource::Software | AigcEvidenceSource::Label
+            )
+        }) {
+            AigcVerdict::Likely
+        } else {
+            AigcVerdict::NotDetected
+        };
+
+        Self { verdict, evidence }
+    }
+
+    fn inspect_metadata(meta: &EmbeddedMetadata, evidence: &mut Vec<AigcEvidence>) {
+        for (field, value) in &meta.fields {
+            if let Some(ty) = AI_SOURCE_TYPES.iter().find(|ty| value.contains(*ty)) {
+                evidence.push(AigcEvidence {
+                    source: AigcEvidenceSource::DigitalSourceType,
+                    detail: format!("{} is {}", field, ty),
+                });
+            }
+
+            let lower = value.to_lowercase();
+            if let Some(marker) = KNOWN_LABELS.iter().find(|m| contains_token(&lower, m)) {
+                evidence.push(AigcEvidence {
+                    source: AigcEvidenceSource::Label,
+                    detail: format!("{} mentions \"{}\"", field, marker),
+                });
+            }
+        }
+
+        for field in SOFTWARE_FIELDS {
+            let Some(value) = meta.fields.get(*field) else {
+                continue;
+            };
+            let lower = value.to_lowercase();
+            if KNOWN_GENERATORS.iter().any(|g| contains_token(&lower, g)) {
+                evidence.push(AigcEvidence {
+                    source: AigcEvidenceSource::Software,
+                    detail: format!("{} is {}", field, value.trim_matches('"')),
+                });
+            }
+        }
+
+        for field in PARAMETER_FIELDS {
+            if meta.fields.contains_key(*field) {
+                evidence.push(AigcEvidence {
+                    source: AigcEvidenceSource::GenerationParameters,
+                    detail: format!("{} present", field),
+                });
+            }
+        }
+        if meta.prompt.is_some()
+            && !evidence
+                .iter()
+                .any(|e| e.source == AigcEvidenceSource::GenerationParameters)
+        {
+            evidence.push(AigcEvidence {
+                source: AigcEvidenceSource::GenerationParameters,
+                detail: "Generation prompt present".to_string(),
+            });
+        }
+    }
+}
@@ -1,5 +1,8 @@
 use std::{
-    fs::{copy, create_dir_all, metadata, read, read_dir, remove_file, File},
+    fs::{
+        copy, create_dir_all, hard_link, metadata, read, read_dir, remove_dir_all, remove_file,
+        File,
+    },
     io::Write,
     ops::{Deref, DerefMut},
     path::{Path, PathBuf},
@@ -18,6 +21,20 @@ use tauri::{AppHandle, Manager};
 use thiserror::Error;
 use uuid::Uuid;
 
+use crate::{
+    aigc::{AigcDetection, AigcVerdict},
+    autotag::TagSuggestion,
+    bridge::BridgeSettings,
+    crypto::{self, LibraryCipher},
+    http::HttpSettings,
+    lock::StorageFingerprint,
+    metadata::{self, EmbeddedMetadata},
+    rendition,
+    semantic::EmbeddingIndex,
+    snapshot::{self, SnapshotPolicy},
+    sync::{self, SyncState},
+};
+
 pub const LIBRARY_STORAGE: &str = "snowflake.json";
 pub const IMAGE_ASSETS: &str = "images";
 pub const MODEL_ASSETS: &str = "models";
@@ -53,6 +70,76 @@ pub enum AppError {
     SettingNotFound(String, String),
     #[error("Incompatible setting value.")]
     IncompatibleSettingValue,
+    #[error("Watcher error: {0}")]
+    Notify(#[from] notify::Error),
+    #[error("Invalid watch folder: {0}")]
+    InvalidWatchFolder(PathBuf),
+    #[error("Watch folder {0} not found.")]
+    WatchFolderNotFound(PathBuf),
+    #[error("Zip error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Download {0} not found.")]
+    DownloadNotFound(u32),
+    #[error("Http error: {0}")]
+    Reqwest(#[from] reqwest::Error),
+    #[error("Invalid http header: {0}")]
+    InvalidHttpHeader(String),
+    #[error("Invalid cookie file line: {0}")]
+    InvalidCookieFile(String),
+    #[error("Bridge error: {0}")]
+    Bridge(String),
+    #[error("Clipboard error: {0}")]
+    Clipboard(#[from] arboard::Error),
+    #[error("Png error: {0}")]
+    Png(#[from] png::EncodingError),
+    #[error("Unsupported clipboard content.")]
+    UnsupportedClipboardContent,
+    #[error("Invalid foreign library: {0}")]
+    InvalidForeignLibrary(PathBuf),
+    #[error("AIGC detection is disabled.")]
+    AigcDetectionDisabled,
+    #[error("Auto tagging is disabled.")]
+    AutoTagDisabled,
+    #[error("OCR is disabled.")]
+    OcrDisabled,
+    #[error("Library not loaded: {0}")]
+    LibraryNotLoaded(PathBuf),
+    #[error("Can't transfer within the same library: {0}")]
+    SameLibrary(PathBuf),
+    #[error("Model not found: {0}")]
+    ModelNotFound(PathBuf),
+    #[error("Model error: {0}")]
+    Model(String),
+    #[error("Library is locked by {owner} on {host} since {since}.")]
+    LibraryLocked {
+        owner: String,
+        host: String,
+        since: DateTime<FixedOffset>,
+    },
+    #[error("Library at {0} was modified by someone else, reload or merge before saving.")]
+    LibraryModifiedExternally(PathBuf),
+    #[error("Sync is not enabled for this library.")]
+    SyncNotEnabled,
+    #[error("Sync folder {0} belongs to another library.")]
+    SyncLibraryMismatch(PathBuf),
+    #[error("Snapshot {0} not found.")]
+    SnapshotNotFound(String),
+    #[error("Image decoding error: {0}")]
+    Decode(#[from] image::ImageError),
+    #[error("Library is encrypted, a password is required.")]
+    LibraryEncrypted,
+    #[error("Wrong password.")]
+    WrongPassword,
+    #[error("Library is already encrypted.")]
+    LibraryAlreadyEncrypted,
+    #[error("Library is not encrypted.")]
+    LibraryNotEncrypted,
+    #[error("The data key of the library is still being rotated.")]
+    KeyRotationInProgress,
+    #[error("Encrypted libraries can't be synced or written back to folders.")]
+    EncryptedLibrarySync,
+    #[error("Crypto error: {0}")]
+    Crypto(String),
 }
 
 pub type AppResult<T> = Result<T, AppError>;
@@ -136,6 +223,10 @@ impl ResourceCache {
 pub struct AppData {
     pub recent_libs: HashMap<PathBuf, RecentLib>,
     pub settings: UserSettings,
+    #[serde(default)]
+    pub http: HttpSettings,
+    #[serde(default)]
+    pub bridge: BridgeSettings,
 }
 
 impl AppData {
@@ -178,7 +269,7 @@ pub struct RecentLib {
 }
 
 #[derive(Debug)]
-struct FolderAsTag<'a> {
+pub struct FolderAsTag<'a> {
     root_collection: CollectionId,
     tags: &'a mut HashMap<TagId, Tag>,
     collections: &'a mut HashMap<CollectionId, Collection>,
@@ -186,21 +277,12 @@ struct FolderAsTag<'a> {
     folder_collections: &'a mut HashMap<PathBuf, CollectionId>,
 }
 
-fn collect_path<'a>(
-    root: &Path,
-    path: PathBuf,
-    assets: &mut HashMap<AssetId, Asset>,
-    asset_crc: &mut HashMap<AssetId, u32>,
-    folder_as_tag: &mut Option<FolderAsTag<'a>>,
-) -> AppResult<()> {
-    fn retrace_path_collections<'a>(
-        current: &Path,
-        folder_as_tag: &mut FolderAsTag<'a>,
-    ) -> Option<CollectionId> {
-        if let Some(collection) = folder_as_tag.folder_collections.get(current) {
+impl<'a> FolderAsTag<'a> {
+    fn retrace_path_collections(&mut self, current: &Path) -> Option<CollectionId> {
+        if let Some(collection) = self.folder_collections.get(current) {
             Some(*collection)
         } else {
-            let parent = retrace_path_collections(current.parent()?, folder_as_tag)?;
+            let parent = self.retrace_path_collections(current.parent()?)?;
             let collection = Collection::new(
                 Some(parent),
                 Some(Color::random()),
@@ -211,14 +293,10 @@ fn collect_path<'a>(
                     .to_string()
                     .into(),
             );
-            folder_as_tag
-                .folder_collections
+            self.folder_collections
                 .insert(current.to_path_buf(), collection.id);
-            folder_as_tag
-                .collections
-                .insert(collection.id, collection.clone());
-            folder_as_tag
-                .collections
+            self.collections.insert(collection.id, collection.clone());
+            self.collections
                 .get_mut(&parent)
                 .unwrap()
                 .children
@@ -227,6 +305,52 @@ fn collect_path<'a>(
         }
     }
 
+    fn resolve_folder_tag(&mut self, folder: &Path) -> Tag {
+        let tag = match self.folder_tags.get(folder) {
+            Some(tag_id) => self.tags[tag_id].clone(),
+            None => {
+                let parent = folder
+                    .parent()
+                    .and_then(|grandparent| self.retrace_path_collections(grandparent))
+                    .unwrap_or(self.root_collection);
+
+                let mut tag = Tag::new(
+                    folder
+                        .file_stem()
+                        .unwrap()
+                        .to_string_lossy()
+                        .to_string()
+                        .into(),
+                    parent,
+                );
+                if parent != self.root_collection {
+                    tag.group = Some(parent);
+                }
+
+                tag.color = self.collections[&parent].color;
+                self.folder_tags.insert(folder.to_path_buf(), tag.id);
+                self.tags.insert(tag.id, tag.clone());
+                tag
+            }
+        };
+
+        self.collections
+            .get_mut(&tag.parent)
+            .unwrap()
+            .content
+            .insert(tag.id);
+        tag
+    }
+}
+
+pub fn collect_path<'a>(
+    root: &Path,
+    path: PathBuf,
+    assets: &mut HashMap<AssetId, Asset>,
+    asset_crc: &mut HashMap<AssetId, u32>,
+    folder_as_tag: &mut Option<FolderAsTag<'a>>,
+    mode: ImportMode,
+) -> AppResult<()> {
     let std_meta = metadata(&path)?;
     let meta = Metadata::from_std_meta(&std_meta);
     let name = path
@@ -243,7 +367,7 @@ fn collect_path<'a>(
         }
 
         for entry in dir_entries {
-            collect_path(root, entry?.path(), assets, asset_crc, folder_as_tag)?;
+            collect_path(root, entry?.path(), assets, asset_crc, folder_as_tag, mode)?;
         }
 
         Ok(())
@@ -260,6 +384,15 @@ fn collect_path<'a>(
         let Some(ty) = AssetType::from_ext(&ext) else {
             return Ok(());
         };
+        let mut embedded_meta = match ty {
+            AssetType::RasterGraphics => EmbeddedMetadata::extract(&file_content),
+            _ => None,
+        };
+        if let Some(sidecar) = metadata::read_xmp_sidecar(&path) {
+            embedded_meta
+                .get_or_insert_with(Default::default)
+                .merge_xmp(&sidecar);
+        }
 
         let props = match ty {
             AssetType::RasterGraphics => {
@@ -283,52 +416,25 @@ fn collect_path<'a>(
         };
 
         let mut asset = Asset::new(name.into(), ext.into(), meta, ty, props, Default::default());
+        asset.set_embedded_meta(embedded_meta);
         if let Some(folder_as_tag) = folder_as_tag.as_mut() {
-            let parent_path = path.parent().unwrap();
-
-            let tag = match folder_as_tag.folder_tags.get(parent_path) {
-                Some(tag_id) => folder_as_tag.tags[tag_id].clone(),
-                None => {
-                    let parent = parent_path
-                        .parent()
-                        .and_then(|grandparent| {
-                            retrace_path_collections(grandparent, folder_as_tag)
-                        })
-                        .unwrap_or(folder_as_tag.root_collection);
-
-                    let mut tag = Tag::new(
-                        parent_path
-                            .file_stem()
-                            .unwrap()
-                            .to_string_lossy()
-                            .to_string()
-                            .into(),
-                        parent,
-                    );
-                    if parent != folder_as_tag.root_collection {
-                        tag.group = Some(parent);
-                    }
-
-                    tag.color = folder_as_tag.collections[&parent].color;
-                    folder_as_tag
-                        .folder_tags
-                        .insert(parent_path.to_path_buf(), tag.id);
-                    folder_as_tag.tags.insert(tag.id, tag.clone());
-                    tag
-                }
-            };
-
-            folder_as_tag
-                .collections
-                .get_mut(&tag.parent)
-                .unwrap()
-                .content
-                .insert(tag.id);
+            let tag = folder_as_tag.resolve_folder_tag(path.parent().unwrap());
             asset.tags.insert_unchecked(&tag);
         }
 
-        // Copy to preserve metadata
-        copy(&path, asset.get_file_path(root))?;
+        match mode {
+            // Copy to preserve metadata
+            ImportMode::Copy => {
+                copy(&path, asset.get_storage_path(root))?;
+            }
+            ImportMode::Link => {
+                asset.link = Some(AssetLink {
+                    path: std::path::absolute(&path)?,
+                    crc,
+                    status: LinkStatus::Available,
+                });
+            }
+        }
         asset_crc.insert(asset.id, crc);
         assets.insert(asset.id, asset);
 
@@ -338,7 +444,16 @@ fn collect_path<'a>(
     }
 }
 
-#[derive(Serialize, Default)]
+/// Assets a batch of watched paths turned into. Files which failed to
+/// import are handed back for the watcher to retry.
+#[derive(Default)]
+pub struct WatchedImport {
+    pub assets: Vec<AssetId>,
+    pub duplication: DuplicateAssets,
+    pub failed: Vec<PathBuf>,
+}
+
+#[derive(Serialize, Default, Clone)]
 pub struct DuplicateAssets(pub HashMap<u32, Vec<AssetId>>);
 
 impl DuplicateAssets {
@@ -385,6 +500,7 @@ pub enum ItemIds {
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 #[serde(tag = "ty", content = "data")]
+#[allow(clippy::large_enum_variant)]
 pub enum Item {
     Asset(Asset),
     Collection(Collection),
@@ -413,6 +529,22 @@ pub struct StorageCache {
     pub root: PathBuf,
     pub asset_crc: HashMap<AssetId, u32>,
     pub crc_lookup: HashMap<u32, Vec<AssetId>>,
+    /// Mirrors the `experimental.aigcDetect` user setting.
+    pub aigc_detect: bool,
+    /// Loaded on the first semantic query.
+    pub embeddings: Option<EmbeddingIndex>,
+    /// `snowflake.json` as we last read or wrote it.
+    pub fingerprint: Option<StorageFingerprint>,
+    /// The plain content behind `fingerprint`, the base of
+    /// [`Storage::merge_external`].
+    pub base: Option<Vec<u8>>,
+    pub sync: Option<SyncState>,
+    /// Mirrors the `library.snapshot*` user settings.
+    pub snapshot_policy: SnapshotPolicy,
+    /// Mirrors the `library.recycleBinRetention` user setting, in days.
+    pub recycle_bin_retention: u32,
+    /// Set while an encrypted library is unlocked.
+    pub cipher: Option<Arc<LibraryCipher>>,
 }
 
 impl StorageCache {
@@ -431,9 +563,57 @@ impl StorageCache {
             root: root.to_path_buf(),
             asset_crc,
             crc_lookup,
+            aigc_detect: false,
+            embeddings: None,
+            fingerprint: None,
+            base: None,
+            sync: SyncState::load(root),
+            snapshot_policy: Default::default(),
+            recycle_bin_retention: 0,
+            cipher: None,
         }
     }
 
+    /// The decrypted content of the asset file.
+    pub fn read_asset(&self, asset: &Asset) -> AppResult<Vec<u8>> {
+        crypto::read_file(&asset.get_file_path(&self.root), self.cipher.as_deref())
+    }
+
+    /// Writes a file into the library, encrypted if the library is.
+    pub fn write_file(&self, path: &Path, bytes: &[u8]) -> AppResult<()> {
+        match &self.cipher {
+            Some(cipher) => std::fs::write(path, cipher.encrypt(bytes)?)?,
+            None => std::fs::write(path, bytes)?,
+        }
+        Ok(())
+    }
+
+    /// Encrypts a file copied into the library as is.
+    pub fn seal_file(&self, path: &Path) -> AppResult<()> {
+        if self.cipher.is_some() {
+            let bytes = read(path)?;
+            if !crypto::is_sealed(&bytes) {
+                self.write_file(path, &bytes)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn compute_crc(&self, asset: &Asset) -> AppResult<u32> {
+        Ok(crc32fast::hash(&self.read_asset(asset)?))
+    }
+
+    /// Mirrors the user settings the library needs outside of commands.
+    pub fn apply_settings(&mut self, settings: &UserSettings) {
+        self.aigc_detect = settings
+            .get_as("experimental", "aigcDetect")
+            .unwrap_or_default();
+        self.snapshot_policy = SnapshotPolicy::from_user_settings(settings);
+        self.recycle_bin_retention = settings
+            .get_as::<f32>("library", "recycleBinRetention")
+            .unwrap_or_default() as u32;
+    }
+
     pub fn add_asset(&mut self, crc: u32, asset: AssetId) -> Option<DuplicateAssets> {
         self.asset_crc.insert(asset, crc);
 
@@ -529,6 +709,98 @@ pub struct RecycleBin {
     pub assets: HashSet<AssetId>,
     pub collections: HashSet<CollectionId>,
     pub tags: HashSet<TagId>,
+    /// When each item entered the bin, keyed by its raw id. Items binned
+    /// before this was recorded are stamped on the next save.
+    #[serde(default)]
+    pub deleted_at: HashMap<Uuid, DateTime<FixedOffset>>,
+    /// What the deletion of each binned collection or tag took along.
+    #[serde(default)]
+    pub cascades: HashMap<Uuid, Cascade>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TagAssignment {
+    pub asset: AssetId,
+    pub tag: TagId,
+}
+
+/// The items soft deleted along with a collection or tag, brought back
+/// together on recovery. Items binned on their own beforehand are not part of
+/// it and stay in the bin.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Cascade {
+    pub collections: Vec<CollectionId>,
+    pub tags: Vec<TagId>,
+    pub assignments: Vec<TagAssignment>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecycleBinNode {
+    pub item: ItemId,
+    pub name: String,
+    /// Only set on the items binned directly.
+    pub deleted_at: Option<DateTime<FixedOffset>>,
+    /// Assets which had this tag.
+    pub assignments: usize,
+    pub children: Vec<RecycleBinNode>,
+}
+
+impl RecycleBin {
+    fn ids(&self) -> impl Iterator<Item = Uuid> + '_ {
+        self.assets
+            .iter()
+            .map(|a| a.0)
+            .chain(self.collections.iter().map(|c| c.0))
+            .chain(self.tags.iter().map(|t| t.0))
+    }
+
+    /// Stamps the items missing a deletion time and forgets the ones which
+    /// left the bin.
+    pub fn sync_deleted_at(&mut self) {
+        let now = Local::now().into();
+        let ids = self.ids().collect::<HashSet<_>>();
+        self.deleted_at.retain(|id, _| ids.contains(id));
+        self.cascades.retain(|id, _| ids.contains(id));
+        for id in ids {
+            self.deleted_at.entry(id).or_insert(now);
+        }
+    }
+
+    /// Whether the item has been in the bin for at least `age`.
+    fn is_expired(&self, id: Uuid, age: Option<chrono::Duration>) -> bool {
+        age.is_none_or(|age| {
+            self.deleted_at
+                .get(&id)
+                .is_some_and(|at| Local::now().signed_duration_since(*at) >= age)
+        })
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecycleBinEntry {
+    pub item: ItemId,
+    pub deleted_at: Option<DateTime<FixedOffset>>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecycleBinInfo {
+    pub entries: Vec<RecycleBinEntry>,
+    /// Bytes freed by emptying the bin, linked files are never removed.
+    pub reclaimable_bytes: u64,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RecycleBinPurge {
+    pub assets: usize,
+    pub collections: usize,
+    pub tags: usize,
+    pub freed_bytes: u64,
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy)]
@@ -543,6 +815,69 @@ impl SpecialCollections {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchFolder {
+    pub path: PathBuf,
+    pub initial_tag: Option<TagId>,
+    pub folder_as_tag: bool,
+    #[serde(default)]
+    pub folder_tags: HashMap<PathBuf, TagId>,
+    #[serde(default)]
+    pub folder_collections: HashMap<PathBuf, CollectionId>,
+    #[serde(default)]
+    pub sync: Option<FolderSync>,
+}
+
+impl WatchFolder {
+    pub fn new(path: PathBuf, initial_tag: Option<TagId>, folder_as_tag: bool) -> Self {
+        Self {
+            path,
+            initial_tag,
+            folder_as_tag,
+            folder_tags: Default::default(),
+            folder_collections: Default::default(),
+            sync: None,
+        }
+    }
+
+    fn prune_mappings(
+        &mut self,
+        tags: &HashMap<TagId, Tag>,
+        collections: &HashMap<CollectionId, Collection>,
+        root_collection: CollectionId,
+    ) {
+        self.folder_tags
+            .retain(|_, t| tags.get(t).is_some_and(|t| !t.is_deleted));
+        self.folder_collections
+            .retain(|_, c| collections.get(c).is_some_and(|c| !c.is_deleted));
+        self.folder_collections
+            .insert(self.path.clone(), root_collection);
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderSync {
+    pub write_back: bool,
+    #[serde(default)]
+    pub files: HashMap<PathBuf, AssetId>,
+    /// Files created by writing tags back, the only ones removed again
+    /// when the tag is.
+    #[serde(default)]
+    pub written: HashSet<PathBuf>,
+}
+
+impl FolderSync {
+    pub fn new(write_back: bool) -> Self {
+        Self {
+            write_back,
+            files: Default::default(),
+            written: Default::default(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Copy)]
 #[serde(rename_all = "camelCase")]
 pub enum TagGroupConflictResolve {
@@ -550,6 +885,14 @@ pub enum TagGroupConflictResolve {
     Remove,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportMode {
+    #[default]
+    Copy,
+    Link,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 
@@ -557,6 +900,8 @@ pub struct StorageConstructionSettings {
     pub src_root: PathBuf,
     pub root: PathBuf,
     pub folder_as_tag: bool,
+    #[serde(default)]
+    pub import_mode: ImportMode,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -570,6 +915,24 @@ pub struct Storage {
     pub assets: HashMap<AssetId, Asset>,
     pub recycle_bin: RecycleBin,
     pub lib_meta: LibraryMeta,
+    #[serde(default)]
+    pub watch_folders: Vec<WatchFolder>,
+    /// Overrides the global http settings for web imports into this library.
+    #[serde(default)]
+    pub http: Option<HttpSettings>,
+    #[serde(default)]
+    pub metadata_mapping: MetadataMapping,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataMapping {
+    /// Collection receiving embedded keywords as tags.
+    #[serde(default)]
+    pub keyword_collection: Option<CollectionId>,
+    /// Recreate `lr:hierarchicalSubject` paths as collections and tags.
+    #[serde(default)]
+    pub hierarchical_tags: bool,
 }
 
 impl Storage {
@@ -607,6 +970,7 @@ impl Storage {
                 folder_tags: &mut folder_tags,
                 folder_collections: &mut folder_collections,
             }),
+            settings.import_mode,
         )?;
 
         let mut result = Self {
@@ -619,44 +983,231 @@ impl Storage {
             lib_meta: LibraryMeta::new(
                 root_path.file_name().unwrap().to_string_lossy().to_string(),
             ),
+            watch_folders: Default::default(),
+            http: None,
+            metadata_mapping: Default::default(),
         };
         result.cache = StorageCache::build(&root_path, duplication);
 
         Ok(result)
     }
 
-    pub fn from_existing(root_folder: impl AsRef<Path>) -> Result<Self, AppError> {
+    pub fn from_empty(root_path: PathBuf) -> AppResult<Self> {
+        if root_path.exists() && read_dir(&root_path)?.count() != 0 {
+            return Err(AppError::FolderNotEmpty(root_path));
+        }
+
+        validate_library(&root_path, true);
+
+        let root_collection = Collection::new(None, None, Default::default());
+        let mut result = Self {
+            cache: Default::default(),
+            sp_collections: SpecialCollections {
+                root: root_collection.id,
+            },
+            tags: Default::default(),
+            collections: HashMap::from([(root_collection.id, root_collection)]),
+            assets: Default::default(),
+            recycle_bin: Default::default(),
+            lib_meta: LibraryMeta::new(
+                root_path.file_name().unwrap().to_string_lossy().to_string(),
+            ),
+            watch_folders: Default::default(),
+            http: None,
+            metadata_mapping: Default::default(),
+        };
+        result.cache = StorageCache::build(&root_path, Default::default());
+
+        Ok(result)
+    }
+
+    /// `cipher` comes from [`crypto::unlock`] for encrypted libraries.
+    pub fn from_existing(
+        root_folder: impl AsRef<Path>,
+        cipher: Option<Arc<LibraryCipher>>,
+    ) -> Result<Self, AppError> {
         let root = root_folder.as_ref();
         if !validate_library(root, false) {
             return Err(AppError::InvalidLibrary);
         }
 
-        let path = root.join(LIBRARY_STORAGE);
-        let reader = File::open(&path)?;
-        let mut result = serde_json::from_reader::<_, Self>(reader)
-            .or(crate::compatibility::load_legacy_storage(root))?;
+        let bytes = read(root.join(LIBRARY_STORAGE))?;
+        let json = crypto::open(bytes.clone(), cipher.as_deref())?;
+        let (mut result, base) = match serde_json::from_slice::<Self>(&json) {
+            Ok(result) => (result, Some(json)),
+            Err(_) => (crate::compatibility::load_legacy_storage(root)?, None),
+        };
 
+        result.cache = StorageCache::build(root, Default::default());
+        result.cache.cipher = cipher;
         let asset_crc = result
             .assets
             .values()
             .filter_map(|asset| {
-                read(asset.get_file_path(&root))
+                result
+                    .cache
+                    .compute_crc(asset)
                     .ok()
-                    .map(|data| (data, asset.id))
+                    .map(|crc| (asset.id, crc))
             })
-            .map(|(data, id)| (id, crc32fast::hash(&data)))
-            .collect();
-
-        result.cache = StorageCache::build(root, asset_crc);
-        result.save()?;
+            .collect::<Vec<_>>();
+        for (id, crc) in asset_crc {
+            result.cache.add_asset(crc, id);
+        }
+        result.cache.fingerprint = Some(StorageFingerprint::of_bytes(root, &bytes));
+        result.cache.base = base;
+        result.update_link_status();
         Ok(result)
     }
 
-    pub fn save(&mut self) -> Result<(), std::io::Error> {
+    /// Refuses to overwrite `snowflake.json` if another session wrote it
+    /// since we read it, see [`Storage::merge_external`].
+    pub fn save(&mut self) -> AppResult<()> {
+        if let Some(fingerprint) = &self.cache.fingerprint {
+            if fingerprint.is_stale(&self.cache.root)? {
+                return Err(AppError::LibraryModifiedExternally(self.cache.root.clone()));
+            }
+        }
+
+        self.overwrite()
+    }
+
+    /// Saves without checking for external modifications.
+    pub fn overwrite(&mut self) -> AppResult<()> {
         self.lib_meta.meta.last_modified = Local::now().into();
+        self.recycle_bin.sync_deleted_at();
+        sync::record(self)?;
+
+        let json = serde_json::to_vec(self)?;
+        let bytes = match &self.cache.cipher {
+            Some(cipher) => cipher.encrypt(&json)?,
+            None => json.clone(),
+        };
+        File::create(self.cache.root.join(LIBRARY_STORAGE))?.write_all(&bytes)?;
+        self.cache.fingerprint = Some(StorageFingerprint::of_bytes(&self.cache.root, &bytes));
+        self.cache.base = Some(json);
+        if let Err(err) =
+            snapshot::auto_snapshot(&self.cache.root, &bytes, self.cache.snapshot_policy)
+        {
+            log::error!("Failed to take snapshot: {}", err);
+        }
+        Ok(())
+    }
+
+    /// `snowflake.json` as written to disk, snapshots share the format.
+    pub fn to_bytes(&self) -> AppResult<Vec<u8>> {
+        let bytes = serde_json::to_vec(self)?;
+        match &self.cache.cipher {
+            Some(cipher) => cipher.encrypt(&bytes),
+            None => Ok(bytes),
+        }
+    }
+
+    /// Three-way merges what another session wrote to `snowflake.json` into
+    /// this one, against the version we last read or wrote. Items changed on
+    /// one side only take that side's version, including deletions for good.
+    /// Items changed on both sides keep the local version.
+    pub fn merge_external(&mut self) -> AppResult<()> {
+        let external = serde_json::from_slice::<Storage>(&crypto::read_file(
+            &self.cache.root.join(LIBRARY_STORAGE),
+            self.cache.cipher.as_deref(),
+        )?)?;
+        let base = match &self.cache.base {
+            Some(base) => Some(serde_json::from_slice::<Storage>(base)?),
+            None => None,
+        };
+        let (base_assets, base_tags, base_collections) = match base {
+            Some(base) => (base.assets, base.tags, base.collections),
+            None => Default::default(),
+        };
+
+        for id in merge_items(&mut self.assets, &base_assets, external.assets) {
+            self.cache.remove_asset(id);
+            let Some(asset) = self.assets.get(&id) else {
+                continue;
+            };
+            match self.cache.compute_crc(asset) {
+                Ok(crc) => {
+                    self.cache.add_asset(crc, id);
+                }
+                Err(err) => log::warn!("Failed to read merged asset {:?}: {}", id, err),
+            }
+        }
+        merge_items(&mut self.tags, &base_tags, external.tags);
+        merge_items(
+            &mut self.collections,
+            &base_collections,
+            external.collections,
+        );
+        self.relink_tree();
+
+        let bin = external.recycle_bin;
+        self.recycle_bin.assets.extend(bin.assets);
+        self.recycle_bin.tags.extend(bin.tags);
+        self.recycle_bin.collections.extend(bin.collections);
+        self.recycle_bin
+            .assets
+            .retain(|a| self.assets.get(a).is_some_and(|a| a.is_deleted));
+        self.recycle_bin
+            .tags
+            .retain(|t| self.tags.get(t).is_some_and(|t| t.is_deleted));
+        self.recycle_bin
+            .collections
+            .retain(|c| self.collections.get(c).is_some_and(|c| c.is_deleted));
+        for (id, at) in bin.deleted_at {
+            self.recycle_bin.deleted_at.entry(id).or_insert(at);
+        }
+        for (id, cascade) in bin.cascades {
+            self.recycle_bin.cascades.entry(id).or_insert(cascade);
+        }
+
+        for folder in external.watch_folders {
+            if !self.watch_folders.iter().any(|f| f.path == folder.path) {
+                self.watch_folders.push(folder);
+            }
+        }
+
+        self.update_link_status();
+        Ok(())
+    }
+
+    /// Rebuilds `children` and `content` from the parents of collections and
+    /// tags, after a merge took them from different sides. Items whose parent
+    /// is gone move to the root collection.
+    fn relink_tree(&mut self) {
+        let root = self.sp_collections.root;
+        let parents = self
+            .collections
+            .values()
+            .filter(|c| c.id != root)
+            .map(|c| (c.id, c.parent))
+            .collect::<Vec<_>>();
+        for (id, parent) in parents {
+            let parent = parent.filter(|p| self.collections.contains_key(p));
+            if parent.is_none() {
+                self.collections.get_mut(&id).unwrap().parent = Some(root);
+            }
+        }
+        for tag in self.tags.values_mut() {
+            if !self.collections.contains_key(&tag.parent) {
+                tag.parent = root;
+            }
+        }
 
-        Ok(File::create(self.cache.root.join(LIBRARY_STORAGE))?
-            .write_all(serde_json::to_string(self)?.as_bytes())?)
+        let mut children = HashMap::<CollectionId, HashSet<CollectionId>>::default();
+        for collection in self.collections.values() {
+            if let Some(parent) = collection.parent {
+                children.entry(parent).or_default().insert(collection.id);
+            }
+        }
+        let mut content = HashMap::<CollectionId, HashSet<TagId>>::default();
+        for tag in self.tags.values() {
+            content.entry(tag.parent).or_default().insert(tag.id);
+        }
+        for collection in self.collections.values_mut() {
+            collection.children = children.remove(&collection.id).unwrap_or_default();
+            collection.content = content.remove(&collection.id).unwrap_or_default();
+        }
     }
 
     pub fn get_assets_containing_tag(&self, tag: TagId) -> AppResult<Vec<AssetId>> {
@@ -674,37 +1225,726 @@ impl Storage {
             .collect())
     }
 
-    pub fn add_assets(
-        &mut self,
-        initial_tag: Option<TagId>,
-        path: Vec<PathBuf>,
-    ) -> AppResult<DuplicateAssets> {
-        let mut asset_crc = HashMap::default();
-        let mut assets = HashMap::default();
+    pub fn add_assets(
+        &mut self,
+        initial_tag: Option<TagId>,
+        path: Vec<PathBuf>,
+        mode: ImportMode,
+    ) -> AppResult<DuplicateAssets> {
+        let mut asset_crc = HashMap::default();
+        let mut assets = HashMap::default();
+
+        for path in path {
+            collect_path(
+                &self.cache.root.clone(),
+                path,
+                &mut assets,
+                &mut asset_crc,
+                &mut None,
+                mode,
+            )?;
+        }
+
+        if let Some(initial_tag) = initial_tag.and_then(|i| self.tags.get(&i)) {
+            for asset in assets.values_mut() {
+                asset.tags.insert_unchecked(initial_tag);
+            }
+        }
+
+        self.insert_collected_assets(assets, asset_crc)
+    }
+
+    fn add_watched_assets(&mut self, path: Vec<PathBuf>) -> AppResult<WatchedImport> {
+        let root = self.cache.root.clone();
+        let mut asset_crc = HashMap::default();
+        let mut assets = HashMap::default();
+        let mut failed = Vec::new();
+
+        let mut files = Vec::new();
+        for path in path {
+            if !path.exists() || path.starts_with(&root) {
+                continue;
+            }
+            if let Err(err) = collect_files(&path, &mut files) {
+                log::error!("Failed to read watched path {:?}: {}", path, err);
+                failed.push(path);
+            }
+        }
+
+        for path in files {
+            let Some(index) = self.find_watch_folder(&path) else {
+                continue;
+            };
+            let folder = &mut self.watch_folders[index];
+
+            let mut collected = HashMap::default();
+            let result = if folder.folder_as_tag {
+                folder.prune_mappings(&self.tags, &self.collections, self.sp_collections.root);
+
+                collect_path(
+                    &root,
+                    path.clone(),
+                    &mut collected,
+                    &mut asset_crc,
+                    &mut Some(FolderAsTag {
+                        root_collection: self.sp_collections.root,
+                        tags: &mut self.tags,
+                        collections: &mut self.collections,
+                        folder_tags: &mut folder.folder_tags,
+                        folder_collections: &mut folder.folder_collections,
+                    }),
+                    ImportMode::Copy,
+                )
+            } else {
+                collect_path(
+                    &root,
+                    path.clone(),
+                    &mut collected,
+                    &mut asset_crc,
+                    &mut None,
+                    ImportMode::Copy,
+                )
+            };
+
+            if let Err(err) = result {
+                log::error!("Failed to import watched path {:?}: {}", path, err);
+                failed.push(path);
+            }
+
+            if let Some(initial_tag) = folder.initial_tag.and_then(|i| self.tags.get(&i)) {
+                for asset in collected.values_mut() {
+                    asset.tags.insert_unchecked(initial_tag);
+                }
+            }
+
+            assets.extend(collected);
+        }
+
+        Ok(WatchedImport {
+            assets: assets.keys().cloned().collect(),
+            duplication: self.insert_collected_assets(assets, asset_crc)?,
+            failed,
+        })
+    }
+
+    pub fn handle_watched_paths(&mut self, path: Vec<PathBuf>) -> AppResult<WatchedImport> {
+        let mut added = Vec::new();
+        let mut synced = HashMap::<usize, Vec<PathBuf>>::default();
+
+        for path in path {
+            if path.starts_with(&self.cache.root) {
+                continue;
+            }
+
+            match self.find_watch_folder(&path) {
+                Some(index) if self.watch_folders[index].sync.is_some() => {
+                    synced.entry(index).or_default().push(path);
+                }
+                Some(_) => added.push(path),
+                None => {}
+            }
+        }
+
+        let mut result = self.add_watched_assets(added)?;
+        for (index, path) in synced {
+            let synced = self.sync_watched_paths(index, path)?;
+            result.assets.extend(synced.assets);
+            result.duplication.0.extend(synced.duplication.0);
+            result.failed.extend(synced.failed);
+        }
+
+        Ok(result)
+    }
+
+    /// Follows a file or folder renamed within a watch folder, so its assets
+    /// aren't imported again under the new name. Synced folders match moved
+    /// files themselves, `false` leaves the rename to them.
+    pub fn rename_watched_path(&mut self, from: &Path, to: &Path) -> bool {
+        let Some(index) = self.find_watch_folder(to) else {
+            return false;
+        };
+        if self.find_watch_folder(from) != Some(index) || self.watch_folders[index].sync.is_some() {
+            return false;
+        }
+
+        let folder = &mut self.watch_folders[index];
+        rebase_paths(&mut folder.folder_tags, from, to);
+        rebase_paths(&mut folder.folder_collections, from, to);
+        true
+    }
+
+    pub fn sync_watch_folders(&mut self) -> AppResult<DuplicateAssets> {
+        let path = self
+            .watch_folders
+            .iter()
+            .filter(|f| f.sync.is_some())
+            .map(|f| f.path.clone())
+            .collect();
+
+        self.handle_watched_paths(path)
+            .map(|result| result.duplication)
+    }
+
+    fn sync_watched_paths(&mut self, index: usize, path: Vec<PathBuf>) -> AppResult<WatchedImport> {
+        let root = self.cache.root.clone();
+        let folder = &mut self.watch_folders[index];
+        folder.prune_mappings(&self.tags, &self.collections, self.sp_collections.root);
+
+        let Some(sync) = folder.sync.as_mut() else {
+            return Ok(Default::default());
+        };
+        // An unmounted drive or share would look like every file vanished.
+        if !folder.path.is_dir() {
+            log::warn!("Skipping unreachable synced folder {:?}", folder.path);
+            return Ok(Default::default());
+        }
+
+        let mut new_files = Vec::new();
+        let mut new_folders = Vec::new();
+        let mut failed = Vec::new();
+        for path in &path {
+            if path.is_dir() {
+                match read_dir(path) {
+                    Ok(mut entries) => {
+                        if entries.next().is_none() {
+                            new_folders.push(path.clone());
+                        }
+                    }
+                    Err(err) => {
+                        log::error!("Failed to read synced folder {:?}: {}", path, err);
+                        failed.push(path.clone());
+                        continue;
+                    }
+                }
+                collect_readable_files(path, &mut new_files, &mut failed);
+            } else if path.is_file() {
+                new_files.push(path.clone());
+            }
+        }
+        new_files.retain(|file| {
+            !sync.files.contains_key(file)
+                && AssetType::from_ext(&file.extension().unwrap_or_default().to_string_lossy())
+                    .is_some()
+        });
+
+        let vanished = sync
+            .files
+            .iter()
+            .filter(|(file, _)| {
+                path.iter().any(|p| file.starts_with(p))
+                    && !failed.iter().any(|f| file.starts_with(f))
+                    // Errors other than not found mean the file can't be reached.
+                    && matches!(file.try_exists(), Ok(false))
+            })
+            .map(|(file, asset)| (file.clone(), *asset))
+            .collect::<Vec<_>>();
+        let mut vanished_crc = vanished
+            .iter()
+            .filter_map(|(file, asset)| {
+                self.cache
+                    .asset_crc
+                    .get(asset)
+                    .map(|crc| (*crc, (file.clone(), *asset)))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut folder_as_tag = Some(FolderAsTag {
+            root_collection: self.sp_collections.root,
+            tags: &mut self.tags,
+            collections: &mut self.collections,
+            folder_tags: &mut folder.folder_tags,
+            folder_collections: &mut folder.folder_collections,
+        });
+
+        for new_folder in new_folders {
+            folder_as_tag
+                .as_mut()
+                .unwrap()
+                .resolve_folder_tag(&new_folder);
+        }
+
+        let mut affected = Vec::new();
+        let mut assets = HashMap::default();
+        let mut asset_crc = HashMap::default();
+        for file in new_files {
+            let crc = match read(&file) {
+                Ok(bytes) => crc32fast::hash(&bytes),
+                Err(err) => {
+                    log::error!("Failed to read synced file {:?}: {}", file, err);
+                    failed.push(file);
+                    continue;
+                }
+            };
+
+            if let Some((old_file, id)) = vanished_crc.remove(&crc) {
+                sync.files.remove(&old_file);
+                sync.files.insert(file.clone(), id);
+                if sync.written.remove(&old_file) {
+                    sync.written.insert(file.clone());
+                }
+
+                if let Some(asset) = self.assets.get_mut(&id) {
+                    let folder_as_tag = folder_as_tag.as_mut().unwrap();
+                    if let Some(old_tag) = old_file
+                        .parent()
+                        .and_then(|p| folder_as_tag.folder_tags.get(p))
+                        .and_then(|t| folder_as_tag.tags.get(t))
+                    {
+                        asset.tags.remove(old_tag);
+                    }
+                    let tag = folder_as_tag.resolve_folder_tag(file.parent().unwrap());
+                    asset.tags.insert_unchecked(&tag);
+                }
+
+                affected.push(id);
+                continue;
+            }
+
+            let mut collected = HashMap::default();
+            if let Err(err) = collect_path(
+                &root,
+                file.clone(),
+                &mut collected,
+                &mut asset_crc,
+                &mut folder_as_tag,
+                ImportMode::Copy,
+            ) {
+                log::error!("Failed to import synced file {:?}: {}", file, err);
+                failed.push(file.clone());
+            }
+
+            for id in collected.keys() {
+                sync.files.insert(file.clone(), *id);
+                affected.push(*id);
+            }
+            assets.extend(collected);
+        }
+
+        let mut recycled = Vec::new();
+        for (file, id) in vanished {
+            if sync.files.get(&file) != Some(&id) {
+                continue;
+            }
+            sync.files.remove(&file);
+            sync.written.remove(&file);
+
+            let folder_as_tag = folder_as_tag.as_mut().unwrap();
+            if let Some(asset) = self.assets.get_mut(&id) {
+                if let Some(tag) = file
+                    .parent()
+                    .and_then(|p| folder_as_tag.folder_tags.get(p))
+                    .and_then(|t| folder_as_tag.tags.get(t))
+                {
+                    asset.tags.remove(tag);
+                }
+            }
+
+            if !sync.files.values().any(|a| *a == id) {
+                recycled.push(id);
+            }
+            affected.push(id);
+        }
+
+        if let Some(initial_tag) = folder.initial_tag.and_then(|i| self.tags.get(&i)) {
+            for asset in assets.values_mut() {
+                asset.tags.insert_unchecked(initial_tag);
+            }
+        }
+
+        for id in recycled {
+            if self.assets.get(&id).is_some_and(|a| !a.is_deleted) {
+                self.move_asset_to_recycle_bin(id)?;
+            }
+        }
+
+        let collected = assets.keys().cloned().collect::<HashSet<_>>();
+        let duplication = match self.insert_collected_assets(assets, asset_crc) {
+            Ok(duplication) => duplication,
+            Err(err) => {
+                // Forgotten, so the files are imported again on the next sync.
+                if let Some(sync) = self.watch_folders[index].sync.as_mut() {
+                    sync.files.retain(|_, id| !collected.contains(id));
+                }
+                return Err(err);
+            }
+        };
+
+        Ok(WatchedImport {
+            assets: affected,
+            duplication,
+            failed,
+        })
+    }
+
+    fn write_back_tag(&mut self, asset: AssetId, tag: TagId, added: bool) -> AppResult<()> {
+        // Written back files would be decrypted copies.
+        if self.cache.cipher.is_some() {
+            return Ok(());
+        }
+
+        let Some(asset) = self.assets.get(&asset) else {
+            return Err(AppError::AssetNotFound(asset));
+        };
+        let src = asset.get_file_path(&self.cache.root);
+
+        for folder in &mut self.watch_folders {
+            let Some(sync) = folder.sync.as_mut().filter(|s| s.write_back) else {
+                continue;
+            };
+            let Some(dir) = folder
+                .folder_tags
+                .iter()
+                .find(|(_, t)| **t == tag)
+                .map(|(p, _)| p.clone())
+            else {
+                continue;
+            };
+
+            let linked = sync
+                .files
+                .iter()
+                .filter(|(f, a)| **a == asset.id && f.parent() == Some(dir.as_path()))
+                .map(|(f, _)| f.clone())
+                .collect::<Vec<_>>();
+
+            if added {
+                if !linked.is_empty() {
+                    continue;
+                }
+
+                let mut target = dir.join(asset.gen_file_name().as_ref());
+                if target.exists() {
+                    target = dir.join(asset.get_file_name().as_ref());
+                }
+                if hard_link(&src, &target).is_err() {
+                    copy(&src, &target)?;
+                }
+                sync.written.insert(target.clone());
+                sync.files.insert(target, asset.id);
+            } else {
+                for file in linked {
+                    // The user's own files stay, and stay tracked so they
+                    // aren't imported again.
+                    if !sync.written.remove(&file) {
+                        log::info!("Keeping {:?} which wasn't written back", file);
+                        continue;
+                    }
+                    sync.files.remove(&file);
+                    if file.exists() {
+                        remove_file(&file)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn find_watch_folder(&self, path: &Path) -> Option<usize> {
+        self.watch_folders
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| path.starts_with(&f.path))
+            .max_by_key(|(_, f)| f.path.components().count())
+            .map(|(index, _)| index)
+    }
+
+    /// Nothing is added if a copy can't be encrypted, the copies of the
+    /// whole batch are removed instead of staying in the library readable.
+    pub fn insert_collected_assets(
+        &mut self,
+        mut assets: HashMap<AssetId, Asset>,
+        asset_crc: HashMap<AssetId, u32>,
+    ) -> AppResult<DuplicateAssets> {
+        let copies = assets
+            .values()
+            .filter(|asset| asset.link.is_none())
+            .map(|asset| asset.get_storage_path(&self.cache.root))
+            .collect::<Vec<_>>();
+        if let Err(err) = copies
+            .iter()
+            .try_for_each(|path| self.cache.seal_file(path))
+        {
+            log::error!("Failed to encrypt imported assets: {}", err);
+            for path in &copies {
+                let _ = remove_file(path);
+            }
+            return Err(err);
+        }
+
+        for asset in assets.values_mut() {
+            self.map_embedded_metadata(asset);
+            if self.cache.aigc_detect {
+                asset.detect_aigc(&self.cache);
+            }
+        }
+        for (asset, crc) in &asset_crc {
+            self.cache.add_asset(*crc, *asset);
+        }
+        self.assets.extend(assets);
+        let duplication = self
+            .cache
+            .get_duplications(asset_crc.values().cloned().collect());
+
+        Ok(DuplicateAssets(duplication))
+    }
+
+    pub fn find_or_create_tag(&mut self, collection: CollectionId, name: &str) -> TagId {
+        let existing = self.collections[&collection]
+            .content
+            .iter()
+            .filter_map(|t| self.tags.get(t))
+            .find(|t| !t.is_deleted && t.name.as_ref() == name)
+            .map(|t| t.id);
+
+        existing.unwrap_or_else(|| {
+            let tag = Tag::new(name.into(), collection);
+            let id = tag.id;
+            self.collections
+                .get_mut(&collection)
+                .unwrap()
+                .content
+                .insert(id);
+            self.tags.insert(id, tag);
+            id
+        })
+    }
+
+    pub fn find_or_create_collection(&mut self, parent: CollectionId, name: &str) -> CollectionId {
+        let existing = self.collections[&parent]
+            .children
+            .iter()
+            .filter_map(|c| self.collections.get(c))
+            .find(|c| !c.is_deleted && c.name.as_ref() == name)
+            .map(|c| c.id);
+
+        existing.unwrap_or_else(|| {
+            let collection = Collection::new(Some(parent), None, name.into());
+            let id = collection.id;
+            self.collections
+                .get_mut(&parent)
+                .unwrap()
+                .children
+                .insert(id);
+            self.collections.insert(id, collection);
+            id
+        })
+    }
+
+    /// Turns embedded keywords into tags when the library asks for it.
+    pub fn map_embedded_metadata(&mut self, asset: &mut Asset) {
+        let Some(embedded) = asset.embedded_meta.clone() else {
+            return;
+        };
+        let mut tags = Vec::new();
+
+        if self.metadata_mapping.hierarchical_tags {
+            for path in &embedded.hierarchical_keywords {
+                let Some((tag, collections)) = path.split_last() else {
+                    continue;
+                };
+                // Tags can't live in the root collection.
+                if collections.is_empty() {
+                    continue;
+                }
+
+                let mut parent = self.sp_collections.root;
+                for name in collections {
+                    parent = self.find_or_create_collection(parent, name);
+                }
+                tags.push(self.find_or_create_tag(parent, tag));
+            }
+        }
+
+        if let Some(collection) = self
+            .metadata_mapping
+            .keyword_collection
+            .filter(|c| self.collections.contains_key(c))
+        {
+            for keyword in &embedded.keywords {
+                // Already placed through its hierarchy.
+                let mapped = self.metadata_mapping.hierarchical_tags
+                    && embedded
+                        .hierarchical_keywords
+                        .iter()
+                        .any(|path| path.len() > 1 && path.last() == Some(keyword));
+                if !mapped {
+                    tags.push(self.find_or_create_tag(collection, keyword));
+                }
+            }
+        }
+
+        for tag in tags {
+            asset.tags.insert_unchecked(&self.tags[&tag]);
+        }
+    }
+
+    pub fn refresh_embedded_metadata(&mut self, assets: &[AssetId]) -> AppResult<()> {
+        for id in assets {
+            let Some(mut asset) = self.assets.remove(id) else {
+                return Err(AppError::AssetNotFound(*id));
+            };
+
+            if let AssetType::RasterGraphics = asset.ty {
+                match self.cache.read_asset(&asset) {
+                    Ok(bytes) => {
+                        asset.set_embedded_meta(EmbeddedMetadata::extract(&bytes));
+                        self.map_embedded_metadata(&mut asset);
+                    }
+                    Err(err) => log::warn!("Failed to read asset {:?}: {}", id, err),
+                }
+            }
+
+            self.assets.insert(*id, asset);
+        }
+
+        Ok(())
+    }
+
+    pub fn detect_aigc(&mut self, assets: &[AssetId]) -> AppResult<()> {
+        for id in assets {
+            let Some(asset) = self.assets.get_mut(id) else {
+                return Err(AppError::AssetNotFound(*id));
+            };
+            asset.detect_aigc(&self.cache);
+        }
+
+        Ok(())
+    }
+
+    pub fn add_watch_folder(&mut self, mut folder: WatchFolder) -> AppResult<()> {
+        if !folder.path.is_dir()
+            || folder.path.starts_with(&self.cache.root)
+            || self.cache.root.starts_with(&folder.path)
+        {
+            return Err(AppError::InvalidWatchFolder(folder.path));
+        }
+
+        if let Some(tag) = folder.initial_tag {
+            if !self.tags.contains_key(&tag) {
+                return Err(AppError::TagNotFound(tag));
+            }
+        }
+
+        if folder.sync.as_ref().is_some_and(|s| s.write_back) && self.cache.cipher.is_some() {
+            return Err(AppError::EncryptedLibrarySync);
+        }
+
+        if folder.sync.is_some() {
+            folder.folder_as_tag = true;
+        }
+
+        self.watch_folders.retain(|f| f.path != folder.path);
+        self.watch_folders.push(folder);
+        Ok(())
+    }
+
+    pub fn update_link_status(&mut self) {
+        for asset in self.assets.values_mut() {
+            let Some(link) = &mut asset.link else {
+                continue;
+            };
+
+            // The crc recorded when linking is kept, it is what relinking
+            // looks for.
+            link.status = match self.cache.asset_crc.get(&asset.id) {
+                Some(crc) if *crc == link.crc => LinkStatus::Available,
+                Some(_) => LinkStatus::Modified,
+                None => LinkStatus::Missing,
+            };
+        }
+    }
+
+    pub fn relink_assets(&mut self, search_root: &Path) -> AppResult<Vec<AssetId>> {
+        let mut missing = HashMap::<u32, Vec<AssetId>>::default();
+        for asset in self.assets.values() {
+            if let Some(link) = asset
+                .link
+                .as_ref()
+                .filter(|link| link.status == LinkStatus::Missing)
+            {
+                missing.entry(link.crc).or_default().push(asset.id);
+            }
+        }
+
+        let mut files = Vec::new();
+        collect_files(search_root, &mut files)?;
+
+        let mut relinked = Vec::new();
+        for file in files {
+            if missing.is_empty() {
+                break;
+            }
+
+            let ext = file.extension().unwrap_or_default().to_string_lossy();
+            if AssetType::from_ext(&ext).is_none() {
+                continue;
+            }
+
+            let crc = match read(&file) {
+                Ok(bytes) => crc32fast::hash(&bytes),
+                Err(err) => {
+                    log::warn!("Skipping unreadable file {:?}: {}", file, err);
+                    continue;
+                }
+            };
+            let Some(candidates) = missing.get_mut(&crc) else {
+                continue;
+            };
+            // Identical copies are told apart by their old file name.
+            let index = candidates
+                .iter()
+                .position(|id| {
+                    self.assets[id]
+                        .link
+                        .as_ref()
+                        .is_some_and(|l| l.path.file_name() == file.file_name())
+                })
+                .unwrap_or_default();
+            let id = candidates.swap_remove(index);
+            if candidates.is_empty() {
+                missing.remove(&crc);
+            }
+
+            let asset = self.assets.get_mut(&id).unwrap();
+            asset.link = Some(AssetLink {
+                path: std::path::absolute(&file)?,
+                crc,
+                status: LinkStatus::Available,
+            });
+            if !asset.is_deleted {
+                self.cache.add_asset(crc, id);
+            }
+            relinked.push(id);
+        }
+
+        Ok(relinked)
+    }
+
+    pub fn consolidate_asset(&mut self, id: AssetId) -> AppResult<()> {
+        let Some(asset) = self.assets.get_mut(&id) else {
+            return Err(AppError::AssetNotFound(id));
+        };
 
-        for path in path {
-            collect_path(
-                &self.cache.root.clone(),
-                path,
-                &mut assets,
-                &mut asset_crc,
-                &mut None,
-            )?;
+        if let Some(link) = &asset.link {
+            let path = asset.get_storage_path(&self.cache.root);
+            copy(&link.path, &path)?;
+            self.cache.seal_file(&path)?;
+            asset.link = None;
         }
 
-        if let Some(initial_tag) = initial_tag.and_then(|i| self.tags.get(&i)) {
-            for asset in assets.values_mut() {
-                asset.tags.insert_unchecked(initial_tag);
-            }
-        }
+        Ok(())
+    }
 
-        self.cache.asset_crc.extend(asset_crc.clone());
-        self.assets.extend(assets);
-        let duplication = self
-            .cache
-            .get_duplications(asset_crc.values().cloned().collect());
+    pub fn remove_watch_folder(&mut self, path: &Path) -> AppResult<()> {
+        let len = self.watch_folders.len();
+        self.watch_folders.retain(|f| f.path != path);
 
-        Ok(DuplicateAssets(duplication))
+        if self.watch_folders.len() == len {
+            Err(AppError::WatchFolderNotFound(path.to_path_buf()))
+        } else {
+            Ok(())
+        }
     }
 
     pub fn add_raw_assets(
@@ -715,7 +1955,15 @@ impl Storage {
         let root = self.cache.root.clone();
 
         let mut added_crc = HashSet::<u32>::default();
-        for RawAsset { bytes, ext, src } in data {
+        for RawAsset {
+            bytes,
+            ext,
+            src,
+            page,
+            name,
+            tags,
+        } in data
+        {
             let Some(ty) = AssetType::from_ext(&ext) else {
                 continue;
             };
@@ -730,11 +1978,15 @@ impl Storage {
             added_crc.insert(crc);
             self.cache.add_asset(crc, AssetId(id));
 
-            let mut file = File::create(&path)?;
-            file.write(&bytes)?;
-            file.flush()?;
+            self.cache.write_file(&path, &bytes)?;
 
-            let meta = Metadata::from_std_meta(&file.metadata()?);
+            let meta = Metadata::from_std_meta(&metadata(&path)?);
+            let embedded_meta = match ty {
+                AssetType::RasterGraphics => EmbeddedMetadata::extract(&bytes),
+                _ => None,
+            };
+            let aigc = (self.cache.aigc_detect && matches!(ty, AssetType::RasterGraphics))
+                .then(|| AigcDetection::detect(&bytes, embedded_meta.as_ref()));
             let props = match ty {
                 AssetType::RasterGraphics => {
                     let size = imagesize::blob_size(&bytes)?;
@@ -756,13 +2008,23 @@ impl Storage {
                 }
             };
 
+            let name = name
+                .map(Into::into)
+                .unwrap_or_else(|| id.to_string().into());
             let mut asset = Asset {
                 id: AssetId(id),
-                ..Asset::new(id.to_string().into(), ext.into(), meta, ty, props, src)
+                source_page: page,
+                aigc,
+                ..Asset::new(name, ext, meta, ty, props, src)
             };
 
-            if let Some(initial_tag) = initial_tag.and_then(|i| self.tags.get(&i)) {
-                asset.tags.insert_unchecked(initial_tag);
+            asset.set_embedded_meta(embedded_meta);
+            self.map_embedded_metadata(&mut asset);
+
+            for tag in initial_tag.iter().chain(&tags) {
+                if let Some(tag) = self.tags.get(tag) {
+                    asset.tags.insert_unchecked(tag);
+                }
             }
 
             self.assets.insert(asset.id, asset);
@@ -785,6 +2047,149 @@ impl Storage {
         }
     }
 
+    /// Removes the tag from every asset, returning where it was.
+    fn unassign_tag(&mut self, id: TagId) -> Vec<TagAssignment> {
+        let Some(tag) = self.tags.get(&id) else {
+            return Vec::new();
+        };
+
+        let mut assignments = Vec::new();
+        for asset in self.assets.values_mut() {
+            if asset.tags.contains(tag) {
+                asset.tags.remove(tag);
+                assignments.push(TagAssignment {
+                    asset: asset.id,
+                    tag: id,
+                });
+            }
+        }
+        assignments
+    }
+
+    /// Soft deletes what is still alive under the collection.
+    fn cascade_collection(&mut self, id: CollectionId, cascade: &mut Cascade) {
+        let Some(collection) = self.collections.get(&id) else {
+            return;
+        };
+        let children = collection.children.iter().copied().collect::<Vec<_>>();
+        let content = collection.content.iter().copied().collect::<Vec<_>>();
+
+        for tag in content {
+            match self.tags.get_mut(&tag) {
+                Some(data) if !data.is_deleted => data.is_deleted = true,
+                _ => continue,
+            }
+            cascade.tags.push(tag);
+            cascade.assignments.extend(self.unassign_tag(tag));
+        }
+
+        for child in children {
+            match self.collections.get_mut(&child) {
+                Some(data) if !data.is_deleted => data.is_deleted = true,
+                _ => continue,
+            }
+            cascade.collections.push(child);
+            self.cascade_collection(child, cascade);
+        }
+    }
+
+    fn restore_cascade(&mut self, id: Uuid, resolve: TagGroupConflictResolve) {
+        let Some(cascade) = self.recycle_bin.cascades.remove(&id) else {
+            return;
+        };
+
+        for collection in cascade.collections {
+            if let Some(collection) = self.collections.get_mut(&collection) {
+                collection.is_deleted = false;
+            }
+        }
+        for tag in cascade.tags {
+            if let Some(tag) = self.tags.get_mut(&tag) {
+                tag.is_deleted = false;
+            }
+        }
+        for TagAssignment { asset, tag } in cascade.assignments {
+            if let (Some(asset), Some(tag)) = (self.assets.get_mut(&asset), self.tags.get(&tag)) {
+                asset.tags.insert(tag, resolve);
+            }
+        }
+    }
+
+    fn recycle_bin_node(&self, item: ItemId, cascaded: &HashSet<Uuid>) -> Option<RecycleBinNode> {
+        let bin = &self.recycle_bin;
+        let node = match item {
+            ItemId::Asset(id) => RecycleBinNode {
+                item,
+                name: self.assets.get(&id)?.name.to_string(),
+                deleted_at: bin.deleted_at.get(&id.0).copied(),
+                assignments: 0,
+                children: Vec::new(),
+            },
+            ItemId::Tag(id) => RecycleBinNode {
+                item,
+                name: self.tags.get(&id)?.name.to_string(),
+                deleted_at: bin.deleted_at.get(&id.0).copied(),
+                assignments: bin
+                    .cascades
+                    .values()
+                    .flat_map(|c| &c.assignments)
+                    .filter(|a| a.tag == id)
+                    .count(),
+                children: Vec::new(),
+            },
+            ItemId::Collection(id) => {
+                let collection = self.collections.get(&id)?;
+                let children = collection
+                    .children
+                    .iter()
+                    .filter(|c| cascaded.contains(&c.0))
+                    .map(|c| ItemId::Collection(*c))
+                    .chain(
+                        collection
+                            .content
+                            .iter()
+                            .filter(|t| cascaded.contains(&t.0))
+                            .map(|t| ItemId::Tag(*t)),
+                    )
+                    .filter_map(|item| self.recycle_bin_node(item, cascaded))
+                    .collect();
+
+                RecycleBinNode {
+                    item,
+                    name: collection.name.to_string(),
+                    deleted_at: bin.deleted_at.get(&id.0).copied(),
+                    assignments: 0,
+                    children,
+                }
+            }
+        };
+
+        Some(node)
+    }
+
+    /// The binned items with what their deletion took along underneath.
+    pub fn get_recycle_bin_tree(&self) -> Vec<RecycleBinNode> {
+        let bin = &self.recycle_bin;
+        let cascaded = bin
+            .cascades
+            .values()
+            .flat_map(|c| {
+                c.collections
+                    .iter()
+                    .map(|c| c.0)
+                    .chain(c.tags.iter().map(|t| t.0))
+            })
+            .collect::<HashSet<_>>();
+
+        bin.collections
+            .iter()
+            .map(|c| ItemId::Collection(*c))
+            .chain(bin.tags.iter().map(|t| ItemId::Tag(*t)))
+            .chain(bin.assets.iter().map(|a| ItemId::Asset(*a)))
+            .filter_map(|item| self.recycle_bin_node(item, &cascaded))
+            .collect()
+    }
+
     pub fn move_collection_to_recycle_bin(&mut self, id: CollectionId) -> AppResult<()> {
         if let Some(parent) = self.collections.get(&id).map(|c| c.parent) {
             if let Some(parent) = parent.and_then(|p| self.collections.get_mut(&p)) {
@@ -792,6 +2197,9 @@ impl Storage {
             }
 
             self.collections.get_mut(&id).unwrap().is_deleted = true;
+            let mut cascade = Cascade::default();
+            self.cascade_collection(id, &mut cascade);
+            self.recycle_bin.cascades.insert(id.0, cascade);
             self.recycle_bin.collections.insert(id);
 
             Ok(())
@@ -809,6 +2217,14 @@ impl Storage {
             }
 
             tag.is_deleted = true;
+            let assignments = self.unassign_tag(id);
+            self.recycle_bin.cascades.insert(
+                id.0,
+                Cascade {
+                    assignments,
+                    ..Default::default()
+                },
+            );
             self.recycle_bin.tags.insert(id);
 
             Ok(())
@@ -817,10 +2233,152 @@ impl Storage {
         }
     }
 
+    pub fn get_recycle_bin_info(&self) -> RecycleBinInfo {
+        let bin = &self.recycle_bin;
+        let entries = bin
+            .assets
+            .iter()
+            .map(|a| (ItemId::Asset(*a), a.0))
+            .chain(
+                bin.collections
+                    .iter()
+                    .map(|c| (ItemId::Collection(*c), c.0)),
+            )
+            .chain(bin.tags.iter().map(|t| (ItemId::Tag(*t), t.0)))
+            .map(|(item, id)| RecycleBinEntry {
+                item,
+                deleted_at: bin.deleted_at.get(&id).copied(),
+            })
+            .collect();
+
+        RecycleBinInfo {
+            entries,
+            reclaimable_bytes: bin
+                .assets
+                .iter()
+                .filter_map(|a| self.assets.get(a))
+                .map(|a| self.size_on_disk(a))
+                .sum(),
+        }
+    }
+
+    /// What removing the asset file frees, which differs from the size at
+    /// import for sealed files and files modified since. Linked files are
+    /// never removed.
+    fn size_on_disk(&self, asset: &Asset) -> u64 {
+        if asset.link.is_some() {
+            return 0;
+        }
+        metadata(asset.get_file_path(&self.cache.root))
+            .map(|m| m.len())
+            .unwrap_or_default()
+    }
+
+    /// Deletes for good what has been in the bin for at least `older_than`,
+    /// or everything if omitted.
+    pub fn empty_recycle_bin(
+        &mut self,
+        older_than: Option<chrono::Duration>,
+    ) -> AppResult<RecycleBinPurge> {
+        self.recycle_bin.sync_deleted_at();
+        let bin = &self.recycle_bin;
+        let assets = bin
+            .assets
+            .iter()
+            .filter(|a| bin.is_expired(a.0, older_than))
+            .copied()
+            .collect::<Vec<_>>();
+        let collections = bin
+            .collections
+            .iter()
+            .filter(|c| bin.is_expired(c.0, older_than))
+            .copied()
+            .collect::<Vec<_>>();
+        let tags = bin
+            .tags
+            .iter()
+            .filter(|t| bin.is_expired(t.0, older_than))
+            .copied()
+            .collect::<Vec<_>>();
+
+        let mut purge = RecycleBinPurge::default();
+        for id in assets {
+            if let Some(asset) = self.assets.get(&id) {
+                purge.freed_bytes += self.size_on_disk(asset);
+                match self.delete_asset(id) {
+                    // Already gone from the disk, the record is removed anyway.
+                    Err(AppError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {}
+                    result => result?,
+                }
+                purge.assets += 1;
+            }
+            self.recycle_bin.assets.remove(&id);
+        }
+        // Deleting a collection takes its tags and children along.
+        for id in collections {
+            if self.collections.contains_key(&id) {
+                self.delete_collection(id)?;
+                purge.collections += 1;
+            }
+            self.recycle_bin.collections.remove(&id);
+        }
+        // Binned on their own before their parent was purged, they would
+        // point to a collection which is gone.
+        let root = self.sp_collections.root;
+        let orphans = self
+            .recycle_bin
+            .collections
+            .iter()
+            .filter(|c| {
+                self.collections
+                    .get(*c)
+                    .and_then(|c| c.parent)
+                    .is_some_and(|p| !self.collections.contains_key(&p))
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        for id in orphans {
+            self.collections.get_mut(&id).unwrap().parent = Some(root);
+        }
+        for id in &self.recycle_bin.tags {
+            if let Some(tag) = self.tags.get_mut(id) {
+                if !self.collections.contains_key(&tag.parent) {
+                    tag.parent = root;
+                }
+            }
+        }
+        for id in tags {
+            if self.tags.contains_key(&id) {
+                self.delete_tag(id)?;
+                purge.tags += 1;
+            }
+            self.recycle_bin.tags.remove(&id);
+        }
+
+        self.recycle_bin.sync_deleted_at();
+        Ok(purge)
+    }
+
+    /// Empties what outlived `library.recycleBinRetention`, if set.
+    pub fn auto_purge_recycle_bin(&mut self) -> AppResult<RecycleBinPurge> {
+        if self.cache.recycle_bin_retention == 0 {
+            return Ok(RecycleBinPurge::default());
+        }
+
+        self.empty_recycle_bin(Some(chrono::Duration::days(
+            self.cache.recycle_bin_retention as i64,
+        )))
+    }
+
     pub fn delete_asset(&mut self, id: AssetId) -> AppResult<()> {
         if let Some(asset) = self.assets.remove(&id) {
             self.cache.remove_asset(asset.id);
-            remove_file(asset.get_file_path(&self.cache.root))?;
+            if asset.link.is_none() {
+                remove_file(asset.get_file_path(&self.cache.root))?;
+            }
+            if let Err(err) = rendition::remove_renditions(&self.cache.root, asset.id) {
+                log::warn!("Failed to remove renditions of {:?}: {}", asset.id, err);
+            }
 
             Ok(())
         } else {
@@ -875,8 +2433,14 @@ impl Storage {
                 .get_mut(&asset_id)
                 .ok_or_else(|| AppError::AssetNotFound(asset_id))?;
             asset.is_deleted = false;
-            self.cache
-                .add_asset(asset.compute_crc(&self.cache.root)?, asset.id);
+            if asset
+                .link
+                .as_ref()
+                .is_none_or(|l| l.status != LinkStatus::Missing)
+            {
+                self.cache
+                    .add_asset(self.cache.compute_crc(asset)?, asset.id);
+            }
 
             self.recycle_bin.assets.remove(&asset.id);
         }
@@ -884,26 +2448,56 @@ impl Storage {
         Ok(DuplicateAssets::default())
     }
 
+    /// Checks that items can be recovered into `parent`, which must be alive
+    /// and must not lie below the recovered collection `item`.
+    fn validate_recovery_parent(
+        &self,
+        parent: CollectionId,
+        item: Option<CollectionId>,
+    ) -> AppResult<()> {
+        if self.collections.get(&parent).is_none_or(|c| c.is_deleted) {
+            return Err(AppError::CollectionNotFound(parent));
+        }
+
+        let mut ancestor = Some(parent);
+        while let Some(id) = ancestor {
+            if Some(id) == item {
+                return Err(AppError::IllegalCollectionModification(id));
+            }
+            ancestor = self.collections.get(&id).and_then(|c| c.parent);
+        }
+        Ok(())
+    }
+
     pub fn recover_collections(
         &mut self,
         collections: Vec<CollectionId>,
         parent: Option<CollectionId>,
+        resolve: TagGroupConflictResolve,
     ) -> AppResult<()> {
         for collection_id in collections {
-            let collection = self
+            let old_parent = self
                 .collections
-                .get_mut(&collection_id)
-                .ok_or_else(|| AppError::CollectionNotFound(collection_id))?;
-            collection.is_deleted = false;
-            self.recycle_bin.collections.remove(&collection.id);
+                .get(&collection_id)
+                .ok_or_else(|| AppError::CollectionNotFound(collection_id))?
+                .parent;
+            let parent = parent.or(old_parent);
+            if let Some(parent) = parent {
+                self.validate_recovery_parent(parent, Some(collection_id))?;
+            }
 
-            if let Some(parent) = parent.or(collection.parent) {
-                let parent = self
-                    .collections
-                    .get_mut(&parent)
-                    .ok_or_else(|| AppError::CollectionNotFound(parent))?;
+            // Otherwise purging the old parent would take it along.
+            if let Some(old_parent) = old_parent.and_then(|p| self.collections.get_mut(&p)) {
+                old_parent.children.remove(&collection_id);
+            }
+            let collection = self.collections.get_mut(&collection_id).unwrap();
+            collection.is_deleted = false;
+            collection.parent = parent;
+            if let Some(parent) = parent.and_then(|p| self.collections.get_mut(&p)) {
                 parent.children.insert(collection_id);
             }
+            self.recycle_bin.collections.remove(&collection_id);
+            self.restore_cascade(collection_id.0, resolve);
         }
 
         Ok(())
@@ -913,20 +2507,31 @@ impl Storage {
         &mut self,
         tags: Vec<TagId>,
         parent: Option<CollectionId>,
+        resolve: TagGroupConflictResolve,
     ) -> AppResult<()> {
         for tag_id in tags {
-            let tag = self
+            let old_parent = self
                 .tags
-                .get_mut(&tag_id)
-                .ok_or_else(|| AppError::TagNotFound(tag_id))?;
+                .get(&tag_id)
+                .ok_or_else(|| AppError::TagNotFound(tag_id))?
+                .parent;
+            let parent = parent.unwrap_or(old_parent);
+            self.validate_recovery_parent(parent, None)?;
+
+            // Otherwise purging the old parent would take it along.
+            if let Some(old_parent) = self.collections.get_mut(&old_parent) {
+                old_parent.content.remove(&tag_id);
+            }
+            let tag = self.tags.get_mut(&tag_id).unwrap();
             tag.is_deleted = false;
-            self.recycle_bin.tags.remove(&tag.id);
-
-            let parent = self
-                .collections
-                .get_mut(&parent.unwrap_or(tag.parent))
-                .ok_or_else(|| AppError::CollectionNotFound(tag.parent))?;
-            parent.content.insert(tag_id);
+            tag.parent = parent;
+            self.collections
+                .get_mut(&parent)
+                .unwrap()
+                .content
+                .insert(tag_id);
+            self.recycle_bin.tags.remove(&tag_id);
+            self.restore_cascade(tag_id.0, resolve);
         }
 
         Ok(())
@@ -1119,27 +2724,60 @@ impl Storage {
         tag: TagId,
         resolve: TagGroupConflictResolve,
     ) -> AppResult<()> {
-        let Some(asset) = self.assets.get_mut(&asset) else {
+        let Some(asset_data) = self.assets.get_mut(&asset) else {
             return Err(AppError::AssetNotFound(asset));
         };
-        let Some(tag) = self.tags.get(&tag) else {
+        let Some(tag_data) = self.tags.get(&tag) else {
             return Err(AppError::TagNotFound(tag));
         };
 
-        asset.tags.insert(tag, resolve);
+        let replaced = tag_data
+            .group
+            .and_then(|g| asset_data.tags.grouped.get(&g))
+            .copied()
+            .filter(|t| *t != tag);
+        asset_data.tags.insert(tag_data, resolve);
+        if !asset_data.tags.contains(tag_data) {
+            return Ok(());
+        }
+
+        if let Some(replaced) = replaced {
+            self.write_back_tag(asset, replaced, false)?;
+        }
+        self.write_back_tag(asset, tag, true)
+    }
+
+    pub fn accept_tag_suggestions(
+        &mut self,
+        asset: AssetId,
+        tags: &[TagId],
+        resolve: TagGroupConflictResolve,
+    ) -> AppResult<()> {
+        for tag in tags {
+            self.add_tag_to_asset(asset, *tag, resolve)?;
+        }
+
+        if let Some(suggestions) = self
+            .assets
+            .get_mut(&asset)
+            .and_then(|a| a.tag_suggestions.as_mut())
+        {
+            suggestions.retain(|s| !tags.contains(&s.tag));
+        }
+
         Ok(())
     }
 
     pub fn remove_tag_from_asset(&mut self, asset: AssetId, tag: TagId) -> AppResult<()> {
-        let Some(asset) = self.assets.get_mut(&asset) else {
+        let Some(asset_data) = self.assets.get_mut(&asset) else {
             return Err(AppError::AssetNotFound(asset));
         };
-        let Some(tag) = self.tags.get(&tag) else {
+        let Some(tag_data) = self.tags.get(&tag) else {
             return Err(AppError::TagNotFound(tag));
         };
 
-        asset.tags.remove(tag);
-        Ok(())
+        asset_data.tags.remove(tag_data);
+        self.write_back_tag(asset, tag, false)
     }
 
     pub fn get_asset_abs_path(&self, id: AssetId) -> AppResult<PathBuf> {
@@ -1149,6 +2787,58 @@ impl Storage {
             .ok_or_else(|| AppError::AssetNotFound(id))
     }
 
+    /// A path other apps can read, encrypted assets are decrypted into the
+    /// temp folder first.
+    pub fn get_asset_plain_path(&self, id: AssetId) -> AppResult<PathBuf> {
+        let Some(asset) = self.assets.get(&id) else {
+            return Err(AppError::AssetNotFound(id));
+        };
+        if self.cache.cipher.is_none() || asset.link.is_some() {
+            return Ok(asset.get_file_path(&self.cache.root));
+        }
+
+        // Named after the asset for the other app, in a folder of its own
+        // so assets sharing a name don't overwrite each other's copy.
+        let dir = self.plain_copies_dir().join(asset.id.0.to_string());
+        create_dir_all(&dir)?;
+        let path = dir.join(asset.gen_file_name().as_ref());
+        std::fs::write(&path, self.cache.read_asset(asset)?)?;
+        Ok(path)
+    }
+
+    /// Plaintext which must not land in an encrypted library goes below here.
+    fn temp_dir(&self) -> PathBuf {
+        let root = self.cache.root.to_string_lossy();
+        std::env::temp_dir()
+            .join("snowflake")
+            .join(format!("{:x}", md5::compute(root.as_bytes())))
+    }
+
+    fn plain_copies_dir(&self) -> PathBuf {
+        self.temp_dir().join("plain")
+    }
+
+    /// Where partial downloads are kept, outside of encrypted libraries.
+    pub fn download_dir(&self) -> AppResult<PathBuf> {
+        if self.cache.cipher.is_none() {
+            return Ok(self.cache.root.join(CACHE));
+        }
+
+        let dir = self.temp_dir().join("downloads");
+        create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    /// Removes the decrypted copies made by [`Self::get_asset_plain_path`],
+    /// called whenever the library is loaded or closed.
+    pub fn remove_plain_copies(&self) -> AppResult<()> {
+        let dir = self.plain_copies_dir();
+        if dir.exists() {
+            remove_dir_all(dir)?;
+        }
+        Ok(())
+    }
+
     pub fn get_tag_virtual_path(&self, id: TagId) -> AppResult<Vec<String>> {
         let Some(tag) = self.tags.get(&id) else {
             return Err(AppError::TagNotFound(id));
@@ -1193,6 +2883,9 @@ pub struct RawAsset {
     pub bytes: Vec<u8>,
     pub ext: Arc<str>,
     pub src: String,
+    pub page: Option<String>,
+    pub name: Option<String>,
+    pub tags: Vec<TagId>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -1415,6 +3108,80 @@ pub struct Asset {
     pub meta: Metadata,
     pub tags: TagContainer,
     pub src: String,
+    /// The page the asset was picked from, when `src` points to the media itself.
+    #[serde(default)]
+    pub source_page: Option<String>,
+    #[serde(default)]
+    pub embedded_meta: Option<EmbeddedMetadata>,
+    #[serde(default)]
+    pub aigc: Option<AigcDetection>,
+    /// Suggestions of the on-device tagger, `None` until it has run.
+    #[serde(default)]
+    pub tag_suggestions: Option<Vec<TagSuggestion>>,
+    /// Text recognized by the on-device OCR, `None` until it has run.
+    #[serde(default)]
+    pub ocr_text: Option<String>,
+    #[serde(default)]
+    pub link: Option<AssetLink>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum SearchQueryTy {
+    AssetId,
+    AssetName,
+    AssetMetadata,
+    AssetText,
+    /// Matches the verdict name, or any flagged asset when empty.
+    AigcVerdict,
+    TagId,
+    TagName,
+}
+
+impl SearchQueryTy {
+    /// Tag queries never match assets.
+    pub fn matches_asset(self, asset: &Asset, query: &str) -> bool {
+        match self {
+            Self::AssetId => asset.id.0.to_string().contains(query),
+            Self::AssetName => asset.name.to_lowercase().contains(&query.to_lowercase()),
+            Self::AssetMetadata => asset
+                .embedded_meta
+                .as_ref()
+                .is_some_and(|meta| meta.contains(query)),
+            Self::AssetText => {
+                let query = query.to_lowercase();
+                asset
+                    .ocr_text
+                    .as_ref()
+                    .is_some_and(|text| text.to_lowercase().contains(&query))
+            }
+            Self::AigcVerdict => asset.aigc.as_ref().is_some_and(|aigc| {
+                if query.is_empty() {
+                    aigc.verdict != AigcVerdict::NotDetected
+                } else {
+                    serde_json::to_value(aigc.verdict).is_ok_and(|v| v.as_str() == Some(query))
+                }
+            }),
+            Self::TagId | Self::TagName => false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum LinkStatus {
+    Available,
+    Missing,
+    /// The linked file changed since it was linked.
+    Modified,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetLink {
+    pub path: PathBuf,
+    pub crc: u32,
+    pub status: LinkStatus,
 }
 
 impl Asset {
@@ -1436,6 +3203,32 @@ impl Asset {
             meta,
             tags: Default::default(),
             src,
+            source_page: None,
+            embedded_meta: None,
+            aigc: None,
+            tag_suggestions: None,
+            ocr_text: None,
+            link: None,
+        }
+    }
+
+    pub fn set_embedded_meta(&mut self, meta: Option<EmbeddedMetadata>) {
+        if let AssetProperty::RasterGraphics(props) = &mut self.props {
+            props.camera = meta.as_ref().and_then(|m| m.camera());
+        }
+        self.embedded_meta = meta;
+    }
+
+    pub fn detect_aigc(&mut self, cache: &StorageCache) {
+        if !matches!(self.ty, AssetType::RasterGraphics) {
+            return;
+        }
+
+        match cache.read_asset(self) {
+            Ok(bytes) => {
+                self.aigc = Some(AigcDetection::detect(&bytes, self.embedded_meta.as_ref()));
+            }
+            Err(err) => log::warn!("Failed to read asset {:?}: {}", self.id, err),
         }
     }
 
@@ -1456,15 +3249,15 @@ impl Asset {
     }
 
     pub fn get_file_path(&self, root: &Path) -> PathBuf {
-        root.join(self.ty.storage_folder())
-            .join(self.get_file_name().as_ref())
+        match &self.link {
+            Some(link) => link.path.clone(),
+            None => self.get_storage_path(root),
+        }
     }
 
-    pub fn compute_crc(&self, root: &Path) -> std::io::Result<u32> {
-        Ok(crc32fast::hash(&read(
-            root.join(self.ty.storage_folder())
-                .join(self.get_file_name().as_ref()),
-        )?))
+    pub fn get_storage_path(&self, root: &Path) -> PathBuf {
+        root.join(self.ty.storage_folder())
+            .join(self.get_file_name().as_ref())
     }
 }
 
@@ -1513,6 +3306,8 @@ impl AssetProperty {
 pub struct RasterGraphicsProperty {
     pub width: u32,
     pub height: u32,
+    #[serde(default)]
+    pub camera: Option<String>,
 }
 
 impl RasterGraphicsProperty {
@@ -1520,6 +3315,7 @@ impl RasterGraphicsProperty {
         Self {
             width: size.width as u32,
             height: size.height as u32,
+            camera: None,
         }
     }
 }
@@ -1743,6 +3539,102 @@ impl Metadata {
     }
 }
 
+/// Moves the entries at or under `from` to `to`.
+fn rebase_paths<T>(map: &mut HashMap<PathBuf, T>, from: &Path, to: &Path) {
+    let moved = map
+        .keys()
+        .filter(|path| path.starts_with(from))
+        .cloned()
+        .collect::<Vec<_>>();
+    for path in moved {
+        let value = map.remove(&path).unwrap();
+        match path.strip_prefix(from) {
+            Ok(rest) if !rest.as_os_str().is_empty() => map.insert(to.join(rest), value),
+            _ => map.insert(to.to_path_buf(), value),
+        };
+    }
+}
+
+/// Merges `remote` into `local` item by item, see [`Storage::merge_external`].
+/// Returns the items taken from `remote`.
+fn merge_items<K, V>(
+    local: &mut HashMap<K, V>,
+    base: &HashMap<K, V>,
+    mut remote: HashMap<K, V>,
+) -> Vec<K>
+where
+    K: Copy + Eq + std::hash::Hash,
+    V: Serialize,
+{
+    fn same<V: Serialize>(a: Option<&V>, b: Option<&V>) -> bool {
+        match (a, b) {
+            (None, None) => true,
+            (Some(a), Some(b)) => serde_json::to_value(a).ok() == serde_json::to_value(b).ok(),
+            _ => false,
+        }
+    }
+
+    let ids = local
+        .keys()
+        .chain(base.keys())
+        .chain(remote.keys())
+        .copied()
+        .collect::<HashSet<_>>();
+
+    let mut taken = Vec::new();
+    for id in ids {
+        let base = base.get(&id);
+        if !same(local.get(&id), base) || same(remote.get(&id), base) {
+            continue;
+        }
+
+        match remote.remove(&id) {
+            Some(item) => local.insert(id, item),
+            None => local.remove(&id),
+        };
+        taken.push(id);
+    }
+    taken
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if path.is_dir() {
+        for entry in read_dir(path)? {
+            collect_files(&entry?.path(), files)?;
+        }
+    } else if path.is_file() {
+        files.push(path.to_path_buf());
+    }
+
+    Ok(())
+}
+
+/// Like [`collect_files`], but skips folders which can't be read and records
+/// them in `failed`.
+fn collect_readable_files(path: &Path, files: &mut Vec<PathBuf>, failed: &mut Vec<PathBuf>) {
+    if path.is_dir() {
+        let entries = match read_dir(path) {
+            Ok(entries) => entries,
+            Err(err) => {
+                log::error!("Failed to read synced folder {:?}: {}", path, err);
+                failed.push(path.to_path_buf());
+                return;
+            }
+        };
+        for entry in entries {
+            match entry {
+                Ok(entry) => collect_readable_files(&entry.path(), files, failed),
+                Err(err) => {
+                    log::error!("Failed to read synced folder {:?}: {}", path, err);
+                    failed.push(path.to_path_buf());
+                }
+            }
+        }
+    } else if path.is_file() {
+        files.push(path.to_path_buf());
+    }
+}
+
 fn validate_library(root_folder: impl AsRef<Path>, create_structure: bool) -> bool {
     let root = root_folder.as_ref();
     if root.join(LIBRARY_STORAGE).exists() || create_structure {
@@ -1755,3 +3647,233 @@ fn validate_library(root_folder: impl AsRef<Path>, create_structure: bool) -> bo
         false
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::fs::remove_dir_all;
+
+    use super::*;
+
+    pub(crate) fn empty_storage() -> Storage {
+        let root = std::env::temp_dir().join(format!("snowflake-{}", Uuid::new_v4()));
+        let storage = Storage::from_empty(root.clone()).unwrap();
+        remove_dir_all(root).unwrap();
+        storage
+    }
+
+    pub(crate) fn collection(
+        storage: &mut Storage,
+        name: &str,
+        parent: CollectionId,
+    ) -> CollectionId {
+        storage.create_collection(name.into(), parent).unwrap();
+        storage.collections[&parent]
+            .children
+            .iter()
+            .copied()
+            .find(|c| storage.collections[c].name.as_ref() == name)
+            .unwrap()
+    }
+
+    pub(crate) fn tag(storage: &mut Storage, name: &str, parent: CollectionId) -> TagId {
+        storage.create_tag(name.into(), parent).unwrap();
+        storage.collections[&parent]
+            .content
+            .iter()
+            .copied()
+            .find(|t| storage.tags[t].name.as_ref() == name)
+            .unwrap()
+    }
+
+    pub(crate) fn asset(storage: &mut Storage, tags: &[TagId]) -> AssetId {
+        let mut asset = Asset::new(
+            "asset".into(),
+            "png".into(),
+            Metadata {
+                byte_size: 0,
+                created_at: None,
+                last_modified: Local::now().into(),
+            },
+            AssetType::RasterGraphics,
+            AssetProperty::RasterGraphics(RasterGraphicsProperty {
+                width: 1,
+                height: 1,
+                camera: None,
+            }),
+            String::new(),
+        );
+        for tag in tags {
+            asset.tags.insert_unchecked(&storage.tags[tag]);
+        }
+        let id = asset.id;
+        storage.assets.insert(id, asset);
+        id
+    }
+
+    fn has_tag(storage: &Storage, asset: AssetId, tag: TagId) -> bool {
+        storage.assets[&asset].tags.contains(&storage.tags[&tag])
+    }
+
+    #[test]
+    fn collections_restore_what_they_took_along() {
+        let mut storage = empty_storage();
+        let root = storage.sp_collections.root;
+        let outer = collection(&mut storage, "outer", root);
+        let inner = collection(&mut storage, "inner", outer);
+        let (a, b) = (tag(&mut storage, "a", outer), tag(&mut storage, "b", inner));
+        let binned = tag(&mut storage, "binned", inner);
+        let asset = asset(&mut storage, &[a, b, binned]);
+
+        storage.move_tag_to_recycle_bin(binned).unwrap();
+        storage.move_collection_to_recycle_bin(outer).unwrap();
+        assert!(storage.collections[&inner].is_deleted);
+        assert!(storage.tags[&a].is_deleted && storage.tags[&b].is_deleted);
+        assert!(!has_tag(&storage, asset, a) && !has_tag(&storage, asset, b));
+
+        storage
+            .recover_collections(vec![outer], None, TagGroupConflictResolve::Override)
+            .unwrap();
+        assert!(storage.collections[&root].children.contains(&outer));
+        assert!(!storage.collections[&outer].is_deleted);
+        assert!(!storage.collections[&inner].is_deleted);
+        assert!(!storage.tags[&a].is_deleted && !storage.tags[&b].is_deleted);
+        assert!(has_tag(&storage, asset, a) && has_tag(&storage, asset, b));
+
+        // Binned on its own before, it stays in the bin.
+        assert!(storage.tags[&binned].is_deleted);
+        assert!(storage.recycle_bin.tags.contains(&binned));
+        assert!(!has_tag(&storage, asset, binned));
+        assert!(!storage.recycle_bin.cascades.contains_key(&outer.0));
+    }
+
+    #[test]
+    fn tags_restore_their_assignments() {
+        let mut storage = empty_storage();
+        let root = storage.sp_collections.root;
+        let parent = collection(&mut storage, "parent", root);
+        let tag = tag(&mut storage, "tag", parent);
+        let (tagged, untagged) = (asset(&mut storage, &[tag]), asset(&mut storage, &[]));
+
+        storage.move_tag_to_recycle_bin(tag).unwrap();
+        assert!(!has_tag(&storage, tagged, tag));
+
+        storage
+            .recover_tags(vec![tag], None, TagGroupConflictResolve::Override)
+            .unwrap();
+        assert!(storage.collections[&parent].content.contains(&tag));
+        assert!(has_tag(&storage, tagged, tag));
+        assert!(!has_tag(&storage, untagged, tag));
+    }
+
+    #[test]
+    fn recovery_checks_the_parent_first() {
+        let mut storage = empty_storage();
+        let root = storage.sp_collections.root;
+        let old = collection(&mut storage, "old", root);
+        let new = collection(&mut storage, "new", root);
+        let tag = tag(&mut storage, "tag", old);
+
+        // Taken along by its collection, it can't return there on its own.
+        storage.move_collection_to_recycle_bin(old).unwrap();
+        let resolve = TagGroupConflictResolve::Override;
+        assert!(storage.recover_tags(vec![tag], None, resolve).is_err());
+        assert!(storage.tags[&tag].is_deleted);
+
+        storage.recover_tags(vec![tag], Some(new), resolve).unwrap();
+        assert!(storage.collections[&new].content.contains(&tag));
+        assert!(!storage.collections[&old].content.contains(&tag));
+
+        // Purging the old parent leaves the recovered tag alone.
+        storage.empty_recycle_bin(None).unwrap();
+        assert!(storage.tags.contains_key(&tag));
+    }
+
+    #[test]
+    fn restored_assignments_respect_taken_group_slots() {
+        let mut storage = empty_storage();
+        let root = storage.sp_collections.root;
+        let group = collection(&mut storage, "group", root);
+        let (binned, other) = (
+            tag(&mut storage, "binned", group),
+            tag(&mut storage, "other", group),
+        );
+        for tag in [binned, other] {
+            storage.tags.get_mut(&tag).unwrap().group = Some(group);
+        }
+        let asset = asset(&mut storage, &[binned]);
+
+        storage.move_tag_to_recycle_bin(binned).unwrap();
+        let other_tag = storage.tags[&other].clone();
+        let tags = &mut storage.assets.get_mut(&asset).unwrap().tags;
+        tags.insert(&other_tag, TagGroupConflictResolve::Override);
+
+        storage
+            .recover_tags(vec![binned], None, TagGroupConflictResolve::Remove)
+            .unwrap();
+        assert!(has_tag(&storage, asset, other));
+        assert!(!has_tag(&storage, asset, binned));
+    }
+
+    #[test]
+    fn purged_collections_leave_their_binned_tags_recoverable() {
+        let mut storage = empty_storage();
+        let root = storage.sp_collections.root;
+        let outer = collection(&mut storage, "outer", root);
+        let tag = tag(&mut storage, "tag", outer);
+
+        storage.move_tag_to_recycle_bin(tag).unwrap();
+        storage.move_collection_to_recycle_bin(outer).unwrap();
+        storage.recycle_bin.sync_deleted_at();
+        let long_ago = Local::now() - chrono::Duration::days(2);
+        storage
+            .recycle_bin
+            .deleted_at
+            .insert(outer.0, long_ago.into());
+
+        let purge = storage
+            .empty_recycle_bin(Some(chrono::Duration::days(1)))
+            .unwrap();
+        assert_eq!((purge.collections, purge.tags), (1, 0));
+        assert_eq!(storage.tags[&tag].parent, root);
+
+        storage
+            .recover_tags(vec![tag], None, TagGroupConflictResolve::Override)
+            .unwrap();
+        assert!(storage.collections[&root].content.contains(&tag));
+    }
+
+    #[test]
+    fn merge_takes_what_changed_on_one_side() {
+        let path = std::env::temp_dir().join(format!("snowflake-{}", Uuid::new_v4()));
+        let mut storage = Storage::from_empty(path.clone()).unwrap();
+        let root = storage.sp_collections.root;
+        let renamed = tag(&mut storage, "renamed", root);
+        let edited = tag(&mut storage, "edited", root);
+        let purged = tag(&mut storage, "purged", root);
+        let purged_asset = asset(&mut storage, &[]);
+        storage.overwrite().unwrap();
+
+        let mut remote = Storage::from_existing(&path, None).unwrap();
+        remote.rename_tag(renamed, "remote".into()).unwrap();
+        remote.rename_tag(edited, "remote".into()).unwrap();
+        remote.delete_tag(purged).unwrap();
+        remote.assets.remove(&purged_asset);
+        let remote_tag = tag(&mut remote, "remote tag", root);
+        remote.overwrite().unwrap();
+
+        storage.rename_tag(edited, "local".into()).unwrap();
+        let local_tag = tag(&mut storage, "local tag", root);
+        storage.merge_external().unwrap();
+        remove_dir_all(path).unwrap();
+
+        assert_eq!(storage.tags[&renamed].name.as_ref(), "remote");
+        // Changed on both sides, the local version wins.
+        assert_eq!(storage.tags[&edited].name.as_ref(), "local");
+        assert!(!storage.tags.contains_key(&purged));
+        assert!(!storage.assets.contains_key(&purged_asset));
+
+        let content = &storage.collections[&root].content;
+        assert!(content.contains(&remote_tag) && content.contains(&local_tag));
+        assert!(!content.contains(&purged));
+    }
+}
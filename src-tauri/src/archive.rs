@@ -0,0 +1,306 @@
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::Path,
+};
+
+use hashbrown::{HashMap, HashSet};
+use uuid::Uuid;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{
+    app::{
+        AppResult, Asset, AssetId, AssetProperty, AssetType, Collection, CollectionId,
+        DuplicateAssets, Storage, Tag, TagId, CACHE, LIBRARY_STORAGE,
+    },
+    crypto,
+    export::{self, ExportScope, ExportSettings},
+};
+
+pub const ARCHIVE_EXT: &str = "snowflake";
+
+fn render_cache_name(asset: AssetId) -> String {
+    format!("{}/{}.png", CACHE, asset.0)
+}
+
+fn asset_entry_name(asset: &Asset) -> String {
+    format!("{}/{}", asset.ty.storage_folder(), asset.get_file_name())
+}
+
+pub fn export_archive(storage: &Storage, path: &Path, scope: ExportScope) -> AppResult<()> {
+    let whole_library = matches!(scope, ExportScope::Library);
+    let assets = export::collect_scope(
+        storage,
+        &ExportSettings {
+            scope,
+            ..Default::default()
+        },
+    )?
+    .into_iter()
+    .filter(|asset| asset.get_file_path(&storage.cache.root).exists())
+    .collect::<Vec<_>>();
+
+    let tags = if whole_library {
+        storage
+            .tags
+            .values()
+            .filter(|tag| !tag.is_deleted)
+            .map(|tag| tag.id)
+            .collect::<HashSet<_>>()
+    } else {
+        assets
+            .iter()
+            .flat_map(|asset| asset.tags.grouped.values().chain(&asset.tags.ungrouped))
+            .filter(|tag| storage.tags.get(*tag).is_some_and(|t| !t.is_deleted))
+            .cloned()
+            .collect()
+    };
+
+    let mut collections: HashSet<CollectionId> = HashSet::from([storage.sp_collections.root]);
+    for tag in &tags {
+        let mut current = Some(storage.tags[tag].parent);
+        while let Some(collection) = current.and_then(|c| storage.collections.get(&c)) {
+            if !collections.insert(collection.id) {
+                break;
+            }
+            current = collection.parent;
+        }
+    }
+
+    let manifest = Storage {
+        cache: Default::default(),
+        sp_collections: storage.sp_collections,
+        tags: tags
+            .iter()
+            .map(|tag| (*tag, storage.tags[tag].clone()))
+            .collect(),
+        collections: collections
+            .iter()
+            .map(|collection| {
+                let mut collection = storage.collections[collection].clone();
+                collection.children.retain(|c| collections.contains(c));
+                collection.content.retain(|t| tags.contains(t));
+                (collection.id, collection)
+            })
+            .collect(),
+        assets: assets
+            .iter()
+            .map(|asset| {
+                (
+                    asset.id,
+                    Asset {
+                        link: None,
+                        ..(*asset).clone()
+                    },
+                )
+            })
+            .collect(),
+        recycle_bin: Default::default(),
+        lib_meta: storage.lib_meta.clone(),
+        watch_folders: Default::default(),
+        http: None,
+        metadata_mapping: Default::default(),
+    };
+
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let options = SimpleFileOptions::default();
+    // Most assets are compressed already.
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+    zip.start_file(LIBRARY_STORAGE, options)?;
+    serde_json::to_writer(&mut zip, &manifest)?;
+
+    for asset in assets {
+        // Archives are always written decrypted.
+        zip.start_file(asset_entry_name(asset), stored)?;
+        zip.write_all(&storage.cache.read_asset(asset)?)?;
+
+        let cache = storage
+            .cache
+            .root
+            .join(CACHE)
+            .join(format!("{}.png", asset.id.0));
+        if cache.exists() {
+            zip.start_file(render_cache_name(asset.id), stored)?;
+            zip.write_all(&crypto::read_file(&cache, storage.cache.cipher.as_deref())?)?;
+        }
+    }
+
+    zip.finish()?;
+    Ok(())
+}
+
+fn merge_collection(
+    storage: &mut Storage,
+    manifest: &Storage,
+    src: CollectionId,
+    dst: CollectionId,
+    collection_map: &mut HashMap<CollectionId, CollectionId>,
+) {
+    collection_map.insert(src, dst);
+
+    let Some(collection) = manifest.collections.get(&src) else {
+        return;
+    };
+
+    for child in &collection.children {
+        let Some(child) = manifest.collections.get(child) else {
+            continue;
+        };
+
+        let existing = storage.collections[&dst]
+            .children
+            .iter()
+            .filter_map(|c| storage.collections.get(c))
+            .find(|c| !c.is_deleted && c.name == child.name)
+            .map(|c| c.id);
+
+        let mapped = match existing {
+            Some(id) => id,
+            None => {
+                let collection = Collection::new(Some(dst), child.color, child.name.clone());
+                let id = collection.id;
+                storage
+                    .collections
+                    .get_mut(&dst)
+                    .unwrap()
+                    .children
+                    .insert(id);
+                storage.collections.insert(id, collection);
+                id
+            }
+        };
+
+        merge_collection(storage, manifest, child.id, mapped, collection_map);
+    }
+}
+
+pub fn import_archive(
+    storage: &mut Storage,
+    path: &Path,
+    initial_tag: Option<TagId>,
+) -> AppResult<DuplicateAssets> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let manifest = serde_json::from_reader::<_, Storage>(archive.by_name(LIBRARY_STORAGE)?)?;
+
+    let mut collection_map = HashMap::default();
+    merge_collection(
+        storage,
+        &manifest,
+        manifest.sp_collections.root,
+        storage.sp_collections.root,
+        &mut collection_map,
+    );
+
+    let mut tag_map = HashMap::<TagId, TagId>::default();
+    for tag in manifest.tags.values() {
+        let Some(parent) = collection_map.get(&tag.parent).copied() else {
+            continue;
+        };
+
+        let existing = storage.collections[&parent]
+            .content
+            .iter()
+            .filter_map(|t| storage.tags.get(t))
+            .find(|t| !t.is_deleted && t.name == tag.name)
+            .map(|t| t.id);
+
+        let mapped = match existing {
+            Some(id) => id,
+            None => {
+                let new_tag = Tag {
+                    group: tag.group.and_then(|g| collection_map.get(&g).copied()),
+                    color: tag.color,
+                    ..Tag::new(tag.name.clone(), parent)
+                };
+                let id = new_tag.id;
+                storage
+                    .collections
+                    .get_mut(&parent)
+                    .unwrap()
+                    .content
+                    .insert(id);
+                storage.tags.insert(id, new_tag);
+                id
+            }
+        };
+
+        tag_map.insert(tag.id, mapped);
+    }
+
+    let root = storage.cache.root.clone();
+    let mut assets = HashMap::default();
+    let mut asset_crc = HashMap::default();
+
+    for asset in manifest.assets.values() {
+        // The manifest is untrusted, the extension decides where the file goes.
+        let ty = match AssetType::from_ext(&asset.ext) {
+            Some(ty)
+                if matches!(
+                    (ty, &asset.props),
+                    (AssetType::RasterGraphics, AssetProperty::RasterGraphics(_))
+                        | (AssetType::VectorGraphics, AssetProperty::VectorGraphics(_))
+                        | (AssetType::GltfModel, AssetProperty::GltfModel(_))
+                ) =>
+            {
+                ty
+            }
+            _ => {
+                log::warn!(
+                    "Skipping asset {:?} of unknown type {:?}",
+                    asset.id,
+                    asset.ext
+                );
+                continue;
+            }
+        };
+        let mut new_asset = Asset {
+            id: AssetId(Uuid::new_v4()),
+            ty,
+            is_deleted: false,
+            tags: Default::default(),
+            link: None,
+            ..asset.clone()
+        };
+        let storage_path = new_asset.get_storage_path(&root);
+        if storage_path.parent() != Some(root.join(ty.storage_folder()).as_path()) {
+            log::warn!("Skipping asset {:?} outside of the library", asset.id);
+            continue;
+        }
+
+        let mut bytes = Vec::new();
+        match archive.by_name(&asset_entry_name(asset)) {
+            Ok(mut file) => {
+                file.read_to_end(&mut bytes)?;
+            }
+            Err(err) => {
+                log::warn!("Asset {:?} missing in archive: {}", asset.id, err);
+                continue;
+            }
+        }
+        storage.cache.write_file(&storage_path, &bytes)?;
+        asset_crc.insert(new_asset.id, crc32fast::hash(&bytes));
+
+        if let Ok(mut cache) = archive.by_name(&render_cache_name(asset.id)) {
+            let mut bytes = Vec::new();
+            cache.read_to_end(&mut bytes)?;
+            storage.cache.write_file(
+                &root.join(CACHE).join(format!("{}.png", new_asset.id.0)),
+                &bytes,
+            )?;
+        }
+
+        for tag in asset.tags.grouped.values().chain(&asset.tags.ungrouped) {
+            if let Some(tag) = tag_map.get(tag).and_then(|t| storage.tags.get(t)) {
+                new_asset.tags.insert_unchecked(tag);
+            }
+        }
+        if let Some(initial_tag) = initial_tag.and_then(|t| storage.tags.get(&t)) {
+            new_asset.tags.insert_unchecked(initial_tag);
+        }
+
+        assets.insert(new_asset.id, new_asset);
+    }
+
+    storage.insert_collected_assets(assets, asset_crc)
+}
@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::{AppResult, TagId, UserSettings},
+    clip::{self, ClipModel, LOGIT_SCALE},
+};
+
+const PROMPT: &str = "a picture of";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TagSuggestion {
+    pub tag: TagId,
+    pub confidence: f32,
+}
+
+pub struct AutoTagSettings {
+    pub enabled: bool,
+    pub threshold: f32,
+    pub limit: usize,
+}
+
+impl AutoTagSettings {
+    pub fn from_user_settings(settings: &UserSettings) -> Self {
+        Self {
+            enabled: settings
+                .get_as("experimental", "autoTag")
+                .unwrap_or_default(),
+            threshold: settings
+                .get_as::<f32>("experimental", "autoTagThreshold")
+                .unwrap_or(0.2),
+            limit: settings
+                .get_as::<f32>("experimental", "autoTagLimit")
+                .unwrap_or(5.0) as usize,
+        }
+    }
+}
+
+/// Zero-shot classifies the image against the names of the candidate tags,
+/// confidences are the softmax over all candidates.
+pub fn suggest_tags(
+    model: &ClipModel,
+    image: &[f32],
+    candidates: &[(TagId, String)],
+    settings: &AutoTagSettings,
+) -> AppResult<Vec<TagSuggestion>> {
+    let mut logits = Vec::with_capacity(candidates.len());
+    for (tag, name) in candidates {
+        let text = model.embed_text(&format!("{} {}", PROMPT, name))?;
+        logits.push((*tag, clip::similarity(image, &text) * LOGIT_SCALE));
+    }
+
+    let max = logits
+        .iter()
+        .map(|(_, l)| *l)
+        .fold(f32::NEG_INFINITY, f32::max);
+    let sum = logits.iter().map(|(_, l)| (l - max).exp()).sum::<f32>();
+
+    let mut suggestions = logits
+        .into_iter()
+        .map(|(tag, logit)| TagSuggestion {
+            tag,
+            confidence: (logit - max).exp() / sum,
+        })
+        .filter(|s| s.confidence >= settings.threshold)
+        .collect::<Vec<_>>();
+    suggestions.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    suggestions.truncate(settings.limit);
+
+    Ok(suggestions)
+}
@@ -0,0 +1,381 @@
+use std::{
+    io::Read,
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU32, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+};
+
+use base64::Engine;
+use file_format::FileFormat;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{
+    app::{AppData, AppError, AppResult, AssetType, DuplicateAssets, RawAsset, TagId},
+    download::{Download, DownloadSettings},
+    event::{DownloadEvent, DownloadStatus, BRIDGE_DOWNLOAD, BRIDGE_IMPORT},
+    http::HttpSettings,
+    library::Libraries,
+    watcher::save_in_background,
+};
+
+pub const DEFAULT_BRIDGE_PORT: u16 = 17420;
+
+/// Requests beyond this are turned away instead of spawning more threads.
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+
+static NEXT_DOWNLOAD_ID: AtomicU32 = AtomicU32::new(0);
+
+fn gen_token() -> String {
+    hex::encode(rand::thread_rng().gen::<[u8; 16]>())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BridgeSettings {
+    pub enabled: bool,
+    pub port: u16,
+    pub token: String,
+}
+
+impl Default for BridgeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: DEFAULT_BRIDGE_PORT,
+            token: gen_token(),
+        }
+    }
+}
+
+impl BridgeSettings {
+    pub fn regenerate_token(&mut self) {
+        self.token = gen_token();
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(tag = "ty")]
+enum CaptureContent {
+    /// A base64 encoded image picked on the page.
+    Image { data: String },
+    /// Media to be downloaded by the app itself.
+    Url { url: String },
+    /// A base64 encoded screenshot of the page.
+    Page { data: String },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Capture {
+    #[serde(flatten)]
+    content: CaptureContent,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    page_url: Option<String>,
+    #[serde(default)]
+    tags: Vec<TagId>,
+    /// Root of the target library, the active one when omitted.
+    #[serde(default)]
+    library: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BridgeTag {
+    id: TagId,
+    name: String,
+    path: Vec<String>,
+}
+
+/// Localhost server through which browser extensions and scripts submit
+/// references into the open library.
+#[derive(Default)]
+pub struct Bridge {
+    server: Option<(Arc<Server>, JoinHandle<()>)>,
+}
+
+impl Bridge {
+    pub fn refresh(&mut self, app: &AppHandle, settings: &BridgeSettings) -> AppResult<()> {
+        self.stop();
+
+        if !settings.enabled {
+            return Ok(());
+        }
+
+        let server = Server::http(SocketAddr::from((Ipv4Addr::LOCALHOST, settings.port)))
+            .map_err(|e| AppError::Bridge(e.to_string()))?;
+        let server = Arc::new(server);
+
+        let app = app.clone();
+        let token = settings.token.clone();
+        let incoming = server.clone();
+        let running = Arc::new(AtomicUsize::new(0));
+        let accept = std::thread::spawn(move || {
+            for request in incoming.incoming_requests() {
+                if running.fetch_add(1, Ordering::AcqRel) >= MAX_CONCURRENT_REQUESTS {
+                    running.fetch_sub(1, Ordering::AcqRel);
+                    respond_error(request, 503, "Too many requests.");
+                    continue;
+                }
+
+                let app = app.clone();
+                let token = token.clone();
+                let running = running.clone();
+                std::thread::spawn(move || {
+                    handle_request(&app, &token, request);
+                    running.fetch_sub(1, Ordering::AcqRel);
+                });
+            }
+        });
+        self.server = Some((server, accept));
+
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        if let Some((server, accept)) = self.server.take() {
+            server.unblock();
+            // The port is only free again once the listener is dropped.
+            drop(server);
+            if accept.join().is_err() {
+                log::error!("Bridge thread panicked.");
+            }
+        }
+    }
+}
+
+fn respond(request: Request, status: u16, body: String) {
+    let response = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap())
+        .with_header(Header::from_bytes("Access-Control-Allow-Origin", "*").unwrap())
+        .with_header(
+            Header::from_bytes(
+                "Access-Control-Allow-Headers",
+                "Authorization, Content-Type",
+            )
+            .unwrap(),
+        );
+
+    if let Err(err) = request.respond(response) {
+        log::error!("Failed to respond to bridge request: {}", err);
+    }
+}
+
+fn respond_error(request: Request, status: u16, err: impl ToString) {
+    respond(
+        request,
+        status,
+        serde_json::json!({ "error": err.to_string() }).to_string(),
+    );
+}
+
+/// Takes as long for any token of the right length.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn handle_request(app: &AppHandle, token: &str, mut request: Request) {
+    if *request.method() == Method::Options {
+        respond(request, 204, String::new());
+        return;
+    }
+
+    let authorized = request.headers().iter().any(|header| {
+        header.field.equiv("Authorization")
+            && header
+                .value
+                .as_str()
+                .strip_prefix("Bearer ")
+                .is_some_and(|t| constant_time_eq(t.as_bytes(), token.as_bytes()))
+    });
+    if !authorized {
+        respond_error(request, 401, "Unauthorized.");
+        return;
+    }
+
+    let method = request.method().clone();
+    let url = request.url().to_string();
+
+    match (method, url.as_str()) {
+        (Method::Get, "/ping") => {
+            let libraries = app.state::<Mutex<Libraries>>();
+            let name = match libraries.lock().as_deref().map(|l| l.get(None)) {
+                Ok(Some(storage)) => storage.lib_meta.name.clone(),
+                _ => return respond_error(request, 503, AppError::StorageNotInitialized),
+            };
+            respond(
+                request,
+                200,
+                serde_json::json!({ "library": name }).to_string(),
+            );
+        }
+        (Method::Get, "/tags") => {
+            let libraries = app.state::<Mutex<Libraries>>();
+            let tags = match libraries.lock().as_deref().map(|l| l.get(None)) {
+                Ok(Some(storage)) => storage
+                    .tags
+                    .values()
+                    .filter(|tag| !tag.is_deleted)
+                    .map(|tag| -> AppResult<_> {
+                        Ok(BridgeTag {
+                            id: tag.id,
+                            name: tag.name.to_string(),
+                            path: storage.get_tag_virtual_path(tag.id)?,
+                        })
+                    })
+                    .collect::<AppResult<Vec<_>>>(),
+                _ => return respond_error(request, 503, AppError::StorageNotInitialized),
+            };
+
+            match tags.and_then(|tags| Ok(serde_json::to_string(&tags)?)) {
+                Ok(body) => respond(request, 200, body),
+                Err(err) => respond_error(request, 500, err),
+            }
+        }
+        (Method::Post, "/capture") => {
+            let max_size = app
+                .state::<Mutex<AppData>>()
+                .lock()
+                .map(|data| DownloadSettings::from_user_settings(&data.settings).max_size)
+                .unwrap_or_else(|_| DownloadSettings::default().max_size);
+            if request.body_length().unwrap_or_default() as u64 > max_size {
+                return respond_error(request, 413, "Capture too large.");
+            }
+
+            let mut body = Vec::new();
+            if let Err(err) = request.as_reader().take(max_size).read_to_end(&mut body) {
+                return respond_error(request, 400, err);
+            }
+            let capture = match serde_json::from_slice::<Capture>(&body) {
+                Ok(capture) => capture,
+                Err(err) => return respond_error(request, 400, err),
+            };
+
+            match import_capture(app, capture) {
+                Ok(duplication) => respond(
+                    request,
+                    200,
+                    serde_json::json!({ "duplication": duplication }).to_string(),
+                ),
+                Err(err) => respond_error(request, 500, err),
+            }
+        }
+        _ => respond_error(request, 404, "Not found."),
+    }
+}
+
+fn decode_image(data: &str) -> AppResult<(Vec<u8>, String)> {
+    // Accept data urls as produced by `canvas.toDataURL`.
+    let data = data.split_once(',').map(|(_, d)| d).unwrap_or(data);
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .map_err(|e| AppError::Bridge(e.to_string()))?;
+    let ext = FileFormat::from_bytes(&bytes).extension().to_string();
+
+    if AssetType::from_ext(&ext).is_none() {
+        return Err(AppError::Bridge(format!(
+            "Unsupported capture format {}.",
+            ext
+        )));
+    }
+
+    Ok((bytes, ext))
+}
+
+fn import_capture(app: &AppHandle, capture: Capture) -> AppResult<Option<DuplicateAssets>> {
+    log::info!(
+        "Importing capture from bridge, page {:?}.",
+        capture.page_url
+    );
+
+    let id = NEXT_DOWNLOAD_ID.fetch_add(1, Ordering::Relaxed);
+    let send_progress = |event: DownloadEvent| {
+        let _ = app.emit(BRIDGE_DOWNLOAD, event);
+    };
+
+    let raw = match capture.content {
+        CaptureContent::Image { data } | CaptureContent::Page { data } => {
+            let (bytes, ext) = decode_image(&data)?;
+            send_progress(DownloadEvent {
+                id,
+                downloaded: bytes.len() as f32,
+                total: Some(bytes.len() as f32),
+                status: DownloadStatus::Finished,
+            });
+
+            Some(RawAsset {
+                bytes,
+                ext: ext.into(),
+                src: capture.page_url.clone().unwrap_or_default(),
+                page: capture.page_url,
+                name: capture.title,
+                tags: capture.tags,
+            })
+        }
+        CaptureContent::Url { url } => {
+            let (temp_dir, library_http) = {
+                let libraries = app.state::<Mutex<Libraries>>();
+                let libraries = libraries
+                    .lock()
+                    .map_err(|_| AppError::StorageNotInitialized)?;
+                let Some(storage) = libraries.get(capture.library.as_deref()) else {
+                    return Err(AppError::StorageNotInitialized);
+                };
+                (storage.download_dir()?, storage.http.clone())
+            };
+            let (settings, http) = {
+                let data = app.state::<Mutex<AppData>>();
+                let data = data.lock().map_err(|e| AppError::Bridge(e.to_string()))?;
+                (
+                    DownloadSettings::from_user_settings(&data.settings),
+                    HttpSettings::merged(&data.http, library_http.as_ref()),
+                )
+            };
+            let client = http.client(settings.timeout)?;
+            let downloader = Download {
+                // Ids of bridge downloads are unique on their own.
+                channel: u32::MAX,
+                client: &client,
+                settings,
+                temp_dir: &temp_dir,
+                progress: &send_progress,
+            };
+
+            tauri::async_runtime::block_on(downloader.fetch(id, &url, capture.page_url.as_deref()))
+                .map(|raw| RawAsset {
+                    name: capture.title,
+                    tags: capture.tags,
+                    ..raw
+                })
+        }
+    };
+
+    let Some(raw) = raw else {
+        // The failure has been reported through the progress events.
+        return Ok(None);
+    };
+
+    let libraries = app.state::<Mutex<Libraries>>();
+    let mut libraries = libraries
+        .lock()
+        .map_err(|_| AppError::StorageNotInitialized)?;
+    let Some(storage) = libraries.get_mut(capture.library.as_deref()) else {
+        return Err(AppError::StorageNotInitialized);
+    };
+
+    let duplication = storage.add_raw_assets(None, vec![raw])?.reduce();
+    save_in_background(app, storage)?;
+    let _ = app.emit(BRIDGE_IMPORT, &duplication);
+
+    Ok(duplication)
+}
@@ -0,0 +1,141 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use hashbrown::HashMap;
+use image::imageops::FilterType;
+use tokenizers::Tokenizer;
+use tract_onnx::prelude::*;
+
+use crate::app::{AppError, AppResult};
+
+pub const CLIP: &str = "clip";
+const VISUAL_MODEL: &str = "visual.onnx";
+const TEXTUAL_MODEL: &str = "textual.onnx";
+const TOKENIZER: &str = "tokenizer.json";
+
+const IMAGE_SIZE: usize = 224;
+const CONTEXT_LENGTH: usize = 77;
+const MEAN: [f32; 3] = [0.481_454_66, 0.457_827_5, 0.408_210_73];
+const STD: [f32; 3] = [0.268_629_54, 0.261_302_6, 0.275_777_1];
+/// The temperature CLIP was trained with, turns similarities into logits.
+pub const LOGIT_SCALE: f32 = 100.0;
+
+type Plan = TypedRunnableModel<TypedModel>;
+
+fn model_err(err: impl ToString) -> AppError {
+    AppError::Model(err.to_string())
+}
+
+fn load_plan(path: &Path, fact: InferenceFact) -> AppResult<Plan> {
+    if !path.is_file() {
+        return Err(AppError::ModelNotFound(path.to_path_buf()));
+    }
+
+    tract_onnx::onnx()
+        .model_for_path(path)
+        .and_then(|model| model.with_input_fact(0, fact))
+        .and_then(|model| model.into_optimized())
+        .and_then(|model| model.into_runnable())
+        .map_err(model_err)
+}
+
+fn normalize(mut embedding: Vec<f32>) -> Vec<f32> {
+    let norm = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        embedding.iter_mut().for_each(|x| *x /= norm);
+    }
+    embedding
+}
+
+/// Both embeddings are normalized, so the dot product is the cosine.
+pub fn similarity(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+/// A CLIP image and text encoder pair exported to ONNX, running on the CPU.
+///
+/// The visual model takes `f32[1, 3, 224, 224]` pixels and the textual one
+/// `i64[1, 77]` token ids, both output a single embedding.
+pub struct ClipModel {
+    visual: Plan,
+    textual: Plan,
+    tokenizer: Tokenizer,
+    text_cache: Mutex<HashMap<String, Arc<Vec<f32>>>>,
+}
+
+impl ClipModel {
+    pub fn load(dir: &Path) -> AppResult<Self> {
+        let tokenizer_path = dir.join(TOKENIZER);
+        if !tokenizer_path.is_file() {
+            return Err(AppError::ModelNotFound(tokenizer_path));
+        }
+
+        Ok(Self {
+            visual: load_plan(
+                &dir.join(VISUAL_MODEL),
+                f32::fact([1, 3, IMAGE_SIZE, IMAGE_SIZE]).into(),
+            )?,
+            textual: load_plan(
+                &dir.join(TEXTUAL_MODEL),
+                i64::fact([1, CONTEXT_LENGTH]).into(),
+            )?,
+            tokenizer: Tokenizer::from_file(tokenizer_path).map_err(model_err)?,
+            text_cache: Default::default(),
+        })
+    }
+
+    fn run(plan: &Plan, input: Tensor) -> AppResult<Vec<f32>> {
+        let outputs = plan.run(tvec!(input.into())).map_err(model_err)?;
+        let embedding = outputs[0]
+            .to_array_view::<f32>()
+            .map_err(model_err)?
+            .iter()
+            .copied()
+            .collect();
+        Ok(normalize(embedding))
+    }
+
+    pub fn embed_image(&self, bytes: &[u8]) -> AppResult<Vec<f32>> {
+        // Resizing to fill then cropping matches the CLIP preprocessing.
+        let image = image::load_from_memory(bytes)?
+            .resize_to_fill(IMAGE_SIZE as u32, IMAGE_SIZE as u32, FilterType::CatmullRom)
+            .to_rgb8();
+        let input =
+            tract_ndarray::Array4::from_shape_fn((1, 3, IMAGE_SIZE, IMAGE_SIZE), |(_, c, y, x)| {
+                let value = image.get_pixel(x as u32, y as u32)[c] as f32 / 255.0;
+                (value - MEAN[c]) / STD[c]
+            });
+
+        Self::run(&self.visual, input.into())
+    }
+
+    pub fn embed_text(&self, text: &str) -> AppResult<Arc<Vec<f32>>> {
+        if let Some(embedding) = self
+            .text_cache
+            .lock()
+            .ok()
+            .and_then(|c| c.get(text).cloned())
+        {
+            return Ok(embedding);
+        }
+
+        let encoding = self.tokenizer.encode(text, true).map_err(model_err)?;
+        let mut ids = encoding
+            .get_ids()
+            .iter()
+            .take(CONTEXT_LENGTH)
+            .map(|id| *id as i64)
+            .collect::<Vec<_>>();
+        ids.resize(CONTEXT_LENGTH, 0);
+        let input =
+            tract_ndarray::Array2::from_shape_vec((1, CONTEXT_LENGTH), ids).map_err(model_err)?;
+
+        let embedding = Arc::new(Self::run(&self.textual, input.into())?);
+        if let Ok(mut cache) = self.text_cache.lock() {
+            cache.insert(text.to_string(), embedding.clone());
+        }
+        Ok(embedding)
+    }
+}
@@ -0,0 +1,89 @@
+use std::path::PathBuf;
+
+use arboard::Clipboard;
+use file_format::FileFormat;
+use reqwest::Url;
+
+use crate::app::{AppError, AppResult, AssetType};
+
+pub enum ClipboardContent {
+    Files(Vec<PathBuf>),
+    Urls(Vec<String>),
+    Image { bytes: Vec<u8>, ext: String },
+}
+
+/// Sniffs the format of raw asset bytes, falling back to the given
+/// extension only when the content can't be recognized.
+pub fn sniff_ext(bytes: &[u8], fallback: Option<&str>) -> Option<String> {
+    let format = FileFormat::from_bytes(bytes);
+    let ext = format.extension();
+    if AssetType::from_ext(ext).is_some() {
+        return Some(ext.to_string());
+    }
+
+    fallback
+        .map(|ext| ext.trim_start_matches('.').to_lowercase())
+        .filter(|ext| AssetType::from_ext(ext).is_some())
+}
+
+fn encode_png(width: usize, height: usize, rgba: &[u8]) -> AppResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(rgba)?;
+
+    Ok(bytes)
+}
+
+fn parse_text(text: &str) -> Option<ClipboardContent> {
+    let mut files = Vec::new();
+    let mut urls = Vec::new();
+
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        match Url::parse(line) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => urls.push(url.to_string()),
+            Ok(url) if url.scheme() == "file" => {
+                if let Ok(path) = url.to_file_path() {
+                    files.push(path);
+                }
+            }
+            _ => {
+                let path = PathBuf::from(line);
+                if path.is_absolute() && path.exists() {
+                    files.push(path);
+                }
+            }
+        }
+    }
+
+    if !files.is_empty() {
+        Some(ClipboardContent::Files(files))
+    } else if !urls.is_empty() {
+        Some(ClipboardContent::Urls(urls))
+    } else {
+        None
+    }
+}
+
+pub fn read_clipboard() -> AppResult<ClipboardContent> {
+    let mut clipboard = Clipboard::new()?;
+
+    if let Ok(files) = clipboard.get().file_list() {
+        if !files.is_empty() {
+            return Ok(ClipboardContent::Files(files));
+        }
+    }
+
+    if let Ok(image) = clipboard.get_image() {
+        let bytes = encode_png(image.width, image.height, &image.bytes)?;
+        let ext = sniff_ext(&bytes, None).ok_or(AppError::UnsupportedClipboardContent)?;
+        return Ok(ClipboardContent::Image { bytes, ext });
+    }
+
+    clipboard
+        .get_text()
+        .ok()
+        .and_then(|text| parse_text(&text))
+        .ok_or(AppError::UnsupportedClipboardContent)
+}
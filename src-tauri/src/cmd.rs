@@ -1,27 +1,45 @@
-use std::{
-    fs::{copy, create_dir_all, write},
-    path::PathBuf,
-    sync::Mutex,
-};
+use std::{fs::remove_file, path::PathBuf, pin::pin, sync::Mutex};
 
 use base64::Engine;
 use chrono::Local;
-use file_format::{FileFormat, Kind};
-use futures::StreamExt;
+use futures::{
+    future::{AbortHandle, Abortable},
+    StreamExt,
+};
 use hashbrown::{HashMap, HashSet};
-use reqwest::Client;
+use reqwest::header::CONTENT_TYPE;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tauri::{ipc::Channel, AppHandle, Manager, State, WebviewUrl, WebviewWindowBuilder};
 
 use crate::{
     app::{
-        AppData, AppError, Asset, AssetId, AssetProperty, AssetType, Collection, CollectionId,
-        Color, DuplicateAssets, GltfPreviewCamera, IdType, Item, ItemId, ItemIds, LibraryMeta,
-        LibraryStatistics, RawAsset, RecentLib, ResourceCache, SettingsDefault, SpecialCollections,
-        Storage, StorageConstructionSettings, Tag, TagId, UserSettings, CACHE,
+        AppData, AppError, AppResult, Asset, AssetId, AssetProperty, AssetType, Collection,
+        CollectionId, Color, DuplicateAssets, FolderSync, GltfPreviewCamera, IdType, ImportMode,
+        Item, ItemId, ItemIds, LibraryMeta, LibraryStatistics, LinkStatus, MetadataMapping,
+        RawAsset, RecentLib, RecycleBinInfo, RecycleBinNode, RecycleBinPurge, ResourceCache,
+        SearchQueryTy, SettingsDefault, SpecialCollections, Storage, StorageConstructionSettings,
+        Tag, TagId, UserSettings, WatchFolder, CACHE,
     },
+    archive,
+    autotag::{self, AutoTagSettings, TagSuggestion},
+    bridge::{Bridge, BridgeSettings},
+    clipboard::{self, ClipboardContent},
+    crypto,
+    download::{self, Download, DownloadManager, DownloadSettings},
     event::{DownloadEvent, DownloadStatus},
+    export::{self, ExportScope, ExportSettings},
+    foreign,
+    http::HttpSettings,
+    library::{Libraries, LoadedLibrary, TransferMode},
+    lock::{ExternalModification, LibraryLock},
+    model::ModelCache,
+    ocr,
+    semantic::{self, SemanticMatch},
+    snapshot::{self, RestoreReport, Snapshot, SnapshotDiff},
+    sync::{self, SyncReport, SyncState, SyncStatus},
+    watcher::FolderWatcher,
+    web::{self, WebMediaCandidate, WebMediaSource},
 };
 
 #[tauri::command]
@@ -149,10 +167,17 @@ pub fn get_user_setting(
 }
 
 #[tauri::command]
-pub fn get_library_meta(storage: State<'_, Mutex<Option<Storage>>>) -> Result<LibraryMeta, String> {
+pub fn get_library_meta(
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<LibraryMeta, String> {
     log::info!("Getting library meta.");
 
-    if let Ok(Some(storage)) = storage.lock().as_deref() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
         Ok(storage.lib_meta.clone())
     } else {
         Err(AppError::StorageNotInitialized.to_string())
@@ -188,6 +213,7 @@ pub fn set_user_setting(
     value: Value,
     data: State<'_, Mutex<AppData>>,
     resource: State<'_, ResourceCache>,
+    libraries: State<'_, Mutex<Libraries>>,
     app: AppHandle,
 ) -> Result<(), String> {
     log::info!("Setting user setting {} {} {:?}.", category, item, value);
@@ -227,6 +253,12 @@ pub fn set_user_setting(
         }
     }
 
+    if let Ok(libraries) = libraries.lock().as_deref_mut() {
+        for storage in libraries.values_mut() {
+            storage.cache.apply_settings(&data.settings);
+        }
+    }
+
     data.save(app.app_handle()).map_err(|e| e.to_string())?;
 
     Ok(())
@@ -235,13 +267,40 @@ pub fn set_user_setting(
 #[tauri::command]
 pub fn load_library(
     root_folder: PathBuf,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    force: Option<bool>,
+    password: Option<String>,
+    libraries: State<'_, Mutex<Libraries>>,
     data: State<'_, Mutex<AppData>>,
+    watcher: State<'_, Mutex<FolderWatcher>>,
     app: AppHandle,
 ) -> Result<Option<DuplicateAssets>, String> {
     log::info!("Start loading library at {:?}", root_folder);
 
-    let new_storage = Storage::from_existing(&root_folder).map_err(|e| e.to_string())?;
+    let cipher = crypto::unlock(&root_folder, password.as_deref()).map_err(|e| e.to_string())?;
+    // Locked before reading, so nobody writes the library in between.
+    LibraryLock::acquire(&root_folder, force.unwrap_or_default()).map_err(|e| e.to_string())?;
+    let loaded = Storage::from_existing(&root_folder, cipher).and_then(|mut storage| {
+        crypto::resume_rotation(&mut storage)?;
+        if let Err(err) = storage.remove_plain_copies() {
+            log::warn!("Failed to remove decrypted copies: {}", err);
+        }
+        storage.sync_watch_folders()?;
+        storage.save()?;
+        Ok(storage)
+    });
+    let mut new_storage = match loaded {
+        Ok(storage) => storage,
+        Err(err) => {
+            // Unless it is loaded already, then the lock is still in use.
+            if libraries
+                .lock()
+                .is_ok_and(|l| l.get(Some(&root_folder)).is_none())
+            {
+                let _ = LibraryLock::release(&root_folder);
+            }
+            return Err(err.to_string());
+        }
+    };
 
     let mut data = data.lock().map_err(|e| e.to_string())?;
     data.recent_libs.insert(
@@ -253,9 +312,24 @@ pub fn load_library(
         },
     );
 
+    new_storage.cache.apply_settings(&data.settings);
+    let purge = new_storage
+        .auto_purge_recycle_bin()
+        .map_err(|e| e.to_string())?;
+    if purge.assets + purge.collections + purge.tags > 0 {
+        log::info!("Purged {:?} from the recycle bin.", purge);
+        new_storage.save().map_err(|e| e.to_string())?;
+    }
     let duplication = new_storage.cache.get_all_duplication();
-    let mut storage = storage.lock().map_err(|e| e.to_string())?;
-    storage.replace(new_storage);
+    watcher
+        .lock()
+        .map_err(|e| e.to_string())?
+        .refresh(&app, &new_storage.cache.root, &new_storage.watch_folders)
+        .map_err(|e| e.to_string())?;
+    libraries
+        .lock()
+        .map_err(|e| e.to_string())?
+        .insert(new_storage);
     data.save(&app).map_err(|e| e.to_string())?;
 
     Ok(DuplicateAssets(duplication).reduce())
@@ -264,13 +338,15 @@ pub fn load_library(
 #[tauri::command]
 pub fn initialize_library(
     settings: StorageConstructionSettings,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    libraries: State<'_, Mutex<Libraries>>,
     data: State<'_, Mutex<AppData>>,
+    watcher: State<'_, Mutex<FolderWatcher>>,
     app: AppHandle,
 ) -> Result<Option<DuplicateAssets>, String> {
     log::info!("Start initializing library {:?}", settings);
 
     let mut new_storage = Storage::from_constructed(settings.clone()).map_err(|e| e.to_string())?;
+    LibraryLock::acquire(&settings.root, false).map_err(|e| e.to_string())?;
     new_storage.save().map_err(|e| e.to_string())?;
 
     let mut data = data.lock().map_err(|e| e.to_string())?;
@@ -283,290 +359,1726 @@ pub fn initialize_library(
         },
     );
 
+    new_storage.cache.apply_settings(&data.settings);
     let duplication = new_storage.cache.get_all_duplication();
-
-    let mut storage = storage.lock().map_err(|e| e.to_string())?;
-    storage.replace(new_storage);
+    watcher
+        .lock()
+        .map_err(|e| e.to_string())?
+        .stop(&new_storage.cache.root);
+
+    libraries
+        .lock()
+        .map_err(|e| e.to_string())?
+        .insert(new_storage);
     data.save(&app).map_err(|e| e.to_string())?;
 
     Ok(DuplicateAssets(duplication).reduce())
 }
 
 #[tauri::command]
-pub fn save_library(storage: State<'_, Mutex<Option<Storage>>>) -> Result<(), String> {
-    log::info!("Saving library...");
+pub fn get_loaded_libraries(
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<Vec<LoadedLibrary>, String> {
+    log::info!("Getting loaded libraries.");
 
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
-        storage.save().map_err(|e| e.to_string())?;
+    Ok(libraries.lock().map_err(|e| e.to_string())?.list())
+}
+
+#[tauri::command]
+pub fn activate_library(
+    library: PathBuf,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<(), String> {
+    log::info!("Activating library {:?}.", library);
+
+    libraries
+        .lock()
+        .map_err(|e| e.to_string())?
+        .activate(&library)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn transfer_assets(
+    from: PathBuf,
+    to: PathBuf,
+    assets: Vec<AssetId>,
+    tag_map: Option<HashMap<TagId, TagId>>,
+    mode: Option<TransferMode>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<Option<DuplicateAssets>, String> {
+    log::info!(
+        "Transferring assets {:?} from {:?} to {:?}",
+        assets,
+        from,
+        to
+    );
+
+    libraries
+        .lock()
+        .map_err(|e| e.to_string())?
+        .transfer_assets(
+            &from,
+            &to,
+            &assets,
+            tag_map.unwrap_or_default(),
+            mode.unwrap_or_default(),
+        )
+        .map(|duplication| duplication.reduce())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn transfer_tags(
+    from: PathBuf,
+    to: PathBuf,
+    tags: Vec<TagId>,
+    mode: Option<TransferMode>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<HashMap<TagId, TagId>, String> {
+    log::info!("Transferring tags {:?} from {:?} to {:?}", tags, from, to);
+
+    libraries
+        .lock()
+        .map_err(|e| e.to_string())?
+        .transfer_tags(&from, &to, &tags, mode.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn save_library(
+    force: Option<bool>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<(), String> {
+    log::info!("Saving library, force: {:?}", force);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        if force.unwrap_or_default() {
+            storage.overwrite().map_err(|e| e.to_string())?;
+        } else {
+            storage.save().map_err(|e| e.to_string())?;
+        }
     }
 
     Ok(())
 }
 
 #[tauri::command]
-pub fn unload_library(storage: State<'_, Mutex<Option<Storage>>>) -> Result<(), String> {
-    log::info!("Unloading library.");
+pub fn check_external_modification(
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<ExternalModification, String> {
+    log::info!("Checking {:?} for external modifications.", library);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
+        ExternalModification::check(&storage.cache.root, storage.cache.fingerprint.as_ref())
+            .map_err(|e| e.to_string())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
 
-    if let Ok(mut storage) = storage.lock() {
-        if let Some(storage) = storage.as_mut() {
-            storage.save().map_err(|e| e.to_string())?;
+#[tauri::command]
+pub fn list_snapshots(
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<Vec<Snapshot>, String> {
+    log::info!("Listing snapshots of {:?}.", library);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
+        snapshot::list_snapshots(&storage.cache.root).map_err(|e| e.to_string())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn create_snapshot(
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<Snapshot, String> {
+    log::info!("Creating snapshot of {:?}.", library);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        storage.save().map_err(|e| e.to_string())?;
+        let bytes = storage.to_bytes().map_err(|e| e.to_string())?;
+        snapshot::take_snapshot(&storage.cache.root, &bytes).map_err(|e| e.to_string())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+/// Diffs two snapshots, or a snapshot against the library when `to` is
+/// omitted.
+#[tauri::command]
+pub fn diff_snapshots(
+    from: String,
+    to: Option<String>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<SnapshotDiff, String> {
+    log::info!("Diffing snapshot {} against {:?}.", from, to);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
+        let from = snapshot::load_snapshot(&storage.cache, &from).map_err(|e| e.to_string())?;
+        match to {
+            Some(to) => {
+                let to = snapshot::load_snapshot(&storage.cache, &to).map_err(|e| e.to_string())?;
+                Ok(snapshot::diff(&from, &to))
+            }
+            None => Ok(snapshot::diff(&from, storage)),
+        }
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+/// Restores the whole library when `items` is omitted. The current state is
+/// snapshotted first, so the restore can be undone.
+#[tauri::command]
+pub fn restore_snapshot(
+    snapshot: String,
+    items: Option<ItemIds>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<RestoreReport, String> {
+    log::info!("Restoring {:?} from snapshot {}.", items, snapshot);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        let root = storage.cache.root.clone();
+        let source =
+            snapshot::load_snapshot(&storage.cache, &snapshot).map_err(|e| e.to_string())?;
+        let bytes = storage.to_bytes().map_err(|e| e.to_string())?;
+        snapshot::take_snapshot(&root, &bytes).map_err(|e| e.to_string())?;
+
+        let report = match items {
+            Some(items) => snapshot::restore_items(storage, &source, items),
+            None => snapshot::restore_all(storage, source),
+        }
+        .map_err(|e| e.to_string())?;
+        storage.save().map_err(|e| e.to_string())?;
+        Ok(report)
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn get_sync_status(
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<Option<SyncStatus>, String> {
+    log::info!("Getting sync status of {:?}.", library);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
+        Ok(storage.cache.sync.as_ref().map(SyncState::status))
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn enable_sync(
+    dir: PathBuf,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<SyncReport, String> {
+    log::info!("Enabling sync of {:?} through {:?}.", library, dir);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        if storage.cache.cipher.is_some() {
+            return Err(AppError::EncryptedLibrarySync.to_string());
+        }
+
+        match &mut storage.cache.sync {
+            Some(state) => state.dir = dir,
+            None => storage.cache.sync = Some(SyncState::new(dir)),
+        }
+        let report = sync::sync(storage).map_err(|e| e.to_string())?;
+        storage.save().map_err(|e| e.to_string())?;
+        Ok(report)
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn disable_sync(
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<(), String> {
+    log::info!("Disabling sync of {:?}.", library);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        storage.cache.sync = None;
+        SyncState::remove(&storage.cache.root).map_err(|e| e.to_string())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn sync_library(
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<SyncReport, String> {
+    log::info!("Syncing library {:?}.", library);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        let report = sync::sync(storage).map_err(|e| e.to_string())?;
+        storage.save().map_err(|e| e.to_string())?;
+        Ok(report)
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn is_library_encrypted(root_folder: PathBuf) -> Result<bool, String> {
+    log::info!(
+        "Checking whether library at {:?} is encrypted.",
+        root_folder
+    );
+
+    Ok(crypto::KeyFile::load(&root_folder)
+        .map_err(|e| e.to_string())?
+        .is_some())
+}
+
+#[tauri::command]
+pub fn encrypt_library(
+    password: String,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<(), String> {
+    log::info!("Encrypting library {:?}.", library);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        crypto::encrypt_library(storage, &password).map_err(|e| e.to_string())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn decrypt_library(
+    password: String,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<(), String> {
+    log::info!("Decrypting library {:?}.", library);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        crypto::decrypt_library(storage, &password).map_err(|e| e.to_string())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+/// Changes the password, `rotate` also replaces the key the files are
+/// encrypted with.
+#[tauri::command]
+pub fn rekey_library(
+    old_password: String,
+    new_password: String,
+    rotate: Option<bool>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<(), String> {
+    log::info!("Rekeying library {:?}, rotate: {:?}.", library, rotate);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        crypto::rekey_library(
+            storage,
+            &old_password,
+            &new_password,
+            rotate.unwrap_or_default(),
+        )
+        .map_err(|e| e.to_string())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn reload_library(
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+    data: State<'_, Mutex<AppData>>,
+    watcher: State<'_, Mutex<FolderWatcher>>,
+    app: AppHandle,
+) -> Result<Option<DuplicateAssets>, String> {
+    log::info!("Reloading library {:?} from disk.", library);
+
+    let mut libraries = libraries.lock().map_err(|e| e.to_string())?;
+    let Some((root, cipher)) = libraries
+        .get(library.as_deref())
+        .map(|s| (s.cache.root.clone(), s.cache.cipher.clone()))
+    else {
+        return Err(AppError::StorageNotInitialized.to_string());
+    };
+
+    let mut new_storage = Storage::from_existing(&root, cipher).map_err(|e| e.to_string())?;
+    new_storage
+        .cache
+        .apply_settings(&data.lock().map_err(|e| e.to_string())?.settings);
+    let duplication = new_storage.cache.get_all_duplication();
+    watcher
+        .lock()
+        .map_err(|e| e.to_string())?
+        .refresh(&app, &root, &new_storage.watch_folders)
+        .map_err(|e| e.to_string())?;
+    libraries.replace(new_storage);
+
+    Ok(DuplicateAssets(duplication).reduce())
+}
+
+#[tauri::command]
+pub fn merge_library(
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<(), String> {
+    log::info!("Merging external modifications into library {:?}.", library);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        storage.merge_external().map_err(|e| e.to_string())?;
+        storage.overwrite().map_err(|e| e.to_string())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn unload_library(
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+    watcher: State<'_, Mutex<FolderWatcher>>,
+) -> Result<(), String> {
+    log::info!("Unloading library {:?}.", library);
+
+    let mut libraries = libraries.lock().map_err(|e| e.to_string())?;
+    // Nothing is dropped if the library can't be saved.
+    match libraries.get_mut(library.as_deref()) {
+        Some(storage) => storage.save().map_err(|e| e.to_string())?,
+        None => return Ok(()),
+    }
+    if let Some(storage) = libraries.remove(library.as_deref()) {
+        watcher
+            .lock()
+            .map_err(|e| e.to_string())?
+            .stop(&storage.cache.root);
+        if let Err(err) = storage.remove_plain_copies() {
+            log::warn!("Failed to remove decrypted copies: {}", err);
         }
-        storage.take();
+        LibraryLock::release(&storage.cache.root).map_err(|e| e.to_string())?;
     }
 
     Ok(())
 }
 
-fn export_recursion(
-    storage: &Storage,
-    collection: CollectionId,
-    path: PathBuf,
-    tag_to_path: &mut HashMap<TagId, PathBuf>,
-) -> Result<(), AppError> {
-    if let Some(collection) = storage.collections.get(&collection) {
-        let collection_path = path.join(collection.name.as_ref());
-        let _ = create_dir_all(&collection_path);
-
-        for tag in &collection.content {
-            let Some(tag) = storage.tags.get(tag) else {
-                return Err(AppError::TagNotFound(*tag));
-            };
-            let _ = create_dir_all(&collection_path.join(tag.name.as_ref()));
-            tag_to_path.insert(tag.id, collection_path.join(tag.name.as_ref()));
+#[tauri::command]
+pub fn export_library(
+    root_folder: PathBuf,
+    settings: Option<ExportSettings>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<(), String> {
+    log::info!("Exporting library to {:?} with {:?}", root_folder, settings);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
+        export::export_library(storage, &root_folder, &settings.unwrap_or_default())
+            .map_err(|e| e.to_string())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn export_archive(
+    path: PathBuf,
+    scope: Option<ExportScope>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<(), String> {
+    log::info!("Exporting archive to {:?} with {:?}", path, scope);
+
+    let path = if path.extension().is_none() {
+        path.with_extension(archive::ARCHIVE_EXT)
+    } else {
+        path
+    };
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
+        archive::export_archive(storage, &path, scope.unwrap_or_default())
+            .map_err(|e| e.to_string())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn import_archive(
+    initial_tag: Option<TagId>,
+    path: PathBuf,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<Option<DuplicateAssets>, String> {
+    log::info!("Importing archive {:?} to {:?}", path, initial_tag);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        let duplication =
+            archive::import_archive(storage, &path, initial_tag).map_err(|e| e.to_string())?;
+        storage.save().map_err(|e| e.to_string())?;
+        Ok(duplication.reduce())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn initialize_library_from_archive(
+    path: PathBuf,
+    root_folder: PathBuf,
+    libraries: State<'_, Mutex<Libraries>>,
+    data: State<'_, Mutex<AppData>>,
+    watcher: State<'_, Mutex<FolderWatcher>>,
+    app: AppHandle,
+) -> Result<Option<DuplicateAssets>, String> {
+    log::info!(
+        "Start initializing library {:?} from archive {:?}",
+        root_folder,
+        path
+    );
+
+    let mut new_storage = Storage::from_empty(root_folder.clone()).map_err(|e| e.to_string())?;
+    LibraryLock::acquire(&root_folder, false).map_err(|e| e.to_string())?;
+    archive::import_archive(&mut new_storage, &path, None).map_err(|e| e.to_string())?;
+    new_storage.save().map_err(|e| e.to_string())?;
+
+    let mut data = data.lock().map_err(|e| e.to_string())?;
+    data.recent_libs.insert(
+        root_folder.clone(),
+        RecentLib {
+            name: new_storage.lib_meta.name.clone(),
+            path: root_folder,
+            last_open: Local::now().into(),
+        },
+    );
+
+    new_storage.cache.apply_settings(&data.settings);
+    let duplication = new_storage.cache.get_all_duplication();
+    watcher
+        .lock()
+        .map_err(|e| e.to_string())?
+        .stop(&new_storage.cache.root);
+
+    libraries
+        .lock()
+        .map_err(|e| e.to_string())?
+        .insert(new_storage);
+    data.save(&app).map_err(|e| e.to_string())?;
+
+    Ok(DuplicateAssets(duplication).reduce())
+}
+
+#[tauri::command]
+pub fn gen_statistics(
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<LibraryStatistics, String> {
+    log::info!("Generating statistics.");
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        Ok(storage.gen_statistics())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn import_assets(
+    initial_tag: Option<TagId>,
+    path: Vec<PathBuf>,
+    mode: Option<ImportMode>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<Option<DuplicateAssets>, String> {
+    log::info!(
+        "Importing assets {:?} to {:?}, {:?}.",
+        path,
+        initial_tag,
+        mode
+    );
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        let duplication = storage
+            .add_assets(initial_tag, path, mode.unwrap_or_default())
+            .map_err(|e| e.to_string())?;
+        storage.save().map_err(|e| e.to_string())?;
+        Ok(duplication.reduce())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn import_eagle_library(
+    initial_tag: Option<TagId>,
+    path: PathBuf,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<Option<DuplicateAssets>, String> {
+    log::info!("Importing Eagle library {:?} to {:?}.", path, initial_tag);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        let duplication = foreign::import_eagle_library(storage, &path, initial_tag)
+            .map_err(|e| e.to_string())?;
+        storage.save().map_err(|e| e.to_string())?;
+        Ok(duplication.reduce())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn import_pureref_board(
+    initial_tag: Option<TagId>,
+    path: PathBuf,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<Option<DuplicateAssets>, String> {
+    log::info!("Importing PureRef board {:?} to {:?}.", path, initial_tag);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        let duplication = foreign::import_pureref_board(storage, &path, initial_tag)
+            .map_err(|e| e.to_string())?;
+        storage.save().map_err(|e| e.to_string())?;
+        Ok(duplication.reduce())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn get_missing_linked_assets(
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<Vec<AssetId>, String> {
+    log::info!("Getting missing linked assets.");
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
+        Ok(storage
+            .assets
+            .values()
+            .filter(|a| {
+                a.link
+                    .as_ref()
+                    .is_some_and(|l| l.status == LinkStatus::Missing)
+            })
+            .map(|a| a.id)
+            .collect())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn relink_assets(
+    search_root: PathBuf,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<Vec<AssetId>, String> {
+    log::info!("Relinking missing assets from {:?}", search_root);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        let relinked = storage
+            .relink_assets(&search_root)
+            .map_err(|e| e.to_string())?;
+        storage.save().map_err(|e| e.to_string())?;
+        Ok(relinked)
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn consolidate_assets(
+    assets: Vec<AssetId>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<(), String> {
+    log::info!("Consolidating assets {:?}", assets);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        for asset in assets {
+            storage
+                .consolidate_asset(asset)
+                .map_err(|e| e.to_string())?;
+        }
+
+        storage.save().map_err(|e| e.to_string())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn change_library_name(
+    name: String,
+    data: State<'_, Mutex<AppData>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<(), String> {
+    log::info!("Changing library name into {}", name);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        if let Some(data) = data
+            .lock()
+            .as_deref_mut()
+            .ok()
+            .and_then(|d| d.recent_libs.get_mut(&storage.cache.root))
+        {
+            data.name = name.clone();
+        }
+
+        storage.lib_meta.name = name;
+        storage.save().map_err(|e| e.to_string())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn import_memory_asset(
+    initial_tag: Option<TagId>,
+    data: Vec<u8>,
+    format: String,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<Option<DuplicateAssets>, String> {
+    log::info!("Importing memory asset");
+
+    let Some(ext) = clipboard::sniff_ext(&data, Some(&format)) else {
+        return Err(format!("Failed to import unsupported asset. {}", format));
+    };
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        let duplication = storage
+            .add_raw_assets(
+                initial_tag,
+                vec![RawAsset {
+                    bytes: data,
+                    ext: ext.into(),
+                    src: Default::default(),
+                    page: None,
+                    name: None,
+                    tags: Default::default(),
+                }],
+            )
+            .map_err(|e| e.to_string())?;
+        storage.save().map_err(|e| e.to_string())?;
+
+        Ok(duplication.reduce())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub async fn import_from_clipboard(
+    initial_tag: Option<TagId>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+    data: State<'_, Mutex<AppData>>,
+    downloads: State<'_, Mutex<DownloadManager>>,
+    progress: Channel<DownloadEvent>,
+) -> Result<Option<DuplicateAssets>, String> {
+    log::info!("Importing from clipboard.");
+
+    let content = clipboard::read_clipboard().map_err(|e| e.to_string())?;
+
+    match content {
+        ClipboardContent::Urls(urls) => {
+            import_web_assets(
+                initial_tag,
+                urls,
+                None,
+                library,
+                libraries,
+                data,
+                downloads,
+                progress,
+            )
+            .await
+        }
+        ClipboardContent::Files(path) => {
+            if let Ok(Some(storage)) = libraries
+                .lock()
+                .as_deref_mut()
+                .map(|l| l.get_mut(library.as_deref()))
+            {
+                let duplication = storage
+                    .add_assets(initial_tag, path, ImportMode::Copy)
+                    .map_err(|e| e.to_string())?;
+                storage.save().map_err(|e| e.to_string())?;
+                Ok(duplication.reduce())
+            } else {
+                Err(AppError::StorageNotInitialized.to_string())
+            }
+        }
+        ClipboardContent::Image { bytes, ext } => {
+            if let Ok(Some(storage)) = libraries
+                .lock()
+                .as_deref_mut()
+                .map(|l| l.get_mut(library.as_deref()))
+            {
+                let duplication = storage
+                    .add_raw_assets(
+                        initial_tag,
+                        vec![RawAsset {
+                            bytes,
+                            ext: ext.into(),
+                            src: Default::default(),
+                            page: None,
+                            name: None,
+                            tags: Default::default(),
+                        }],
+                    )
+                    .map_err(|e| e.to_string())?;
+                storage.save().map_err(|e| e.to_string())?;
+                Ok(duplication.reduce())
+            } else {
+                Err(AppError::StorageNotInitialized.to_string())
+            }
+        }
+    }
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn import_web_assets(
+    initial_tag: Option<TagId>,
+    urls: Vec<String>,
+    page: Option<String>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+    data: State<'_, Mutex<AppData>>,
+    downloads: State<'_, Mutex<DownloadManager>>,
+    progress: Channel<DownloadEvent>,
+) -> Result<Option<DuplicateAssets>, String> {
+    log::info!("Importing assets from web {:?}.", urls,);
+
+    let library = Some(
+        libraries
+            .lock()
+            .map_err(|e| e.to_string())?
+            .resolve_root(library.as_deref())
+            .ok_or_else(|| AppError::StorageNotInitialized.to_string())?,
+    );
+
+    let (temp_dir, library_http) = match libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
+        Ok(Some(storage)) => (
+            storage.download_dir().map_err(|e| e.to_string())?,
+            storage.http.clone(),
+        ),
+        _ => return Err(AppError::StorageNotInitialized.to_string()),
+    };
+    let (settings, http) = {
+        let data = data.lock().map_err(|e| e.to_string())?;
+        (
+            DownloadSettings::from_user_settings(&data.settings),
+            HttpSettings::merged(&data.http, library_http.as_ref()),
+        )
+    };
+    let client = http.client(settings.timeout).map_err(|e| e.to_string())?;
+    let send_progress = |event: DownloadEvent| {
+        let _ = progress.send(event);
+    };
+    let channel = progress.id();
+    let downloader = Download {
+        channel,
+        client: &client,
+        settings,
+        temp_dir: &temp_dir,
+        progress: &send_progress,
+    };
+    let downloader = &downloader;
+    let downloads = &downloads;
+    let page = page.as_deref();
+
+    let results = futures::stream::iter(urls.into_iter().enumerate())
+        .map(|(index, url)| {
+            let id = index as u32;
+            let registration = downloads
+                .lock()
+                .map(|mut d| d.register(channel, id))
+                .unwrap_or_else(|_| AbortHandle::new_pair().1);
+
+            async move {
+                let result = Abortable::new(downloader.fetch(id, &url, page), registration).await;
+                if let Ok(mut downloads) = downloads.lock() {
+                    downloads.unregister(channel, id);
+                }
+
+                let raw = result.unwrap_or_else(|_| {
+                    let _ = remove_file(downloader.temp_path(id, &url));
+                    (downloader.progress)(DownloadEvent {
+                        id,
+                        downloaded: 0.0,
+                        total: None,
+                        status: DownloadStatus::Cancelled,
+                    });
+                    None
+                });
+                raw.map(|raw| (id, raw))
+            }
+        })
+        .buffer_unordered(settings.max_concurrent)
+        .filter_map(|result| async move { result });
+    let mut results = pin!(results);
+
+    // Imported as they finish, only the running downloads are held in memory.
+    // A failed import is reported for its download alone, the stream is run
+    // to the end so every download unregisters.
+    let mut duplication = DuplicateAssets::default();
+    let mut imported = false;
+    while let Some((id, raw)) = results.next().await {
+        let added = match libraries.lock().as_deref_mut() {
+            Ok(libraries) => match libraries.get_mut(library.as_deref()) {
+                Some(storage) => storage
+                    .add_raw_assets(initial_tag, vec![raw])
+                    .map_err(|e| e.to_string()),
+                None => Err(AppError::StorageNotInitialized.to_string()),
+            },
+            Err(err) => Err(err.to_string()),
+        };
+        match added {
+            Ok(added) => {
+                duplication.0.extend(added.0);
+                imported = true;
+            }
+            Err(err) => {
+                log::error!("Failed to import download {}: {}", id, err);
+                send_progress(DownloadEvent {
+                    id,
+                    downloaded: 0.0,
+                    total: None,
+                    status: DownloadStatus::Error(err),
+                });
+            }
+        }
+    }
+
+    if imported {
+        let mut libraries = libraries.lock().map_err(|e| e.to_string())?;
+        let Some(storage) = libraries.get_mut(library.as_deref()) else {
+            return Err(AppError::StorageNotInitialized.to_string());
+        };
+        storage.save().map_err(|e| e.to_string())?;
+    }
+
+    Ok(duplication.reduce())
+}
+
+#[tauri::command]
+pub fn cancel_download(
+    channel: u32,
+    id: u32,
+    downloads: State<'_, Mutex<DownloadManager>>,
+) -> Result<(), String> {
+    log::info!("Cancelling download {} of channel {}.", id, channel);
+
+    let mut downloads = downloads.lock().map_err(|e| e.to_string())?;
+    if downloads.cancel(channel, id) {
+        Ok(())
+    } else {
+        Err(AppError::DownloadNotFound(id).to_string())
+    }
+}
+
+#[tauri::command]
+pub async fn scrape_web_page(
+    url: String,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+    data: State<'_, Mutex<AppData>>,
+) -> Result<Vec<WebMediaCandidate>, String> {
+    log::info!("Scraping web page {}.", url);
+
+    let library_http = match libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
+        Ok(Some(storage)) => storage.http.clone(),
+        _ => None,
+    };
+    let (settings, http) = {
+        let data = data.lock().map_err(|e| e.to_string())?;
+        (
+            DownloadSettings::from_user_settings(&data.settings),
+            HttpSettings::merged(&data.http, library_http.as_ref()),
+        )
+    };
+    let response = http
+        .client(settings.timeout)
+        .map_err(|e| e.to_string())?
+        .get(&url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?;
+    let final_url = response.url().clone();
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|ty| ty.to_str().ok())
+        .map(|ty| ty.to_lowercase());
+    let content = download::read_page(response, &settings)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if web::is_html(content_type.as_deref(), &content) {
+        Ok(web::scrape_media(
+            &final_url,
+            &String::from_utf8_lossy(&content),
+        ))
+    } else {
+        // The url points to media directly, nothing to pick from.
+        Ok(vec![WebMediaCandidate {
+            url: final_url.to_string(),
+            source: WebMediaSource::Link,
+        }])
+    }
+}
+
+#[tauri::command]
+pub fn get_http_settings(
+    per_library: bool,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+    data: State<'_, Mutex<AppData>>,
+) -> Result<Option<HttpSettings>, String> {
+    log::info!("Getting http settings, library: {}.", per_library);
+
+    if !per_library {
+        let data = data.lock().map_err(|e| e.to_string())?;
+        return Ok(Some(data.http.clone()));
+    }
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
+        Ok(storage.http.clone())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn set_http_settings(
+    settings: Option<HttpSettings>,
+    per_library: bool,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+    data: State<'_, Mutex<AppData>>,
+    app: AppHandle,
+) -> Result<(), String> {
+    log::info!("Setting http settings, library: {}.", per_library);
+
+    if !per_library {
+        let mut data = data.lock().map_err(|e| e.to_string())?;
+        data.http = settings.unwrap_or_default();
+        return data.save(&app).map_err(|e| e.to_string());
+    }
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        storage.http = settings;
+        storage.save().map_err(|e| e.to_string())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn import_cookie_file(
+    path: PathBuf,
+    per_library: bool,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+    data: State<'_, Mutex<AppData>>,
+    app: AppHandle,
+) -> Result<usize, String> {
+    log::info!(
+        "Importing cookie file {:?}, library: {}.",
+        path,
+        per_library
+    );
+
+    if !per_library {
+        let mut data = data.lock().map_err(|e| e.to_string())?;
+        let count = data
+            .http
+            .import_cookie_file(&path)
+            .map_err(|e| e.to_string())?;
+        data.save(&app).map_err(|e| e.to_string())?;
+        return Ok(count);
+    }
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        let count = storage
+            .http
+            .get_or_insert_with(Default::default)
+            .import_cookie_file(&path)
+            .map_err(|e| e.to_string())?;
+        storage.save().map_err(|e| e.to_string())?;
+        Ok(count)
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn get_bridge_settings(data: State<'_, Mutex<AppData>>) -> Result<BridgeSettings, String> {
+    log::info!("Getting bridge settings.");
+    let data = data.lock().map_err(|e| e.to_string())?;
+    Ok(data.bridge.clone())
+}
+
+#[tauri::command]
+pub fn set_bridge_settings(
+    enabled: bool,
+    port: Option<u16>,
+    data: State<'_, Mutex<AppData>>,
+    bridge: State<'_, Mutex<Bridge>>,
+    app: AppHandle,
+) -> Result<(), String> {
+    log::info!(
+        "Setting bridge settings, enabled: {}, port: {:?}.",
+        enabled,
+        port
+    );
+
+    let mut data = data.lock().map_err(|e| e.to_string())?;
+    data.bridge.enabled = enabled;
+    if let Some(port) = port {
+        data.bridge.port = port;
+    }
+    data.save(&app).map_err(|e| e.to_string())?;
+
+    let mut bridge = bridge.lock().map_err(|e| e.to_string())?;
+    bridge
+        .refresh(&app, &data.bridge)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn regenerate_bridge_token(
+    data: State<'_, Mutex<AppData>>,
+    bridge: State<'_, Mutex<Bridge>>,
+    app: AppHandle,
+) -> Result<String, String> {
+    log::info!("Regenerating bridge token.");
+
+    let mut data = data.lock().map_err(|e| e.to_string())?;
+    data.bridge.regenerate_token();
+    data.save(&app).map_err(|e| e.to_string())?;
+
+    let mut bridge = bridge.lock().map_err(|e| e.to_string())?;
+    bridge
+        .refresh(&app, &data.bridge)
+        .map_err(|e| e.to_string())?;
+
+    Ok(data.bridge.token.clone())
+}
+
+#[tauri::command]
+pub fn get_metadata_mapping(
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<MetadataMapping, String> {
+    log::info!("Getting metadata mapping.");
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
+        Ok(storage.metadata_mapping.clone())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn set_metadata_mapping(
+    mapping: MetadataMapping,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<(), String> {
+    log::info!("Setting metadata mapping {:?}.", mapping);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        if let Some(collection) = mapping.keyword_collection {
+            if !storage.collections.contains_key(&collection) {
+                return Err(AppError::CollectionNotFound(collection).to_string());
+            }
+        }
+        storage.metadata_mapping = mapping;
+        storage.save().map_err(|e| e.to_string())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn refresh_embedded_metadata(
+    assets: Vec<AssetId>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<(), String> {
+    log::info!("Refreshing embedded metadata of {:?}.", assets);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        storage
+            .refresh_embedded_metadata(&assets)
+            .map_err(|e| e.to_string())?;
+        storage.save().map_err(|e| e.to_string())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn detect_aigc(
+    assets: Vec<AssetId>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<(), String> {
+    log::info!("Detecting AI generated content in {:?}.", assets);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        if !storage.cache.aigc_detect {
+            return Err(AppError::AigcDetectionDisabled.to_string());
+        }
+
+        storage.detect_aigc(&assets).map_err(|e| e.to_string())?;
+        storage.save().map_err(|e| e.to_string())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+/// Suggests tags for the given raster assets, or for all the ones the tagger
+/// hasn't seen yet, like newly imported ones.
+#[tauri::command]
+pub async fn suggest_tags(
+    assets: Option<Vec<AssetId>>,
+    library: Option<PathBuf>,
+    app: AppHandle,
+    libraries: State<'_, Mutex<Libraries>>,
+    data: State<'_, Mutex<AppData>>,
+) -> Result<HashMap<AssetId, Vec<TagSuggestion>>, String> {
+    log::info!("Suggesting tags for {:?}.", assets);
+
+    let library = Some(
+        libraries
+            .lock()
+            .map_err(|e| e.to_string())?
+            .resolve_root(library.as_deref())
+            .ok_or_else(|| AppError::StorageNotInitialized.to_string())?,
+    );
+
+    let settings =
+        AutoTagSettings::from_user_settings(&data.lock().map_err(|e| e.to_string())?.settings);
+    if !settings.enabled {
+        return Err(AppError::AutoTagDisabled.to_string());
+    }
+
+    // Inference is slow, don't hold the library while running it.
+    let (targets, candidates, cipher) = if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
+        let targets = storage
+            .assets
+            .values()
+            .filter(|asset| !asset.is_deleted && matches!(asset.ty, AssetType::RasterGraphics))
+            .filter(|asset| match &assets {
+                Some(assets) => assets.contains(&asset.id),
+                None => asset.tag_suggestions.is_none(),
+            })
+            .map(|asset| (asset.id, asset.get_file_path(&storage.cache.root)))
+            .collect::<Vec<_>>();
+        let candidates = storage
+            .tags
+            .values()
+            .filter(|tag| !tag.is_deleted)
+            .map(|tag| (tag.id, tag.name.to_string()))
+            .collect::<Vec<_>>();
+        (targets, candidates, storage.cache.cipher.clone())
+    } else {
+        return Err(AppError::StorageNotInitialized.to_string());
+    };
+
+    // Loading the model and inference block, keep them off the async runtime.
+    let mut suggestions = tauri::async_runtime::spawn_blocking(move || -> AppResult<_> {
+        let model = app
+            .state::<Mutex<ModelCache>>()
+            .lock()
+            .map_err(|e| AppError::Model(e.to_string()))?
+            .clip()?;
+
+        let mut suggestions = HashMap::default();
+        for (asset, path) in targets {
+            let result = crypto::read_file(&path, cipher.as_deref())
+                .and_then(|bytes| model.embed_image(&bytes))
+                .and_then(|image| autotag::suggest_tags(&model, &image, &candidates, &settings));
+            match result {
+                Ok(result) => {
+                    suggestions.insert(asset, result);
+                }
+                Err(err) => log::warn!("Failed to suggest tags for {:?}: {}", asset, err),
+            }
         }
+        Ok(suggestions)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())?;
 
-        for child in collection.children.clone() {
-            export_recursion(storage, child, collection_path.clone(), tag_to_path)?;
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        for (id, result) in &mut suggestions {
+            let Some(asset) = storage.assets.get_mut(id) else {
+                continue;
+            };
+            result.retain(|s| {
+                storage
+                    .tags
+                    .get(&s.tag)
+                    .is_some_and(|tag| !asset.tags.contains(tag))
+            });
+            asset.tag_suggestions = Some(result.clone());
         }
+        storage.save().map_err(|e| e.to_string())?;
+        Ok(suggestions)
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
     }
-
-    Ok(())
 }
 
 #[tauri::command]
-pub fn export_library(
-    root_folder: PathBuf,
-    storage: State<'_, Mutex<Option<Storage>>>,
+pub fn accept_tag_suggestions(
+    asset: AssetId,
+    tags: Vec<TagId>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+    data: State<'_, Mutex<AppData>>,
 ) -> Result<(), String> {
-    log::info!("Exporting library to {:?}", root_folder);
-
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
-        let mut tag_to_path = HashMap::default();
-        export_recursion(
-            storage,
-            storage.sp_collections.root,
-            root_folder.join(&storage.lib_meta.name),
-            &mut tag_to_path,
-        )
-        .map_err(|e| e.to_string())?;
-
-        for asset in storage.assets.values() {
-            let asset_path = asset.get_file_path(&storage.cache.root);
-
-            for tag in asset.tags.grouped.values().chain(&asset.tags.ungrouped) {
-                if let Some(path) = tag_to_path.get(tag) {
-                    copy(&asset_path, path.join(asset.gen_file_name().as_ref()))
-                        .map_err(|e| e.to_string())?;
-                } else {
-                    return Err(AppError::TagNotFound(*tag).to_string());
-                }
-            }
-        }
+    log::info!("Accepting tag suggestions {:?} for {:?}.", tags, asset);
 
-        Ok(())
+    let data = data.lock().map_err(|e| e.to_string())?;
+    let resolve = data
+        .settings
+        .get_as("general", "tagGroupConflictResolve")
+        .unwrap();
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        storage
+            .accept_tag_suggestions(asset, &tags, resolve)
+            .map_err(|e| e.to_string())?;
+        storage.save().map_err(|e| e.to_string())
     } else {
         Err(AppError::StorageNotInitialized.to_string())
     }
 }
 
 #[tauri::command]
-pub fn gen_statistics(
-    storage: State<'_, Mutex<Option<Storage>>>,
-) -> Result<LibraryStatistics, String> {
-    log::info!("Generating statistics.");
+pub async fn semantic_search(
+    query: String,
+    limit: usize,
+    library: Option<PathBuf>,
+    app: AppHandle,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<Vec<SemanticMatch>, String> {
+    log::info!("Semantic searching {}.", query);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
-        Ok(storage.gen_statistics())
+    let library = Some(
+        libraries
+            .lock()
+            .map_err(|e| e.to_string())?
+            .resolve_root(library.as_deref())
+            .ok_or_else(|| AppError::StorageNotInitialized.to_string())?,
+    );
+
+    let embedding_library = library.clone();
+    let text = tauri::async_runtime::spawn_blocking(move || {
+        semantic::sync_embedding_index(
+            &app.state::<Mutex<Libraries>>(),
+            embedding_library.as_deref(),
+            &app.state::<Mutex<ModelCache>>(),
+        )?
+        .embed_text(&query)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())?;
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
+        Ok(storage
+            .cache
+            .embeddings
+            .as_ref()
+            .map(|index| index.rank(&text, limit, None))
+            .unwrap_or_default())
     } else {
         Err(AppError::StorageNotInitialized.to_string())
     }
 }
 
 #[tauri::command]
-pub fn import_assets(
-    initial_tag: Option<TagId>,
-    path: Vec<PathBuf>,
-    storage: State<'_, Mutex<Option<Storage>>>,
-) -> Result<Option<DuplicateAssets>, String> {
-    log::info!("Importing assets {:?} to {:?}.", path, initial_tag);
+pub async fn find_similar_assets(
+    asset: AssetId,
+    limit: usize,
+    library: Option<PathBuf>,
+    app: AppHandle,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<Vec<SemanticMatch>, String> {
+    log::info!("Finding assets similar to {:?}.", asset);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
-        let duplication = storage
-            .add_assets(initial_tag, path)
-            .map_err(|e| e.to_string())?;
-        storage.save().map_err(|e| e.to_string())?;
-        Ok(duplication.reduce())
+    let library = Some(
+        libraries
+            .lock()
+            .map_err(|e| e.to_string())?
+            .resolve_root(library.as_deref())
+            .ok_or_else(|| AppError::StorageNotInitialized.to_string())?,
+    );
+
+    let embedding_library = library.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        semantic::sync_embedding_index(
+            &app.state::<Mutex<Libraries>>(),
+            embedding_library.as_deref(),
+            &app.state::<Mutex<ModelCache>>(),
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())?;
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
+        let Some(index) = storage.cache.embeddings.as_ref() else {
+            return Ok(Vec::new());
+        };
+        let Some(embedding) = index.get(asset) else {
+            return Err(AppError::AssetNotFound(asset).to_string());
+        };
+        Ok(index.rank(embedding, limit, Some(asset)))
     } else {
         Err(AppError::StorageNotInitialized.to_string())
     }
 }
 
+/// Recognizes text in the given raster assets, or in all the ones the OCR
+/// hasn't seen yet, like newly imported ones.
 #[tauri::command]
-pub fn change_library_name(
-    name: String,
+pub async fn recognize_text(
+    assets: Option<Vec<AssetId>>,
+    library: Option<PathBuf>,
+    app: AppHandle,
+    libraries: State<'_, Mutex<Libraries>>,
     data: State<'_, Mutex<AppData>>,
-    storage: State<'_, Mutex<Option<Storage>>>,
-) -> Result<(), String> {
-    log::info!("Changing library name into {}", name);
+) -> Result<HashMap<AssetId, String>, String> {
+    log::info!("Recognizing text in {:?}.", assets);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
-        if let Some(data) = data
+    let library = Some(
+        libraries
             .lock()
-            .as_deref_mut()
-            .ok()
-            .and_then(|d| d.recent_libs.get_mut(&storage.cache.root))
-        {
-            data.name = name.clone();
+            .map_err(|e| e.to_string())?
+            .resolve_root(library.as_deref())
+            .ok_or_else(|| AppError::StorageNotInitialized.to_string())?,
+    );
+
+    if !ocr::is_enabled(&data.lock().map_err(|e| e.to_string())?.settings) {
+        return Err(AppError::OcrDisabled.to_string());
+    }
+
+    let (targets, cipher) = if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
+        let targets = storage
+            .assets
+            .values()
+            .filter(|asset| !asset.is_deleted && matches!(asset.ty, AssetType::RasterGraphics))
+            .filter(|asset| match &assets {
+                Some(assets) => assets.contains(&asset.id),
+                None => asset.ocr_text.is_none(),
+            })
+            .map(|asset| (asset.id, asset.get_file_path(&storage.cache.root)))
+            .collect::<Vec<_>>();
+        (targets, storage.cache.cipher.clone())
+    } else {
+        return Err(AppError::StorageNotInitialized.to_string());
+    };
+
+    // Loading the model and recognition block, keep them off the async runtime.
+    let recognized = tauri::async_runtime::spawn_blocking(move || -> AppResult<_> {
+        let model = app
+            .state::<Mutex<ModelCache>>()
+            .lock()
+            .map_err(|e| AppError::Model(e.to_string()))?
+            .ocr()?;
+
+        let mut recognized = HashMap::default();
+        for (asset, path) in targets {
+            match crypto::read_file(&path, cipher.as_deref())
+                .and_then(|bytes| model.recognize(&bytes))
+            {
+                Ok(text) => {
+                    recognized.insert(asset, text);
+                }
+                Err(err) => log::warn!("Failed to recognize text in {:?}: {}", asset, err),
+            }
         }
+        Ok(recognized)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())?;
 
-        storage.lib_meta.name = name;
-        storage.save().map_err(|e| e.to_string())
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        for (id, text) in &recognized {
+            if let Some(asset) = storage.assets.get_mut(id) {
+                asset.ocr_text = Some(text.clone());
+            }
+        }
+        storage.save().map_err(|e| e.to_string())?;
+        Ok(recognized)
     } else {
         Err(AppError::StorageNotInitialized.to_string())
     }
 }
 
 #[tauri::command]
-pub fn import_memory_asset(
-    initial_tag: Option<TagId>,
-    data: Vec<u8>,
-    format: String,
-    storage: State<'_, Mutex<Option<Storage>>>,
-) -> Result<Option<DuplicateAssets>, String> {
-    log::info!("Importing memory asset");
-
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
-        let duplication = storage
-            .add_raw_assets(
-                initial_tag,
-                vec![RawAsset {
-                    bytes: data,
-                    ext: format.into(),
-                    src: Default::default(),
-                }],
-            )
-            .map_err(|e| e.to_string())?;
-        storage.save().map_err(|e| e.to_string())?;
-
-        Ok(duplication.reduce())
+pub fn get_watch_folders(
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<Vec<WatchFolder>, String> {
+    log::info!("Getting watch folders.");
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
+        Ok(storage.watch_folders.clone())
     } else {
         Err(AppError::StorageNotInitialized.to_string())
     }
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchFolderSyncSettings {
+    pub write_back: bool,
+}
+
 #[tauri::command]
-pub async fn import_web_assets(
+#[allow(clippy::too_many_arguments)]
+pub fn add_watch_folder(
+    path: PathBuf,
     initial_tag: Option<TagId>,
-    urls: Vec<String>,
-    storage: State<'_, Mutex<Option<Storage>>>,
-    progress: Channel<DownloadEvent>,
+    folder_as_tag: bool,
+    sync: Option<WatchFolderSyncSettings>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+    watcher: State<'_, Mutex<FolderWatcher>>,
+    app: AppHandle,
 ) -> Result<Option<DuplicateAssets>, String> {
-    log::info!("Importing assets from web {:?}.", urls,);
+    log::info!(
+        "Adding watch folder {:?} with {:?}, folder as tag: {}, sync: {:?}",
+        path,
+        initial_tag,
+        folder_as_tag,
+        sync
+    );
 
-    let client = Client::new();
-    let mut results = Vec::with_capacity(urls.len());
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        let synced = sync.is_some();
+        storage
+            .add_watch_folder(WatchFolder {
+                sync: sync.map(|s| FolderSync::new(s.write_back)),
+                ..WatchFolder::new(path, initial_tag, folder_as_tag)
+            })
+            .map_err(|e| e.to_string())?;
 
-    for (index, url) in urls.into_iter().enumerate() {
-        let id = index as u32;
-        let request = match client.get(&url).build() {
-            Ok(req) => req,
-            Err(err) => {
-                let _ = progress.send(DownloadEvent {
-                    id,
-                    downloaded: 0.0,
-                    total: None,
-                    status: DownloadStatus::Error(err.to_string()),
-                });
-                continue;
-            }
+        let duplication = if synced {
+            storage.sync_watch_folders().map_err(|e| e.to_string())?
+        } else {
+            Default::default()
         };
 
-        let _ = progress.send(DownloadEvent {
-            id,
-            downloaded: 0.0,
-            total: None,
-            status: DownloadStatus::SendingGet,
-        });
-        let response = client.execute(request).await;
-
-        match response {
-            Ok(ok) => {
-                let total = ok.content_length();
-                let total_f = total.map(|t| t as f32);
-
-                let mut content = Vec::new();
-                let mut stream = ok.bytes_stream();
-
-                let _ = progress.send(DownloadEvent {
-                    id,
-                    downloaded: 0.0,
-                    total: total_f,
-                    status: DownloadStatus::Started,
-                });
-
-                while let Some(Ok(bytes)) = stream.next().await {
-                    content.extend(bytes);
-
-                    let _ = progress.send(DownloadEvent {
-                        id,
-                        downloaded: content.len() as f32,
-                        total: total_f,
-                        status: DownloadStatus::Ongoing,
-                    });
-                }
-
-                let _ = progress.send(DownloadEvent {
-                    id,
-                    downloaded: f32::MAX,
-                    total: total_f,
-                    status: DownloadStatus::Finished,
-                });
-
-                let fmt = FileFormat::from_bytes(&content);
-
-                if fmt.kind() != Kind::Image {
-                    let _ = progress.send(DownloadEvent {
-                        id,
-                        downloaded: f32::MAX,
-                        total: total_f,
-                        status: DownloadStatus::Error(format!(
-                            "Failed to import unsupported asset. {}",
-                            fmt.media_type()
-                        )),
-                    });
-                    continue;
-                }
-
-                results.push(RawAsset {
-                    bytes: content,
-                    ext: fmt.extension().into(),
-                    src: url,
-                });
-            }
-            Err(err) => {
-                let _ = progress.send(DownloadEvent {
-                    id,
-                    downloaded: 0.0,
-                    total: None,
-                    status: DownloadStatus::Error(err.to_string()),
-                });
-            }
-        }
-    }
-
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
-        let duplication = storage
-            .add_raw_assets(initial_tag, results)
+        watcher
+            .lock()
+            .map_err(|e| e.to_string())?
+            .refresh(&app, &storage.cache.root, &storage.watch_folders)
             .map_err(|e| e.to_string())?;
         storage.save().map_err(|e| e.to_string())?;
         Ok(duplication.reduce())
@@ -575,15 +2087,56 @@ pub async fn import_web_assets(
     }
 }
 
+#[tauri::command]
+pub fn remove_watch_folder(
+    path: PathBuf,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+    watcher: State<'_, Mutex<FolderWatcher>>,
+    app: AppHandle,
+) -> Result<(), String> {
+    log::info!("Removing watch folder {:?}", path);
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        storage
+            .remove_watch_folder(&path)
+            .map_err(|e| e.to_string())?;
+        watcher
+            .lock()
+            .map_err(|e| e.to_string())?
+            .refresh(&app, &storage.cache.root, &storage.watch_folders)
+            .map_err(|e| e.to_string())?;
+        storage.save().map_err(|e| e.to_string())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
 #[tauri::command]
 pub fn recover_items(
     items: Vec<ItemId>,
     parent_override: Option<CollectionId>,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+    data: State<'_, Mutex<AppData>>,
 ) -> Result<Option<DuplicateAssets>, String> {
     log::info!("Recovering items {:?} -> {:?}", items, parent_override);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
+    let resolve = data
+        .lock()
+        .map_err(|e| e.to_string())?
+        .settings
+        .get_as("general", "tagGroupConflictResolve")
+        .unwrap();
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
         let mut assets = Vec::with_capacity(items.len());
         let mut collections = Vec::with_capacity(items.len());
         let mut tags = Vec::with_capacity(items.len());
@@ -597,10 +2150,10 @@ pub fn recover_items(
 
         let duplication = storage.recover_assets(assets).map_err(|e| e.to_string())?;
         storage
-            .recover_collections(collections, parent_override)
+            .recover_collections(collections, parent_override, resolve)
             .map_err(|e| e.to_string())?;
         storage
-            .recover_tags(tags, parent_override)
+            .recover_tags(tags, parent_override, resolve)
             .map_err(|e| e.to_string())?;
         storage.save().map_err(|e| e.to_string())?;
         Ok(duplication.reduce())
@@ -612,11 +2165,16 @@ pub fn recover_items(
 #[tauri::command]
 pub fn get_recycle_bin(
     ty: IdType,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<ItemIds, String> {
     log::info!("Getting recycle bin {:?}.", ty);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
         Ok(match ty {
             IdType::Asset => {
                 ItemIds::Asset(storage.recycle_bin.assets.clone().into_iter().collect())
@@ -636,13 +2194,82 @@ pub fn get_recycle_bin(
     }
 }
 
+#[tauri::command]
+pub fn get_recycle_bin_info(
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<RecycleBinInfo, String> {
+    log::info!("Getting recycle bin info.");
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
+        Ok(storage.get_recycle_bin_info())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn get_recycle_bin_tree(
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<Vec<RecycleBinNode>, String> {
+    log::info!("Getting recycle bin tree.");
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
+        Ok(storage.get_recycle_bin_tree())
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
+#[tauri::command]
+pub fn empty_recycle_bin(
+    older_than_days: Option<f32>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<RecycleBinPurge, String> {
+    log::info!(
+        "Emptying recycle bin, older than {:?} days.",
+        older_than_days
+    );
+
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
+        let older_than =
+            older_than_days.map(|days| chrono::Duration::seconds((days * 86400.0) as i64));
+        let purge = storage
+            .empty_recycle_bin(older_than)
+            .map_err(|e| e.to_string())?;
+        storage.save().map_err(|e| e.to_string())?;
+        Ok(purge)
+    } else {
+        Err(AppError::StorageNotInitialized.to_string())
+    }
+}
+
 #[tauri::command]
 pub fn get_duplicated_assets(
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<DuplicateAssets, String> {
     log::info!("Getting duplicated assets.");
 
-    if let Ok(Some(storage)) = storage.lock().as_deref() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
         Ok(DuplicateAssets(
             storage
                 .cache
@@ -660,11 +2287,16 @@ pub fn get_duplicated_assets(
 #[tauri::command]
 pub fn get_asset_abs_path(
     asset: AssetId,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<PathBuf, String> {
     log::info!("Getting absolute path of asset {:?}.", asset);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
         storage.get_asset_abs_path(asset).map_err(|e| e.to_string())
     } else {
         Err(AppError::StorageNotInitialized.to_string())
@@ -674,11 +2306,16 @@ pub fn get_asset_abs_path(
 #[tauri::command]
 pub fn get_tag_virtual_path(
     tag: TagId,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<Vec<String>, String> {
     log::info!("Getting virtual path of tag {:?}.", tag);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
         storage.get_tag_virtual_path(tag).map_err(|e| e.to_string())
     } else {
         Err(AppError::StorageNotInitialized.to_string())
@@ -688,11 +2325,16 @@ pub fn get_tag_virtual_path(
 #[tauri::command]
 pub fn get_collection_tree(
     no_special: bool,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<HashMap<CollectionId, Collection>, String> {
     log::info!("Getting collection tree.");
 
-    if let Ok(Some(storage)) = storage.lock().as_deref() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
         let mut collections = storage.collections.clone();
         if no_special {
             collections.remove(&storage.sp_collections.root);
@@ -708,11 +2350,16 @@ pub fn get_collection_tree(
 
 #[tauri::command]
 pub fn get_special_collections(
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<SpecialCollections, String> {
     log::info!("Getting special collections.");
 
-    if let Ok(Some(storage)) = storage.lock().as_deref() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
         Ok(storage.sp_collections)
     } else {
         Err(AppError::StorageNotInitialized.to_string())
@@ -720,10 +2367,17 @@ pub fn get_special_collections(
 }
 
 #[tauri::command]
-pub fn get_all_tags(storage: State<'_, Mutex<Option<Storage>>>) -> Result<Vec<Tag>, String> {
+pub fn get_all_tags(
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<Vec<Tag>, String> {
     log::info!("Getting all tags");
 
-    if let Ok(Some(storage)) = storage.lock().as_deref() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
         Ok(storage
             .tags
             .values()
@@ -736,10 +2390,17 @@ pub fn get_all_tags(storage: State<'_, Mutex<Option<Storage>>>) -> Result<Vec<Ta
 }
 
 #[tauri::command]
-pub fn get_all_assets(storage: State<'_, Mutex<Option<Storage>>>) -> Result<Vec<AssetId>, String> {
+pub fn get_all_assets(
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<Vec<AssetId>, String> {
     log::info!("Getting all assets");
 
-    if let Ok(Some(storage)) = storage.lock().as_deref() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
         Ok(storage
             .assets
             .values()
@@ -753,11 +2414,16 @@ pub fn get_all_assets(storage: State<'_, Mutex<Option<Storage>>>) -> Result<Vec<
 
 #[tauri::command]
 pub fn get_all_uncategorized_assets(
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<Vec<AssetId>, String> {
     log::info!("Getting all uncategorized assets");
 
-    if let Ok(Some(storage)) = storage.lock().as_deref() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
         Ok(storage
             .assets
             .values()
@@ -770,10 +2436,18 @@ pub fn get_all_uncategorized_assets(
 }
 
 #[tauri::command]
-pub fn modify_tag(new_tag: Tag, storage: State<'_, Mutex<Option<Storage>>>) -> Result<(), String> {
+pub fn modify_tag(
+    new_tag: Tag,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<(), String> {
     log::info!("Modifying tag: {:?}", new_tag);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
         storage.tags.insert(new_tag.id, new_tag);
         storage.save().map_err(|e| e.to_string())
     } else {
@@ -782,10 +2456,18 @@ pub fn modify_tag(new_tag: Tag, storage: State<'_, Mutex<Option<Storage>>>) -> R
 }
 
 #[tauri::command]
-pub fn get_item(item: ItemId, storage: State<'_, Mutex<Option<Storage>>>) -> Result<Item, String> {
+pub fn get_item(
+    item: ItemId,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
+) -> Result<Item, String> {
     log::info!("Getting item {:?}", item);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
         Ok(match item {
             ItemId::Asset(asset) => Item::Asset(
                 storage
@@ -817,11 +2499,16 @@ pub fn get_item(item: ItemId, storage: State<'_, Mutex<Option<Storage>>>) -> Res
 #[tauri::command]
 pub fn get_tags_on_asset(
     asset: AssetId,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<Vec<TagId>, String> {
     log::info!("Getting tags on asset {:?}", asset);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
         if let Some(asset) = storage.assets.get(&asset) {
             Ok(asset.tags.clone().into())
         } else {
@@ -844,11 +2531,16 @@ pub enum FilterStrategy {
 pub fn get_items(
     items: Vec<ItemId>,
     filter: FilterStrategy,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<Vec<Item>, String> {
     log::info!("Getting items {:?}", items);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
         let all = items.into_iter().filter_map(|id| match id {
             ItemId::Asset(a) => storage.assets.get(&a).cloned().map(|a| Item::Asset(a)),
             ItemId::Collection(c) => storage
@@ -872,11 +2564,16 @@ pub fn get_items(
 #[tauri::command]
 pub fn get_tags_without_conflict(
     tags: Vec<TagId>,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<Vec<Tag>, String> {
     log::info!("Getting tags without conflict {:?}", tags);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
         let tags = tags
             .iter()
             .filter_map(|id| storage.tags.get(id))
@@ -910,7 +2607,8 @@ pub fn get_tags_without_conflict(
 pub fn add_tag_to_assets(
     assets: Vec<AssetId>,
     tag: TagId,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
     data: State<'_, Mutex<AppData>>,
 ) -> Result<(), String> {
     log::info!("Adding tag to assets {:?} -> {:?}", tag, assets);
@@ -920,7 +2618,11 @@ pub fn add_tag_to_assets(
         .settings
         .get_as("general", "tagGroupConflictResolve")
         .unwrap();
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
         for asset in assets {
             storage
                 .add_tag_to_asset(asset, tag, resolve)
@@ -937,11 +2639,16 @@ pub fn add_tag_to_assets(
 pub fn remove_tag_from_assets(
     assets: Vec<AssetId>,
     tag: TagId,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<(), String> {
     log::info!("Removing tag from assets {:?} <- {:?}", tag, assets);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
         for asset in assets {
             storage
                 .remove_tag_from_asset(asset, tag)
@@ -957,11 +2664,16 @@ pub fn remove_tag_from_assets(
 #[tauri::command]
 pub fn get_assets_containing_tag(
     tag: TagId,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<Vec<AssetId>, String> {
     log::info!("Getting assets containing tag {:?}", tag);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
         storage
             .get_assets_containing_tag(tag)
             .map_err(|e| e.to_string())
@@ -974,11 +2686,16 @@ pub fn get_assets_containing_tag(
 pub fn modify_src_of(
     asset: AssetId,
     src: String,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<(), String> {
     log::info!("Modifying src of {:?} to {}", asset, src);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
         if let Some(asset) = storage.assets.get_mut(&asset) {
             asset.src = src;
             storage.save().map_err(|e| e.to_string())
@@ -994,11 +2711,16 @@ pub fn modify_src_of(
 pub fn delete_items(
     items: Vec<ItemId>,
     permanently: bool,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<(), String> {
     log::info!("Deleting items {:?}, permanently: {}", items, permanently);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
         if permanently {
             for item in items {
                 match item {
@@ -1029,11 +2751,16 @@ pub fn delete_items(
 pub fn create_tags(
     tag_names: Vec<String>,
     parent: CollectionId,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<(), String> {
     log::info!("Creating tags {:?} in {:?}", tag_names, parent);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
         for name in tag_names {
             storage
                 .create_tag(name, parent)
@@ -1050,7 +2777,8 @@ pub fn create_tags(
 pub fn create_collections(
     collection_names: Vec<String>,
     parent: CollectionId,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<(), String> {
     log::info!(
         "Creating collections {:?} in {:?}",
@@ -1058,7 +2786,11 @@ pub fn create_collections(
         parent
     );
 
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
         for name in collection_names {
             storage
                 .create_collection(name, parent)
@@ -1075,11 +2807,16 @@ pub fn create_collections(
 pub fn recolor_collection(
     collection: CollectionId,
     color: Option<Color>,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<(), String> {
     log::info!("Recoloring collections {:?} into {:?}", collection, color);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
         if storage.sp_collections.is_special(collection) {
             return Err(AppError::IllegalCollectionModification(collection).to_string());
         }
@@ -1097,11 +2834,16 @@ pub fn recolor_collection(
 pub fn rename_item(
     item: ItemId,
     name: String,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<(), String> {
     log::info!("Renaming item {:?}", item);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
         match item {
             ItemId::Asset(a) => storage.rename_asset(a, name),
             ItemId::Collection(c) => storage.rename_collection(c, name),
@@ -1118,11 +2860,16 @@ pub fn rename_item(
 pub fn move_tags_to(
     src_tags: Vec<TagId>,
     dst_collection: CollectionId,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<(), String> {
     log::info!("Moving tags {:?} to {:?}", src_tags, dst_collection);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
         for tag in src_tags {
             if let Err(e) = storage.move_tag_to(tag, dst_collection) {
                 return Err(e.to_string());
@@ -1139,13 +2886,18 @@ pub fn move_tags_to(
 pub fn regroup_tag(
     tag: TagId,
     group: Option<CollectionId>,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
     data: State<'_, Mutex<AppData>>,
 ) -> Result<(), String> {
     log::info!("Regrouping tag {:?} to {:?}", tag, group);
 
     let data = data.lock().map_err(|e| e.to_string())?;
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
         storage
             .regroup_tag(
                 tag,
@@ -1165,7 +2917,8 @@ pub fn regroup_tag(
 pub fn move_collections_to(
     src_collections: Vec<CollectionId>,
     dst_collection: CollectionId,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<(), String> {
     log::info!(
         "Moving collections {:?} to {:?}",
@@ -1173,7 +2926,11 @@ pub fn move_collections_to(
         dst_collection
     );
 
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
         for collection in src_collections {
             if let Err(e) = storage.move_collection_to(collection, dst_collection) {
                 return Err(e.to_string());
@@ -1186,15 +2943,6 @@ pub fn move_collections_to(
     }
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
-pub enum SearchQueryTy {
-    AssetId,
-    AssetName,
-    TagId,
-    TagName,
-}
-
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 #[serde(tag = "ty", content = "data")]
@@ -1207,29 +2955,17 @@ pub enum SearchQueryResult {
 pub async fn global_search(
     ty: SearchQueryTy,
     query: String,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<SearchQueryResult, String> {
     log::info!("Global searching {:?} {}", ty, query);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
         Ok(match ty {
-            SearchQueryTy::AssetId => SearchQueryResult::Assets(
-                storage
-                    .assets
-                    .iter()
-                    .filter(|(_, asset)| !asset.is_deleted)
-                    .filter(|(id, _)| id.0.to_string().contains(&query))
-                    .map(|(_, asset)| asset.clone())
-                    .collect(),
-            ),
-            SearchQueryTy::AssetName => SearchQueryResult::Assets(
-                storage
-                    .assets
-                    .values()
-                    .filter(|asset| !asset.is_deleted && asset.name.contains(&query))
-                    .cloned()
-                    .collect(),
-            ),
             SearchQueryTy::TagId => SearchQueryResult::Tags(
                 storage
                     .tags
@@ -1242,7 +2978,15 @@ pub async fn global_search(
                 storage
                     .tags
                     .values()
-                    .filter(|tag| &tag.name.contains(&query))
+                    .filter(|tag| tag.name.contains(&query))
+                    .cloned()
+                    .collect(),
+            ),
+            ty => SearchQueryResult::Assets(
+                storage
+                    .assets
+                    .values()
+                    .filter(|asset| !asset.is_deleted && ty.matches_asset(asset, &query))
                     .cloned()
                     .collect(),
             ),
@@ -1255,14 +2999,19 @@ pub async fn global_search(
 #[tauri::command]
 pub fn open_with_default_app(
     asset: AssetId,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<(), String> {
     log::info!("Opening asset {:?} with default app.", asset);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
         opener::open(
             storage
-                .get_asset_abs_path(asset)
+                .get_asset_plain_path(asset)
                 .map_err(|e| e.to_string())?,
         )
         .map_err(|e| e.to_string())
@@ -1281,7 +3030,8 @@ pub enum QuickRefSrcTy {
 #[tauri::command]
 pub async fn quick_ref(
     ty: QuickRefSrcTy,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
     app: AppHandle,
 ) -> Result<(), String> {
     log::info!("Creating quick refs {:?}", ty);
@@ -1295,7 +3045,11 @@ pub async fn quick_ref(
     };
     let screen_resolution = [monitor.size().width, monitor.size().height];
 
-    if let Ok(Some(storage)) = storage.lock().as_deref() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
         let ids: Vec<_> = match &ty {
             QuickRefSrcTy::Asset(ids) => ids.clone(),
             QuickRefSrcTy::Tag(id) => storage
@@ -1347,11 +3101,16 @@ pub fn compute_camera_pos(
     y_fov: f32,
     aspect_ratio: f32,
     asset: AssetId,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<[f32; 3], String> {
     log::info!("Computing camera pos for {:?}", asset);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
         if let Some(asset) = storage.assets.get(&asset) {
             match &asset.props {
                 AssetProperty::GltfModel(prop) => {
@@ -1372,11 +3131,16 @@ pub fn save_render_cache(
     asset: AssetId,
     base64_data: String,
     camera: GltfPreviewCamera,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<(), String> {
     log::info!("Saving render result for {:?}", asset);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref_mut() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref_mut()
+        .map(|l| l.get_mut(library.as_deref()))
+    {
         if let Some(asset) = storage.assets.get_mut(&asset) {
             let file_name = format!("{}.png", asset.id.0);
 
@@ -1386,11 +3150,13 @@ pub fn save_render_cache(
                         &base64::alphabet::STANDARD,
                         Default::default(),
                     );
-                    write(
-                        storage.cache.root.join(CACHE).join(&file_name),
-                        engine.decode(base64_data).map_err(|e| e.to_string())?,
-                    )
-                    .map_err(|e| e.to_string())?;
+                    storage
+                        .cache
+                        .write_file(
+                            &storage.cache.root.join(CACHE).join(&file_name),
+                            &engine.decode(base64_data).map_err(|e| e.to_string())?,
+                        )
+                        .map_err(|e| e.to_string())?;
                     prop.cache_camera = Some(camera);
 
                     storage.save().map_err(|e| e.to_string())
@@ -1414,11 +3180,16 @@ pub struct GltfPreviewCache {
 #[tauri::command]
 pub fn get_render_cache(
     asset: AssetId,
-    storage: State<'_, Mutex<Option<Storage>>>,
+    library: Option<PathBuf>,
+    libraries: State<'_, Mutex<Libraries>>,
 ) -> Result<Option<GltfPreviewCache>, String> {
     log::info!("Getting render result for {:?}", asset);
 
-    if let Ok(Some(storage)) = storage.lock().as_deref() {
+    if let Ok(Some(storage)) = libraries
+        .lock()
+        .as_deref()
+        .map(|l| l.get(library.as_deref()))
+    {
         if let Some(asset) = storage.assets.get(&asset) {
             match &asset.props {
                 AssetProperty::GltfModel(prop) => {
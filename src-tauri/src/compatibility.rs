@@ -134,6 +134,12 @@ pub fn load_legacy_storage(root: impl AsRef<Path>) -> AppResult<Storage> {
                             ungrouped: asset.tags.into_iter().collect(),
                         },
                         src: asset.src,
+                        source_page: None,
+                        embedded_meta: None,
+                        aigc: None,
+                        tag_suggestions: None,
+                        ocr_text: None,
+                        link: None,
                     },
                 )
             })
@@ -147,9 +153,14 @@ pub fn load_legacy_storage(root: impl AsRef<Path>) -> AppResult<Storage> {
                 .map(|f| f.id.into())
                 .collect(),
             tags: Default::default(),
+            deleted_at: Default::default(),
+            cascades: Default::default(),
         },
         lib_meta: storage.lib_meta,
         collections: collections.into_iter().collect(),
+        watch_folders: Default::default(),
+        http: None,
+        metadata_mapping: Default::default(),
     };
 
     let removed = storage
@@ -0,0 +1,671 @@
+use std::{
+    fs::{read, remove_file, rename, write},
+    io::{Read, Seek, SeekFrom},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, Payload},
+    KeyInit, XChaCha20Poly1305, XNonce,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::{AppError, AppResult, Storage, CACHE},
+    download, rendition,
+    semantic::EmbeddingIndex,
+    snapshot,
+};
+
+/// Present next to `snowflake.json` when the library is encrypted.
+pub const KEY_FILE: &str = "snowflake.key";
+/// Present while the data key is being rotated, see [`Rotation`].
+pub const ROTATION_FILE: &str = "snowflake.key.rotating";
+
+/// Sealed files start with this, followed by the random nonce prefix.
+pub const MAGIC: &[u8; 8] = b"SFENC\0\0\x01";
+const NONCE_PREFIX: usize = 16;
+pub const HEADER_LEN: usize = MAGIC.len() + NONCE_PREFIX;
+/// Plaintext is sealed in chunks, so ranges can be decrypted on their own.
+pub const CHUNK: usize = 64 * 1024;
+const TAG_LEN: usize = 16;
+pub const SEALED_CHUNK: usize = CHUNK + TAG_LEN;
+
+pub fn is_sealed(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// The nonce of a chunk binds its position and whether it is the last one,
+/// so chunks can't be reordered and the file can't be truncated unnoticed.
+fn chunk_nonce(prefix: &[u8], index: u32, last: bool) -> XNonce {
+    let mut nonce = [0u8; 24];
+    nonce[..NONCE_PREFIX].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX..NONCE_PREFIX + 4].copy_from_slice(&index.to_be_bytes());
+    nonce[NONCE_PREFIX + 4] = last as u8;
+    *XNonce::from_slice(&nonce)
+}
+
+/// The data key of an unlocked library. The password only wraps it, so
+/// changing the password doesn't touch the files.
+pub struct LibraryCipher {
+    key: [u8; 32],
+    aead: XChaCha20Poly1305,
+    /// The key being rotated away from, files not encrypted again yet still
+    /// open with it.
+    previous: Option<XChaCha20Poly1305>,
+}
+
+impl std::fmt::Debug for LibraryCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("LibraryCipher")
+    }
+}
+
+impl LibraryCipher {
+    fn from_key(key: [u8; 32]) -> Self {
+        Self {
+            aead: XChaCha20Poly1305::new(&key.into()),
+            key,
+            previous: None,
+        }
+    }
+
+    fn from_sealed_key(sealed: &str, cipher: &LibraryCipher) -> AppResult<Self> {
+        let key = cipher.decrypt(
+            &BASE64_STANDARD
+                .decode(sealed)
+                .map_err(|e| AppError::Crypto(e.to_string()))?,
+        )?;
+        Ok(Self::from_key(key.try_into().map_err(|_| {
+            AppError::Crypto("Invalid key file.".to_string())
+        })?))
+    }
+
+    fn seal_key(&self, key: &LibraryCipher) -> AppResult<String> {
+        Ok(BASE64_STANDARD.encode(self.encrypt(&key.key)?))
+    }
+
+    fn with_previous(mut self, previous: &LibraryCipher) -> Self {
+        self.previous = Some(previous.aead.clone());
+        self
+    }
+
+    pub fn is_rotating(&self) -> bool {
+        self.previous.is_some()
+    }
+
+    /// Encrypts a file sealed with the previous key again, `None` if it is
+    /// sealed with the current one already.
+    fn reseal(&self, sealed: &[u8]) -> AppResult<Option<Vec<u8>>> {
+        if Self::from_key(self.key).decrypt(sealed).is_ok() {
+            return Ok(None);
+        }
+        Ok(Some(self.encrypt(&self.decrypt(sealed)?)?))
+    }
+
+    pub fn generate() -> Self {
+        Self::from_key(rand::thread_rng().gen())
+    }
+
+    pub fn encrypt(&self, plain: &[u8]) -> AppResult<Vec<u8>> {
+        let prefix = rand::thread_rng().gen::<[u8; NONCE_PREFIX]>();
+        let count = plain.len().div_ceil(CHUNK).max(1);
+
+        let mut sealed = Vec::with_capacity(HEADER_LEN + plain.len() + count * TAG_LEN);
+        sealed.extend_from_slice(MAGIC);
+        sealed.extend_from_slice(&prefix);
+        for index in 0..count {
+            let chunk = &plain[index * CHUNK..((index + 1) * CHUNK).min(plain.len())];
+            let nonce = chunk_nonce(&prefix, index as u32, index + 1 == count);
+            sealed.extend(
+                self.aead
+                    .encrypt(
+                        &nonce,
+                        Payload {
+                            msg: chunk,
+                            aad: MAGIC,
+                        },
+                    )
+                    .map_err(|e| AppError::Crypto(e.to_string()))?,
+            );
+        }
+
+        Ok(sealed)
+    }
+
+    pub fn decrypt(&self, sealed: &[u8]) -> AppResult<Vec<u8>> {
+        if !is_sealed(sealed) || sealed.len() < HEADER_LEN + TAG_LEN {
+            return Err(AppError::Crypto("Not an encrypted file.".to_string()));
+        }
+
+        let prefix = &sealed[MAGIC.len()..HEADER_LEN];
+        let body = &sealed[HEADER_LEN..];
+        let count = body.len().div_ceil(SEALED_CHUNK);
+
+        let mut plain = Vec::with_capacity(body.len() - count * TAG_LEN);
+        for (index, chunk) in body.chunks(SEALED_CHUNK).enumerate() {
+            plain.extend(self.decrypt_chunk(prefix, index, index + 1 == count, chunk)?);
+        }
+
+        Ok(plain)
+    }
+
+    /// Decrypts `range` of the plaintext of a sealed file, reading only the
+    /// chunks it spans.
+    pub fn decrypt_range(
+        &self,
+        file: &mut (impl Read + Seek),
+        sealed_len: u64,
+        range: Range<u64>,
+    ) -> AppResult<Vec<u8>> {
+        if range.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut header = [0u8; HEADER_LEN];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut header)?;
+        if !is_sealed(&header) {
+            return Err(AppError::Crypto("Not an encrypted file.".to_string()));
+        }
+
+        let body = sealed_len.saturating_sub(HEADER_LEN as u64);
+        let count = body.div_ceil(SEALED_CHUNK as u64) as usize;
+        let first = (range.start / CHUNK as u64) as usize;
+        let last = ((range.end - 1) / CHUNK as u64) as usize;
+
+        let mut plain = Vec::with_capacity((last - first + 1) * CHUNK);
+        let mut chunk = Vec::with_capacity(SEALED_CHUNK);
+        for index in first..=last.min(count.saturating_sub(1)) {
+            let offset = (index * SEALED_CHUNK) as u64;
+            chunk.resize((body - offset).min(SEALED_CHUNK as u64) as usize, 0);
+            file.seek(SeekFrom::Start(HEADER_LEN as u64 + offset))?;
+            file.read_exact(&mut chunk)?;
+            plain.extend(self.decrypt_chunk(
+                &header[MAGIC.len()..],
+                index,
+                index + 1 == count,
+                &chunk,
+            )?);
+        }
+
+        let start = (range.start - (first * CHUNK) as u64) as usize;
+        let end = (start + (range.end - range.start) as usize).min(plain.len());
+        Ok(plain[start.min(end)..end].to_vec())
+    }
+
+    fn decrypt_chunk(
+        &self,
+        prefix: &[u8],
+        index: usize,
+        last: bool,
+        chunk: &[u8],
+    ) -> AppResult<Vec<u8>> {
+        let nonce = chunk_nonce(prefix, index as u32, last);
+        let payload = || Payload {
+            msg: chunk,
+            aad: MAGIC,
+        };
+        self.aead
+            .decrypt(&nonce, payload())
+            .or_else(|err| match &self.previous {
+                Some(previous) => previous.decrypt(&nonce, payload()),
+                None => Err(err),
+            })
+            .map_err(|_| AppError::Crypto("File is corrupted.".to_string()))
+    }
+}
+
+/// Length of the plaintext of a sealed file.
+pub fn plain_len(sealed_len: u64) -> u64 {
+    let body = sealed_len.saturating_sub(HEADER_LEN as u64);
+    body.saturating_sub(body.div_ceil(SEALED_CHUNK as u64) * TAG_LEN as u64)
+}
+
+/// Decrypts the bytes if they are sealed, plaintext passes through so
+/// linked files and half encrypted libraries still read.
+pub fn open(bytes: Vec<u8>, cipher: Option<&LibraryCipher>) -> AppResult<Vec<u8>> {
+    if !is_sealed(&bytes) {
+        return Ok(bytes);
+    }
+
+    match cipher {
+        Some(cipher) => cipher.decrypt(&bytes),
+        None => Err(AppError::LibraryEncrypted),
+    }
+}
+
+pub fn read_file(path: &Path, cipher: Option<&LibraryCipher>) -> AppResult<Vec<u8>> {
+    open(read(path)?, cipher)
+}
+
+fn seal_file(path: &Path, cipher: &LibraryCipher) -> AppResult<()> {
+    let bytes = read(path)?;
+    if !is_sealed(&bytes) {
+        write(path, cipher.encrypt(&bytes)?)?;
+    }
+    Ok(())
+}
+
+fn unseal_file(path: &Path, cipher: &LibraryCipher) -> AppResult<()> {
+    let bytes = read(path)?;
+    if is_sealed(&bytes) {
+        write(path, cipher.decrypt(&bytes)?)?;
+    }
+    Ok(())
+}
+
+/// Written aside and renamed, so an interrupted rotation never leaves a
+/// file half encrypted with either key.
+fn reseal_file(path: &Path, cipher: &LibraryCipher) -> AppResult<()> {
+    let bytes = read(path)?;
+    if !is_sealed(&bytes) {
+        return Ok(());
+    }
+    if let Some(sealed) = cipher.reseal(&bytes)? {
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        write(&temp, sealed)?;
+        rename(&temp, path)?;
+    }
+    Ok(())
+}
+
+/// The data key wrapped with a key derived from the password.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyFile {
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+    pub salt: String,
+    pub nonce: String,
+    pub wrapped_key: String,
+}
+
+impl KeyFile {
+    pub fn load(root: &Path) -> AppResult<Option<Self>> {
+        match read(root.join(KEY_FILE)) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self, root: &Path) -> AppResult<()> {
+        write(root.join(KEY_FILE), serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn wrap(password: &str, cipher: &LibraryCipher) -> AppResult<Self> {
+        let mut rng = rand::thread_rng();
+        let params = Params::default();
+        let salt = rng.gen::<[u8; 16]>();
+        let nonce = rng.gen::<[u8; 24]>();
+
+        let wrapped_key = Self::derive(password, &salt, &params)?
+            .encrypt(XNonce::from_slice(&nonce), cipher.key.as_slice())
+            .map_err(|e| AppError::Crypto(e.to_string()))?;
+
+        Ok(Self {
+            m_cost: params.m_cost(),
+            t_cost: params.t_cost(),
+            p_cost: params.p_cost(),
+            salt: BASE64_STANDARD.encode(salt),
+            nonce: BASE64_STANDARD.encode(nonce),
+            wrapped_key: BASE64_STANDARD.encode(wrapped_key),
+        })
+    }
+
+    pub fn unlock(&self, password: &str) -> AppResult<LibraryCipher> {
+        let decode = |s: &str| {
+            BASE64_STANDARD
+                .decode(s)
+                .map_err(|e| AppError::Crypto(e.to_string()))
+        };
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, None)
+            .map_err(|e| AppError::Crypto(e.to_string()))?;
+        let nonce = decode(&self.nonce)?;
+        if nonce.len() != 24 {
+            return Err(AppError::Crypto("Invalid key file.".to_string()));
+        }
+
+        let key = Self::derive(password, &decode(&self.salt)?, &params)?
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                decode(&self.wrapped_key)?.as_slice(),
+            )
+            .map_err(|_| AppError::WrongPassword)?;
+
+        Ok(LibraryCipher::from_key(key.try_into().map_err(|_| {
+            AppError::Crypto("Invalid key file.".to_string())
+        })?))
+    }
+
+    fn derive(password: &str, salt: &[u8], params: &Params) -> AppResult<XChaCha20Poly1305> {
+        let mut key = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone())
+            .hash_password_into(password.as_bytes(), salt, &mut key)
+            .map_err(|e| AppError::Crypto(e.to_string()))?;
+        Ok(XChaCha20Poly1305::new(&key.into()))
+    }
+}
+
+/// Saved before any file is encrypted with a new data key, so a rotation
+/// cut short is finished on the next load. Each key is sealed with the
+/// other, either password unlocks both.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Rotation {
+    /// The new key wrapped with the new password, the key file once done.
+    pub key: KeyFile,
+    pub new_key: String,
+    pub old_key: String,
+}
+
+impl Rotation {
+    pub fn load(root: &Path) -> AppResult<Option<Self>> {
+        match read(root.join(ROTATION_FILE)) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self, root: &Path) -> AppResult<()> {
+        write(root.join(ROTATION_FILE), serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Returns the old and the new data key.
+    fn unlock(&self, key: &KeyFile, password: &str) -> AppResult<(LibraryCipher, LibraryCipher)> {
+        match key.unlock(password) {
+            Ok(old) => {
+                let new = LibraryCipher::from_sealed_key(&self.new_key, &old)?;
+                Ok((old, new))
+            }
+            Err(AppError::WrongPassword) => {
+                let new = self.key.unlock(password)?;
+                let old = LibraryCipher::from_sealed_key(&self.old_key, &new)?;
+                Ok((old, new))
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Unlocks the library at `root` if it is encrypted. A rotation which was
+/// interrupted yields a cipher holding both keys, see [`resume_rotation`].
+pub fn unlock(root: &Path, password: Option<&str>) -> AppResult<Option<Arc<LibraryCipher>>> {
+    let (key, password) = match (KeyFile::load(root)?, password) {
+        (None, _) => return Ok(None),
+        (Some(_), None) => return Err(AppError::LibraryEncrypted),
+        (Some(key), Some(password)) => (key, password),
+    };
+
+    match Rotation::load(root)? {
+        // Stopped right after the new key file replaced the old one.
+        Some(rotation) if rotation.key.wrapped_key == key.wrapped_key => {
+            remove_file(root.join(ROTATION_FILE))?;
+            Ok(Some(Arc::new(key.unlock(password)?)))
+        }
+        Some(rotation) => {
+            let (old, new) = rotation.unlock(&key, password)?;
+            Ok(Some(Arc::new(new.with_previous(&old))))
+        }
+        None => Ok(Some(Arc::new(key.unlock(password)?))),
+    }
+}
+
+/// Files sealed with the data key besides `snowflake.json`. Linked assets
+/// live outside of the library and stay as they are. Renditions are thrown
+/// away instead, see [`rendition::clear_renditions`].
+fn sealed_files(storage: &Storage) -> AppResult<Vec<PathBuf>> {
+    let root = &storage.cache.root;
+    let mut files = storage
+        .assets
+        .values()
+        .filter(|asset| asset.link.is_none())
+        .map(|asset| asset.get_storage_path(root))
+        .filter(|path| path.is_file())
+        .collect::<Vec<_>>();
+    files.extend(
+        storage
+            .assets
+            .keys()
+            .map(|id| root.join(CACHE).join(format!("{}.png", id.0)))
+            .filter(|path| path.is_file()),
+    );
+    files.extend(Some(EmbeddingIndex::path(root)).filter(|path| path.is_file()));
+    files.extend(
+        snapshot::list_snapshots(root)?
+            .iter()
+            .map(|s| snapshot::snapshot_path(root, &s.id)),
+    );
+    Ok(files)
+}
+
+/// The key file is written first, a library interrupted halfway is still
+/// readable since plaintext files pass through.
+pub fn encrypt_library(storage: &mut Storage, password: &str) -> AppResult<()> {
+    let root = storage.cache.root.clone();
+    if KeyFile::load(&root)?.is_some() {
+        return Err(AppError::LibraryAlreadyEncrypted);
+    }
+
+    let write_back = storage
+        .watch_folders
+        .iter()
+        .any(|f| f.sync.as_ref().is_some_and(|s| s.write_back));
+    if storage.cache.sync.is_some() || write_back {
+        return Err(AppError::EncryptedLibrarySync);
+    }
+
+    storage.save()?;
+    let cipher = LibraryCipher::generate();
+    KeyFile::wrap(password, &cipher)?.save(&root)?;
+    for path in sealed_files(storage)? {
+        seal_file(&path, &cipher)?;
+    }
+    rendition::clear_renditions(&root)?;
+    download::remove_partial_downloads(&root.join(CACHE))?;
+
+    storage.cache.cipher = Some(Arc::new(cipher));
+    storage.overwrite()
+}
+
+pub fn decrypt_library(storage: &mut Storage, password: &str) -> AppResult<()> {
+    let root = storage.cache.root.clone();
+    let Some(key) = KeyFile::load(&root)? else {
+        return Err(AppError::LibraryNotEncrypted);
+    };
+
+    if Rotation::load(&root)?.is_some() {
+        return Err(AppError::KeyRotationInProgress);
+    }
+
+    let cipher = key.unlock(password)?;
+    storage.save()?;
+    for path in sealed_files(storage)? {
+        unseal_file(&path, &cipher)?;
+    }
+    rendition::clear_renditions(&root)?;
+
+    storage.cache.cipher = None;
+    storage.overwrite()?;
+    remove_file(root.join(KEY_FILE))?;
+    Ok(())
+}
+
+/// Changes the password. With `rotate` a new data key is generated and
+/// every sealed file is encrypted again, which takes as long as encrypting
+/// the library in the first place.
+pub fn rekey_library(
+    storage: &mut Storage,
+    old_password: &str,
+    new_password: &str,
+    rotate: bool,
+) -> AppResult<()> {
+    let root = storage.cache.root.clone();
+    let Some(key) = KeyFile::load(&root)? else {
+        return Err(AppError::LibraryNotEncrypted);
+    };
+
+    if Rotation::load(&root)?.is_some() {
+        return Err(AppError::KeyRotationInProgress);
+    }
+
+    let current = key.unlock(old_password)?;
+    if !rotate {
+        return KeyFile::wrap(new_password, &current)?.save(&root);
+    }
+
+    storage.save()?;
+    let cipher = LibraryCipher::generate();
+    Rotation {
+        key: KeyFile::wrap(new_password, &cipher)?,
+        new_key: current.seal_key(&cipher)?,
+        old_key: cipher.seal_key(&current)?,
+    }
+    .save(&root)?;
+
+    storage.cache.cipher = Some(Arc::new(cipher.with_previous(&current)));
+    resume_rotation(storage)
+}
+
+/// Encrypts the files still sealed with the previous key again, then makes
+/// the new key file the current one. Safe to run again after any step.
+pub fn resume_rotation(storage: &mut Storage) -> AppResult<()> {
+    let root = storage.cache.root.clone();
+    let Some(cipher) = storage.cache.cipher.clone().filter(|c| c.is_rotating()) else {
+        return Ok(());
+    };
+    let Some(rotation) = Rotation::load(&root)? else {
+        return Ok(());
+    };
+
+    log::info!("Rotating the data key of {:?}.", root);
+    for path in sealed_files(storage)? {
+        reseal_file(&path, &cipher)?;
+    }
+    rendition::clear_renditions(&root)?;
+
+    storage.cache.cipher = Some(Arc::new(LibraryCipher::from_key(cipher.key)));
+    storage.overwrite()?;
+
+    let temp = root.join(format!("{}.tmp", KEY_FILE));
+    write(&temp, serde_json::to_vec_pretty(&rotation.key)?)?;
+    rename(&temp, root.join(KEY_FILE))?;
+    remove_file(root.join(ROTATION_FILE))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const SIZES: &[usize] = &[0, 1, CHUNK - 1, CHUNK, CHUNK + 1, 3 * CHUNK + 17];
+
+    fn plain(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 31 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let cipher = LibraryCipher::generate();
+        for &len in SIZES {
+            let sealed = cipher.encrypt(&plain(len)).unwrap();
+            assert!(is_sealed(&sealed));
+            assert_eq!(plain_len(sealed.len() as u64), len as u64);
+            assert_eq!(cipher.decrypt(&sealed).unwrap(), plain(len));
+        }
+    }
+
+    #[test]
+    fn truncation_is_detected() {
+        let cipher = LibraryCipher::generate();
+        let sealed = cipher.encrypt(&plain(3 * CHUNK + 17)).unwrap();
+
+        // Dropping whole chunks leaves a last chunk not marked as such.
+        let whole_chunks = &sealed[..HEADER_LEN + 2 * SEALED_CHUNK];
+        assert!(cipher.decrypt(whole_chunks).is_err());
+        assert!(cipher.decrypt(&sealed[..sealed.len() - 1]).is_err());
+
+        let mut flipped = sealed.clone();
+        flipped[HEADER_LEN + 5] ^= 1;
+        assert!(cipher.decrypt(&flipped).is_err());
+    }
+
+    #[test]
+    fn wrong_key_fails() {
+        let sealed = LibraryCipher::generate().encrypt(&plain(10)).unwrap();
+        assert!(LibraryCipher::generate().decrypt(&sealed).is_err());
+    }
+
+    #[test]
+    fn ranges_match_the_plaintext() {
+        let cipher = LibraryCipher::generate();
+        let len = 3 * CHUNK + 17;
+        let content = plain(len);
+        let sealed = cipher.encrypt(&content).unwrap();
+
+        for range in [
+            0..1,
+            0..len as u64,
+            5..CHUNK as u64,
+            CHUNK as u64 - 3..CHUNK as u64 + 3,
+            2 * CHUNK as u64..len as u64,
+            len as u64 - 1..len as u64,
+        ] {
+            let mut file = Cursor::new(&sealed);
+            let bytes = cipher
+                .decrypt_range(&mut file, sealed.len() as u64, range.clone())
+                .unwrap();
+            assert_eq!(bytes, content[range.start as usize..range.end as usize]);
+        }
+    }
+
+    #[test]
+    fn rotation_reseals_with_the_new_key() {
+        let old = LibraryCipher::generate();
+        let new = LibraryCipher::generate();
+        let rotating = LibraryCipher::from_key(new.key).with_previous(&old);
+
+        let sealed = old.encrypt(&plain(CHUNK + 1)).unwrap();
+        assert_eq!(rotating.decrypt(&sealed).unwrap(), plain(CHUNK + 1));
+
+        let resealed = rotating.reseal(&sealed).unwrap().unwrap();
+        assert_eq!(new.decrypt(&resealed).unwrap(), plain(CHUNK + 1));
+        assert!(old.decrypt(&resealed).is_err());
+        assert!(rotating.reseal(&resealed).unwrap().is_none());
+    }
+
+    #[test]
+    fn either_password_unlocks_a_rotation() {
+        let old = LibraryCipher::generate();
+        let new = LibraryCipher::generate();
+        let key = KeyFile::wrap("old", &old).unwrap();
+        let rotation = Rotation {
+            key: KeyFile::wrap("new", &new).unwrap(),
+            new_key: old.seal_key(&new).unwrap(),
+            old_key: new.seal_key(&old).unwrap(),
+        };
+
+        for password in ["old", "new"] {
+            let (unlocked_old, unlocked_new) = rotation.unlock(&key, password).unwrap();
+            assert_eq!(unlocked_old.key, old.key);
+            assert_eq!(unlocked_new.key, new.key);
+        }
+        assert!(matches!(
+            rotation.unlock(&key, "other"),
+            Err(AppError::WrongPassword)
+        ));
+    }
+}
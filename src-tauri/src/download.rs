@@ -0,0 +1,376 @@
+use std::{
+    fs::{metadata, read, read_dir, remove_file, File, OpenOptions},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use file_format::FileFormat;
+use futures::{
+    future::{AbortHandle, AbortRegistration},
+    StreamExt,
+};
+use hashbrown::HashMap;
+use reqwest::{
+    header::{CONTENT_TYPE, RANGE},
+    Client, Response, StatusCode, Url,
+};
+
+use crate::{
+    app::{AssetType, RawAsset, UserSettings},
+    event::{DownloadEvent, DownloadStatus},
+    web,
+};
+
+const SETTINGS_CATEGORY: &str = "network";
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+/// Enough to tell the format and whether it is a page.
+const SNIFF_LEN: u64 = 64 * 1024;
+/// Pages larger than this are scraped partially.
+const MAX_HTML_LEN: u64 = 8 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy)]
+pub struct DownloadSettings {
+    pub max_concurrent: usize,
+    pub timeout: Duration,
+    pub max_retries: u32,
+    pub max_size: u64,
+}
+
+impl Default for DownloadSettings {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 4,
+            timeout: Duration::from_secs(30),
+            max_retries: 3,
+            max_size: 1024 * 1024 * 1024,
+        }
+    }
+}
+
+impl DownloadSettings {
+    pub fn from_user_settings(settings: &UserSettings) -> Self {
+        let default = Self::default();
+        let get = |item| settings.get_as::<f64>(SETTINGS_CATEGORY, item);
+
+        Self {
+            max_concurrent: get("maxConcurrentDownloads")
+                .map(|n| (n as usize).max(1))
+                .unwrap_or(default.max_concurrent),
+            timeout: get("downloadTimeout")
+                .filter(|secs| *secs > 0.0)
+                .map(Duration::from_secs_f64)
+                .unwrap_or(default.timeout),
+            max_retries: get("downloadRetries")
+                .map(|n| n as u32)
+                .unwrap_or(default.max_retries),
+            // In MiB.
+            max_size: get("maxDownloadSize")
+                .map(|size| (size * 1024.0 * 1024.0) as u64)
+                .unwrap_or(default.max_size),
+        }
+    }
+}
+
+/// Keeps the abort handles of running downloads, keyed by the progress
+/// channel and the download id within it.
+#[derive(Default)]
+pub struct DownloadManager {
+    handles: HashMap<(u32, u32), AbortHandle>,
+}
+
+impl DownloadManager {
+    pub fn register(&mut self, channel: u32, id: u32) -> AbortRegistration {
+        let (handle, registration) = AbortHandle::new_pair();
+        self.handles.insert((channel, id), handle);
+        registration
+    }
+
+    pub fn unregister(&mut self, channel: u32, id: u32) {
+        self.handles.remove(&(channel, id));
+    }
+
+    pub fn cancel(&mut self, channel: u32, id: u32) -> bool {
+        if let Some(handle) = self.handles.remove(&(channel, id)) {
+            handle.abort();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+enum FetchError {
+    Retryable(String),
+    Fatal(String),
+}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(value: reqwest::Error) -> Self {
+        if value.is_timeout() || value.is_connect() || value.is_body() || value.is_request() {
+            Self::Retryable(value.to_string())
+        } else {
+            Self::Fatal(value.to_string())
+        }
+    }
+}
+
+impl From<std::io::Error> for FetchError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Fatal(value.to_string())
+    }
+}
+
+struct Fetched {
+    url: Url,
+    content_type: Option<String>,
+    total: Option<f32>,
+}
+
+pub struct Download<'a> {
+    /// Tells concurrent batches of downloads apart, the ids are only unique
+    /// within one.
+    pub channel: u32,
+    pub client: &'a Client,
+    pub settings: DownloadSettings,
+    pub temp_dir: &'a Path,
+    pub progress: &'a (dyn Fn(DownloadEvent) + Sync),
+}
+
+/// Reads the body of a page to scrape, which is cut off like pages met by
+/// downloads and never read beyond `maxDownloadSize`.
+pub async fn read_page(
+    response: Response,
+    settings: &DownloadSettings,
+) -> reqwest::Result<Vec<u8>> {
+    let limit = MAX_HTML_LEN.min(settings.max_size) as usize;
+    let mut content = Vec::new();
+    let mut stream = response.bytes_stream();
+    while let Some(bytes) = stream.next().await {
+        let bytes = bytes?;
+        let rest = limit - content.len();
+        content.extend_from_slice(&bytes[..bytes.len().min(rest)]);
+        if content.len() == limit {
+            break;
+        }
+    }
+    Ok(content)
+}
+
+/// Removes what interrupted downloads left in `dir`.
+pub fn remove_partial_downloads(dir: &Path) -> std::io::Result<()> {
+    for entry in read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "part") {
+            remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+impl<'a> Download<'a> {
+    pub fn temp_path(&self, id: u32, url: &str) -> PathBuf {
+        self.temp_dir.join(format!(
+            "{:x}-{}-{}.part",
+            md5::compute(url),
+            self.channel,
+            id
+        ))
+    }
+
+    fn send(&self, id: u32, downloaded: f32, total: Option<f32>, status: DownloadStatus) {
+        (self.progress)(DownloadEvent {
+            id,
+            downloaded,
+            total,
+            status,
+        });
+    }
+
+    /// Downloads `url` into a temp file, resuming it if a previous attempt
+    /// left one behind. Failures are reported through the progress channel.
+    pub async fn fetch(&self, id: u32, url: &str, page: Option<&str>) -> Option<RawAsset> {
+        let temp = self.temp_path(id, url);
+        let mut attempt = 0;
+
+        let fetched = loop {
+            match self.try_fetch(id, url, &temp).await {
+                Ok(fetched) => break fetched,
+                Err(FetchError::Retryable(err)) if attempt < self.settings.max_retries => {
+                    attempt += 1;
+                    log::warn!("Download {} failed, retrying: {}", url, err);
+                    self.send(id, 0.0, None, DownloadStatus::Retrying(attempt));
+                    let delay = 2u32
+                        .checked_pow(attempt - 1)
+                        .and_then(|factor| RETRY_BASE_DELAY.checked_mul(factor))
+                        .map_or(RETRY_MAX_DELAY, |delay| delay.min(RETRY_MAX_DELAY));
+                    tokio::time::sleep(delay).await;
+                }
+                Err(FetchError::Retryable(err) | FetchError::Fatal(err)) => {
+                    let _ = remove_file(&temp);
+                    self.send(id, 0.0, None, DownloadStatus::Error(err));
+                    return None;
+                }
+            }
+        };
+
+        let result = self.read_fetched(id, &temp, &fetched, page);
+        let _ = remove_file(&temp);
+        match result {
+            Ok(raw) => raw,
+            Err(err) => {
+                self.send(id, 0.0, None, DownloadStatus::Error(err.to_string()));
+                None
+            }
+        }
+    }
+
+    /// Only the head of the file is read until it is known to be an asset.
+    fn read_fetched(
+        &self,
+        id: u32,
+        temp: &Path,
+        fetched: &Fetched,
+        page: Option<&str>,
+    ) -> std::io::Result<Option<RawAsset>> {
+        let mut head = Vec::new();
+        File::open(temp)?.take(SNIFF_LEN).read_to_end(&mut head)?;
+
+        if web::is_html(fetched.content_type.as_deref(), &head) {
+            let mut content = Vec::new();
+            File::open(temp)?
+                .take(MAX_HTML_LEN)
+                .read_to_end(&mut content)?;
+            let html = String::from_utf8_lossy(&content);
+            self.send(
+                id,
+                f32::MAX,
+                fetched.total,
+                DownloadStatus::Scraped(web::scrape_media(&fetched.url, &html)),
+            );
+            return Ok(None);
+        }
+
+        let fmt = FileFormat::from_bytes(&head);
+        let ext = if AssetType::from_ext(fmt.extension()).is_some() {
+            Some(fmt.extension().to_string())
+        } else {
+            // Some formats can't be told apart from plain text by content alone.
+            fetched
+                .url
+                .path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .and_then(|name| name.rsplit_once('.'))
+                .map(|(_, ext)| ext.to_lowercase())
+                .filter(|ext| AssetType::from_ext(ext).is_some())
+        };
+
+        let Some(ext) = ext else {
+            self.send(
+                id,
+                f32::MAX,
+                fetched.total,
+                DownloadStatus::Error(format!(
+                    "Failed to import unsupported asset. {}",
+                    fetched.content_type.as_deref().unwrap_or(fmt.media_type())
+                )),
+            );
+            return Ok(None);
+        };
+
+        let content = read(temp)?;
+        self.send(id, f32::MAX, fetched.total, DownloadStatus::Finished);
+
+        Ok(Some(RawAsset {
+            bytes: content,
+            ext: ext.into(),
+            // Record where the media actually lives after redirects.
+            src: fetched.url.to_string(),
+            page: page.map(|p| p.to_string()),
+            name: None,
+            tags: Default::default(),
+        }))
+    }
+
+    async fn try_fetch(&self, id: u32, url: &str, temp: &Path) -> Result<Fetched, FetchError> {
+        let offset = metadata(temp).map(|m| m.len()).unwrap_or(0);
+
+        self.send(id, 0.0, None, DownloadStatus::SendingGet);
+        let mut request = self.client.get(url);
+        if offset > 0 {
+            request = request.header(RANGE, format!("bytes={}-", offset));
+        }
+        let response = request.send().await?;
+
+        let status = response.status();
+        if status == StatusCode::RANGE_NOT_SATISFIABLE {
+            // The partial file is stale, start over.
+            remove_file(temp)?;
+            return Err(FetchError::Retryable(status.to_string()));
+        }
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(FetchError::Retryable(status.to_string()));
+        }
+        if !status.is_success() {
+            return Err(FetchError::Fatal(status.to_string()));
+        }
+
+        let resumed = status == StatusCode::PARTIAL_CONTENT;
+        let mut downloaded = if resumed { offset } else { 0 };
+        let total = response.content_length().map(|len| len + downloaded);
+        let total_f = total.map(|t| t as f32);
+        if total.is_some_and(|total| total > self.settings.max_size) {
+            return Err(FetchError::Fatal(format!(
+                "Asset exceeds the size limit of {} bytes.",
+                self.settings.max_size
+            )));
+        }
+
+        let fetched = Fetched {
+            url: response.url().clone(),
+            content_type: response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|ty| ty.to_str().ok())
+                .map(|ty| ty.to_lowercase()),
+            total: total_f,
+        };
+
+        let mut file = if resumed {
+            OpenOptions::new().append(true).open(temp)?
+        } else {
+            File::create(temp)?
+        };
+
+        self.send(id, downloaded as f32, total_f, DownloadStatus::Started);
+
+        let mut stream = response.bytes_stream();
+        while let Some(bytes) = stream.next().await {
+            let bytes = bytes?;
+            downloaded += bytes.len() as u64;
+            if downloaded > self.settings.max_size {
+                drop(file);
+                remove_file(temp)?;
+                return Err(FetchError::Fatal(format!(
+                    "Asset exceeds the size limit of {} bytes.",
+                    self.settings.max_size
+                )));
+            }
+
+            file.write_all(&bytes)?;
+            self.send(id, downloaded as f32, total_f, DownloadStatus::Ongoing);
+        }
+        file.flush()?;
+
+        if total.is_some_and(|total| downloaded < total) {
+            return Err(FetchError::Retryable(format!(
+                "Connection closed after {} of {} bytes.",
+                downloaded,
+                total.unwrap_or_default()
+            )));
+        }
+
+        Ok(fetched)
+    }
+}
@@ -1,5 +1,18 @@
+use std::path::PathBuf;
+
 use serde::Serialize;
 
+use crate::{
+    app::{AssetId, DuplicateAssets},
+    web::WebMediaCandidate,
+};
+
+pub const WATCH_FOLDER_IMPORT: &str = "watch-folder-import";
+pub const BRIDGE_DOWNLOAD: &str = "bridge-download";
+pub const BRIDGE_IMPORT: &str = "bridge-import";
+/// A background save found `snowflake.json` written by another session.
+pub const LIBRARY_MODIFIED_EXTERNALLY: &str = "library-modified-externally";
+
 #[derive(Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct DownloadEvent {
@@ -15,5 +28,24 @@ pub enum DownloadStatus {
     Started,
     Ongoing,
     Finished,
+    /// The download failed and is attempted again, with the attempt number.
+    Retrying(u32),
+    Cancelled,
+    /// The url led to a web page, the user picks which media to import.
+    Scraped(Vec<WebMediaCandidate>),
     Error(String),
 }
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchFolderImportEvent {
+    pub library: PathBuf,
+    pub assets: Vec<AssetId>,
+    pub duplication: Option<DuplicateAssets>,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryModifiedEvent {
+    pub library: PathBuf,
+}
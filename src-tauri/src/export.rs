@@ -0,0 +1,346 @@
+use std::{
+    fs::{copy, create_dir_all, hard_link, read, write},
+    path::{Path, PathBuf},
+};
+
+use hashbrown::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::{
+        AppError, AppResult, Asset, AssetId, AssetProperty, AssetType, CollectionId, Metadata,
+        SearchQueryTy, Storage, TagId,
+    },
+    metadata,
+};
+
+pub const UNCATEGORIZED_FOLDER: &str = "Uncategorized";
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+#[serde(tag = "ty", content = "data")]
+pub enum ExportScope {
+    #[default]
+    Library,
+    Assets(Vec<AssetId>),
+    Tag(TagId),
+    Collection(CollectionId),
+    /// Assets found by the global search.
+    Query {
+        ty: SearchQueryTy,
+        query: String,
+    },
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportLinkMode {
+    #[default]
+    Copy,
+    Hardlink,
+    Symlink,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum SidecarFormat {
+    Json,
+    Csv,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum XmpMode {
+    Sidecar,
+    /// Embedded into jpeg and png copies, other files get a sidecar.
+    Embedded,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportSettings {
+    #[serde(default)]
+    pub scope: ExportScope,
+    #[serde(default)]
+    pub link_mode: ExportLinkMode,
+    #[serde(default)]
+    pub sidecar: Option<SidecarFormat>,
+    #[serde(default)]
+    pub include_deleted: bool,
+    #[serde(default)]
+    pub xmp: Option<XmpMode>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AssetSidecar<'a> {
+    id: AssetId,
+    name: &'a str,
+    ext: &'a str,
+    ty: AssetType,
+    src: &'a str,
+    is_deleted: bool,
+    tags: Vec<Vec<String>>,
+    props: &'a AssetProperty,
+    meta: &'a Metadata,
+}
+
+impl<'a> AssetSidecar<'a> {
+    const CSV_HEADER: &'static str = "id,name,ext,ty,src,isDeleted,tags,props";
+
+    fn into_csv(self) -> AppResult<String> {
+        let tags = self
+            .tags
+            .iter()
+            .map(|path| path.join("/"))
+            .collect::<Vec<_>>()
+            .join(";");
+
+        Ok(format!(
+            "{}\n{}\n",
+            Self::CSV_HEADER,
+            [
+                self.id.0.to_string(),
+                self.name.to_string(),
+                self.ext.to_string(),
+                serde_json::to_string(&self.ty)?
+                    .trim_matches('"')
+                    .to_string(),
+                self.src.to_string(),
+                self.is_deleted.to_string(),
+                tags,
+                serde_json::to_string(self.props)?,
+            ]
+            .iter()
+            .map(|field| escape_csv(field))
+            .collect::<Vec<_>>()
+            .join(",")
+        ))
+    }
+}
+
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn export_recursion(
+    storage: &Storage,
+    collection: CollectionId,
+    path: PathBuf,
+    tag_to_path: &mut HashMap<TagId, PathBuf>,
+) -> AppResult<()> {
+    if let Some(collection) = storage.collections.get(&collection) {
+        if collection.is_deleted {
+            return Ok(());
+        }
+
+        let collection_path = path.join(collection.name.as_ref());
+
+        for tag in &collection.content {
+            let Some(tag) = storage.tags.get(tag) else {
+                return Err(AppError::TagNotFound(*tag));
+            };
+            if tag.is_deleted {
+                continue;
+            }
+            tag_to_path.insert(tag.id, collection_path.join(tag.name.as_ref()));
+        }
+
+        for child in collection.children.clone() {
+            export_recursion(storage, child, collection_path.clone(), tag_to_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn collect_collection_tags(storage: &Storage, collection: CollectionId, tags: &mut HashSet<TagId>) {
+    if let Some(collection) = storage.collections.get(&collection) {
+        tags.extend(collection.content.iter().cloned());
+        for child in &collection.children {
+            collect_collection_tags(storage, *child, tags);
+        }
+    }
+}
+
+pub fn collect_scope<'a>(
+    storage: &'a Storage,
+    settings: &ExportSettings,
+) -> AppResult<Vec<&'a Asset>> {
+    let assets = storage
+        .assets
+        .values()
+        .filter(|asset| settings.include_deleted || !asset.is_deleted);
+
+    Ok(match &settings.scope {
+        ExportScope::Library => assets.collect(),
+        ExportScope::Assets(ids) => {
+            let ids = ids.iter().collect::<HashSet<_>>();
+            assets.filter(|asset| ids.contains(&asset.id)).collect()
+        }
+        ExportScope::Tag(tag) => {
+            let Some(tag) = storage.tags.get(tag) else {
+                return Err(AppError::TagNotFound(*tag));
+            };
+            assets.filter(|asset| asset.tags.contains(tag)).collect()
+        }
+        ExportScope::Collection(collection) => {
+            if !storage.collections.contains_key(collection) {
+                return Err(AppError::CollectionNotFound(*collection));
+            }
+            let mut tags = HashSet::default();
+            collect_collection_tags(storage, *collection, &mut tags);
+            assets
+                .filter(|asset| {
+                    asset
+                        .tags
+                        .grouped
+                        .values()
+                        .chain(&asset.tags.ungrouped)
+                        .any(|tag| tags.contains(tag))
+                })
+                .collect()
+        }
+        ExportScope::Query { ty, query } => assets
+            .filter(|asset| ty.matches_asset(asset, query))
+            .collect(),
+    })
+}
+
+fn unique_file_path(dir: &Path, asset: &Asset, used: &mut HashSet<PathBuf>) -> PathBuf {
+    let mut path = dir.join(asset.gen_file_name().as_ref());
+    let mut index = 1;
+
+    while used.contains(&path) || path.exists() {
+        path = if asset.ext.is_empty() {
+            dir.join(format!("{} ({})", asset.name, index))
+        } else {
+            dir.join(format!("{} ({}).{}", asset.name, index, asset.ext))
+        };
+        index += 1;
+    }
+
+    used.insert(path.clone());
+    path
+}
+
+fn link_file(src: &Path, dst: &Path, mode: ExportLinkMode) -> std::io::Result<()> {
+    match mode {
+        ExportLinkMode::Copy => copy(src, dst).map(|_| ()),
+        ExportLinkMode::Hardlink => hard_link(src, dst),
+        #[cfg(unix)]
+        ExportLinkMode::Symlink => std::os::unix::fs::symlink(src, dst),
+        #[cfg(windows)]
+        ExportLinkMode::Symlink => std::os::windows::fs::symlink_file(src, dst),
+    }
+}
+
+pub fn export_library(
+    storage: &Storage,
+    root_folder: &Path,
+    settings: &ExportSettings,
+) -> AppResult<()> {
+    let export_root = root_folder.join(&storage.lib_meta.name);
+    let mut tag_to_path = HashMap::default();
+    export_recursion(
+        storage,
+        storage.sp_collections.root,
+        export_root.clone(),
+        &mut tag_to_path,
+    )?;
+
+    let mut used = HashSet::default();
+    for asset in collect_scope(storage, settings)? {
+        let asset_path = asset.get_file_path(&storage.cache.root);
+        if !asset_path.exists() {
+            log::warn!("Skipping missing asset {:?} at {:?}", asset.id, asset_path);
+            continue;
+        }
+
+        let tags = asset
+            .tags
+            .grouped
+            .values()
+            .chain(&asset.tags.ungrouped)
+            .filter(|tag| tag_to_path.contains_key(*tag))
+            .collect::<Vec<_>>();
+
+        let dirs = if tags.is_empty() {
+            vec![export_root.join(UNCATEGORIZED_FOLDER)]
+        } else {
+            tags.iter().map(|tag| tag_to_path[*tag].clone()).collect()
+        };
+        let tag_paths = tags
+            .iter()
+            .map(|tag| storage.get_tag_virtual_path(**tag))
+            .collect::<AppResult<Vec<_>>>()?;
+        let xmp = settings
+            .xmp
+            .map(|mode| (mode, metadata::build_xmp_packet(&tag_paths)));
+
+        let sidecar = settings
+            .sidecar
+            .map(|format| -> AppResult<_> {
+                let sidecar = AssetSidecar {
+                    id: asset.id,
+                    name: &asset.name,
+                    ext: &asset.ext,
+                    ty: asset.ty,
+                    src: &asset.src,
+                    is_deleted: asset.is_deleted,
+                    tags: tag_paths.clone(),
+                    props: &asset.props,
+                    meta: &asset.meta,
+                };
+
+                Ok(match format {
+                    SidecarFormat::Json => ("json", serde_json::to_string_pretty(&sidecar)?),
+                    SidecarFormat::Csv => ("csv", sidecar.into_csv()?),
+                })
+            })
+            .transpose()?;
+
+        // Links to encrypted files are useless outside of the library, they
+        // are always exported as decrypted copies.
+        let decrypt = storage.cache.cipher.is_some() && asset.link.is_none();
+        for dir in dirs {
+            create_dir_all(&dir)?;
+            let dst = unique_file_path(&dir, asset, &mut used);
+            if decrypt {
+                write(&dst, storage.cache.read_asset(asset)?)?;
+            } else {
+                link_file(&asset_path, &dst, settings.link_mode)?;
+            }
+
+            if let Some((mode, packet)) = &xmp {
+                // Never write into links, they share their content with the library.
+                let embedded = matches!(mode, XmpMode::Embedded)
+                    && (decrypt || matches!(settings.link_mode, ExportLinkMode::Copy))
+                    && match metadata::embed_xmp(&read(&dst)?, packet) {
+                        Some(bytes) => {
+                            write(&dst, bytes)?;
+                            true
+                        }
+                        None => false,
+                    };
+
+                if !embedded {
+                    write(metadata::xmp_sidecar_path(&dst), packet)?;
+                }
+            }
+
+            if let Some((ext, content)) = &sidecar {
+                let mut sidecar_path = dst.into_os_string();
+                sidecar_path.push(".");
+                sidecar_path.push(ext);
+                write(sidecar_path, content)?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,280 @@
+use std::{
+    fs::{read, read_dir, File},
+    path::Path,
+};
+
+use hashbrown::HashMap;
+use serde::Deserialize;
+
+use crate::app::{
+    collect_path, AppError, AppResult, CollectionId, DuplicateAssets, ImportMode, RawAsset,
+    Storage, TagId,
+};
+
+const EAGLE_METADATA: &str = "metadata.json";
+const EAGLE_IMAGES: &str = "images";
+const EAGLE_TAGS_COLLECTION: &str = "Eagle Tags";
+const PUREREF_COLLECTION: &str = "PureRef";
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const JPEG_SIGNATURE: &[u8] = &[0xff, 0xd8, 0xff];
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EagleLibraryMeta {
+    #[serde(default)]
+    folders: Vec<EagleFolder>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EagleFolder {
+    id: String,
+    name: String,
+    #[serde(default)]
+    children: Vec<EagleFolder>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EagleItem {
+    name: String,
+    ext: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    folders: Vec<String>,
+    #[serde(default)]
+    is_deleted: bool,
+    #[serde(default)]
+    url: String,
+    #[serde(default)]
+    annotation: String,
+}
+
+/// Eagle folders become tags inside the collection of their parent folder,
+/// folders with subfolders also become collections, like folder-as-tag imports.
+fn map_eagle_folder(
+    storage: &mut Storage,
+    folder: &EagleFolder,
+    parent: CollectionId,
+    folder_tags: &mut HashMap<String, TagId>,
+) {
+    let tag = storage.find_or_create_tag(parent, &folder.name);
+    folder_tags.insert(folder.id.clone(), tag);
+
+    if folder.children.is_empty() {
+        return;
+    }
+
+    let collection = storage.find_or_create_collection(parent, &folder.name);
+    for child in &folder.children {
+        map_eagle_folder(storage, child, collection, folder_tags);
+    }
+}
+
+pub fn import_eagle_library(
+    storage: &mut Storage,
+    path: &Path,
+    initial_tag: Option<TagId>,
+) -> AppResult<DuplicateAssets> {
+    let images = path.join(EAGLE_IMAGES);
+    if !images.is_dir() {
+        return Err(AppError::InvalidForeignLibrary(path.to_path_buf()));
+    }
+
+    let meta =
+        serde_json::from_reader::<_, EagleLibraryMeta>(File::open(path.join(EAGLE_METADATA))?)?;
+
+    let root_collection = storage.sp_collections.root;
+    let mut folder_tags = HashMap::default();
+    for folder in &meta.folders {
+        map_eagle_folder(storage, folder, root_collection, &mut folder_tags);
+    }
+
+    let root = storage.cache.root.clone();
+    let mut tag_collection = None;
+    let mut assets = HashMap::default();
+    let mut asset_crc = HashMap::default();
+
+    for entry in read_dir(images)? {
+        let dir = entry?.path();
+        let Ok(file) = File::open(dir.join(EAGLE_METADATA)) else {
+            continue;
+        };
+        let item = match serde_json::from_reader::<_, EagleItem>(file) {
+            Ok(item) => item,
+            Err(err) => {
+                log::warn!("Skipping invalid Eagle item {:?}: {}", dir, err);
+                continue;
+            }
+        };
+        if item.is_deleted {
+            continue;
+        }
+
+        let file = dir.join(format!("{}.{}", item.name, item.ext));
+        if !file.is_file() {
+            log::warn!("Skipping missing Eagle item file {:?}", file);
+            continue;
+        }
+
+        let mut collected = HashMap::default();
+        let mut collected_crc = HashMap::default();
+        if let Err(err) = collect_path(
+            &root,
+            file.clone(),
+            &mut collected,
+            &mut collected_crc,
+            &mut None,
+            ImportMode::Copy,
+        ) {
+            log::warn!("Skipping unreadable Eagle item file {:?}: {}", file, err);
+            continue;
+        }
+
+        for (id, mut asset) in collected {
+            asset.src = item.url.clone();
+            if !item.annotation.is_empty() {
+                asset
+                    .embedded_meta
+                    .get_or_insert_with(Default::default)
+                    .fields
+                    .insert("Eagle.annotation".to_string(), item.annotation.clone());
+            }
+
+            for folder in &item.folders {
+                if let Some(tag) = folder_tags.get(folder) {
+                    asset.tags.insert_unchecked(&storage.tags[tag]);
+                }
+            }
+            for name in &item.tags {
+                let collection = *tag_collection.get_or_insert_with(|| {
+                    storage.find_or_create_collection(root_collection, EAGLE_TAGS_COLLECTION)
+                });
+                let tag = storage.find_or_create_tag(collection, name);
+                asset.tags.insert_unchecked(&storage.tags[&tag]);
+            }
+            if let Some(initial_tag) = initial_tag.and_then(|t| storage.tags.get(&t)) {
+                asset.tags.insert_unchecked(initial_tag);
+            }
+
+            if let Some(crc) = collected_crc.remove(&id) {
+                asset_crc.insert(id, crc);
+            }
+            assets.insert(id, asset);
+        }
+    }
+
+    storage.insert_collected_assets(assets, asset_crc)
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn png_end(bytes: &[u8]) -> Option<usize> {
+    let mut offset = PNG_SIGNATURE.len();
+    loop {
+        let len = u32::from_be_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?) as usize;
+        let ty = bytes.get(offset + 4..offset + 8)?;
+        offset += 12 + len;
+
+        if ty == b"IEND" {
+            return (offset <= bytes.len()).then_some(offset);
+        }
+    }
+}
+
+fn jpeg_end(bytes: &[u8]) -> Option<usize> {
+    // Skip the headers by their length, so thumbnails embedded in EXIF
+    // aren't mistaken for the end of the image.
+    let mut offset = 2;
+    loop {
+        if *bytes.get(offset)? != 0xff {
+            return None;
+        }
+        let marker = *bytes.get(offset + 1)?;
+        let len = u16::from_be_bytes(bytes.get(offset + 2..offset + 4)?.try_into().ok()?) as usize;
+        offset += 2 + len;
+
+        // Start of scan
+        if marker == 0xda {
+            break;
+        }
+    }
+
+    find_bytes(&bytes[offset..], &[0xff, 0xd9]).map(|end| offset + end + 2)
+}
+
+/// PureRef boards embed the original image files, find them by signature.
+fn extract_embedded_images(bytes: &[u8]) -> Vec<(Vec<u8>, &'static str)> {
+    let mut images = Vec::new();
+    let mut offset = 0;
+
+    while offset < bytes.len() {
+        let rest = &bytes[offset..];
+        let png = find_bytes(rest, PNG_SIGNATURE);
+        let jpeg = find_bytes(rest, JPEG_SIGNATURE);
+
+        let (start, is_png) = match (png, jpeg) {
+            (Some(png), Some(jpeg)) if png < jpeg => (png, true),
+            (Some(png), None) => (png, true),
+            (_, Some(jpeg)) => (jpeg, false),
+            (None, None) => break,
+        };
+
+        let image = &rest[start..];
+        let end = if is_png {
+            png_end(image)
+        } else {
+            jpeg_end(image)
+        };
+
+        match end {
+            Some(end) => {
+                images.push((image[..end].to_vec(), if is_png { "png" } else { "jpg" }));
+                offset += start + end;
+            }
+            None => offset += start + 1,
+        }
+    }
+
+    images
+}
+
+pub fn import_pureref_board(
+    storage: &mut Storage,
+    path: &Path,
+    initial_tag: Option<TagId>,
+) -> AppResult<DuplicateAssets> {
+    let images = extract_embedded_images(&read(path)?);
+    if images.is_empty() {
+        return Err(AppError::InvalidForeignLibrary(path.to_path_buf()));
+    }
+
+    let board = path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let collection =
+        storage.find_or_create_collection(storage.sp_collections.root, PUREREF_COLLECTION);
+    let board_tag = storage.find_or_create_tag(collection, &board);
+
+    let data = images
+        .into_iter()
+        .enumerate()
+        .map(|(index, (bytes, ext))| RawAsset {
+            bytes,
+            ext: ext.into(),
+            src: Default::default(),
+            page: None,
+            name: Some(format!("{} {}", board, index + 1)),
+            tags: vec![board_tag],
+        })
+        .collect();
+
+    storage.add_raw_assets(initial_tag, data)
+}
@@ -0,0 +1,152 @@
+use std::{path::Path, sync::Arc, time::Duration};
+
+use hashbrown::HashMap;
+use reqwest::{
+    cookie::Jar,
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Client, Proxy, Url,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::app::{AppError, AppResult};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpCookie {
+    pub include_subdomains: bool,
+    pub path: String,
+    pub secure: bool,
+    /// Unix timestamp, session cookies have none.
+    pub expires: Option<i64>,
+    pub name: String,
+    pub value: String,
+}
+
+/// Client configuration applied to every web import. The library may
+/// carry its own, which takes precedence over the global one.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpSettings {
+    #[serde(default)]
+    pub proxy: Option<String>,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Cookie jars keyed by domain.
+    #[serde(default)]
+    pub cookies: HashMap<String, Vec<HttpCookie>>,
+}
+
+impl HttpSettings {
+    pub fn merged(global: &Self, library: Option<&Self>) -> Self {
+        let Some(library) = library else {
+            return global.clone();
+        };
+
+        let mut merged = global.clone();
+        if library.proxy.is_some() {
+            merged.proxy = library.proxy.clone();
+        }
+        if library.user_agent.is_some() {
+            merged.user_agent = library.user_agent.clone();
+        }
+        merged.headers.extend(library.headers.clone());
+        merged.cookies.extend(library.cookies.clone());
+        merged
+    }
+
+    /// Parses a cookie file in the Netscape format exported by most browser
+    /// extensions, replacing the jars of the domains it contains.
+    pub fn import_cookie_file(&mut self, path: &Path) -> AppResult<usize> {
+        let content = std::fs::read_to_string(path)?;
+        let mut imported = HashMap::<String, Vec<HttpCookie>>::default();
+
+        for line in content.lines() {
+            let line = line.strip_prefix("#HttpOnly_").unwrap_or(line);
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields = line.split('\t').collect::<Vec<_>>();
+            let [domain, include_subdomains, path, secure, expires, name, value] = fields[..]
+            else {
+                return Err(AppError::InvalidCookieFile(line.to_string()));
+            };
+
+            imported
+                .entry(domain.trim_start_matches('.').to_lowercase())
+                .or_default()
+                .push(HttpCookie {
+                    include_subdomains: include_subdomains.eq_ignore_ascii_case("TRUE"),
+                    path: path.to_string(),
+                    secure: secure.eq_ignore_ascii_case("TRUE"),
+                    expires: expires.parse().ok().filter(|e| *e > 0),
+                    name: name.to_string(),
+                    value: value.to_string(),
+                });
+        }
+
+        let count = imported.values().map(Vec::len).sum();
+        self.cookies.extend(imported);
+        Ok(count)
+    }
+
+    fn cookie_jar(&self) -> Jar {
+        let jar = Jar::default();
+        let now = chrono::Utc::now().timestamp();
+
+        for (domain, cookies) in &self.cookies {
+            let Ok(url) = Url::parse(&format!("https://{}/", domain)) else {
+                log::warn!("Skipping cookies of invalid domain {}", domain);
+                continue;
+            };
+
+            for cookie in cookies {
+                if cookie.expires.is_some_and(|e| e <= now) {
+                    continue;
+                }
+
+                let mut cookie_str =
+                    format!("{}={}; Path={}", cookie.name, cookie.value, cookie.path);
+                if cookie.include_subdomains {
+                    cookie_str.push_str(&format!("; Domain={}", domain));
+                }
+                if cookie.secure {
+                    cookie_str.push_str("; Secure");
+                }
+                jar.add_cookie_str(&cookie_str, &url);
+            }
+        }
+
+        jar
+    }
+
+    pub fn client(&self, timeout: Duration) -> AppResult<Client> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) else {
+                return Err(AppError::InvalidHttpHeader(name.clone()));
+            };
+            headers.insert(name, value);
+        }
+
+        let mut builder = Client::builder()
+            .connect_timeout(timeout)
+            .read_timeout(timeout)
+            .default_headers(headers)
+            .cookie_provider(Arc::new(self.cookie_jar()));
+
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+
+        Ok(builder.build()?)
+    }
+}
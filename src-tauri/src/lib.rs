@@ -1,14 +1,44 @@
 use std::sync::Mutex;
 
 use log::LevelFilter;
-use tauri::Manager;
+use tauri::{Manager, RunEvent};
 
-use crate::app::{AppData, ResourceCache, Storage};
+use crate::{
+    app::{AppData, ResourceCache},
+    bridge::Bridge,
+    download::DownloadManager,
+    library::Libraries,
+    model::{ModelCache, MODELS},
+    watcher::FolderWatcher,
+};
 
+mod aigc;
 mod app;
+mod archive;
+mod autotag;
+mod bridge;
+mod clip;
+mod clipboard;
 mod cmd;
 mod compatibility;
+mod crypto;
+mod download;
 mod event;
+mod export;
+mod foreign;
+mod http;
+mod library;
+mod lock;
+mod metadata;
+mod model;
+mod ocr;
+mod protocol;
+mod rendition;
+mod semantic;
+mod snapshot;
+mod sync;
+mod watcher;
+mod web;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -53,12 +83,34 @@ pub fn run() {
                     .unwrap();
             }
 
-            app.manage(Mutex::new(Option::<Storage>::None));
+            app.manage(Mutex::new(Libraries::default()));
             app.manage(ResourceCache::new(app.handle()).unwrap());
             app.manage(Mutex::new(AppData::read(app.handle()).unwrap()));
+            app.manage(Mutex::new(FolderWatcher::default()));
+            app.manage(Mutex::new(DownloadManager::default()));
+            app.manage(Mutex::new(ModelCache::new(
+                app.path().app_data_dir().unwrap().join(MODELS),
+            )));
+
+            let mut bridge = Bridge::default();
+            let bridge_settings = app
+                .state::<Mutex<AppData>>()
+                .lock()
+                .map(|data| data.bridge.clone())
+                .unwrap_or_default();
+            if let Err(err) = bridge.refresh(app.handle(), &bridge_settings) {
+                log::error!("Failed to start bridge: {}", err);
+            }
+            app.manage(Mutex::new(bridge));
 
             Ok(())
         })
+        .register_asynchronous_uri_scheme_protocol(protocol::SCHEME, |ctx, request, responder| {
+            let app = ctx.app_handle().clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                responder.respond(protocol::handle(&app, request))
+            });
+        })
         .invoke_handler(tauri::generate_handler![
             cmd::crash_test,
             cmd::open_crash_reports_dir,
@@ -72,16 +124,67 @@ pub fn run() {
             cmd::set_user_setting,
             cmd::load_library,
             cmd::initialize_library,
+            cmd::get_loaded_libraries,
+            cmd::activate_library,
+            cmd::transfer_assets,
+            cmd::transfer_tags,
             cmd::save_library,
+            cmd::check_external_modification,
+            cmd::reload_library,
+            cmd::merge_library,
+            cmd::list_snapshots,
+            cmd::create_snapshot,
+            cmd::diff_snapshots,
+            cmd::restore_snapshot,
+            cmd::get_sync_status,
+            cmd::enable_sync,
+            cmd::disable_sync,
+            cmd::sync_library,
+            cmd::is_library_encrypted,
+            cmd::encrypt_library,
+            cmd::decrypt_library,
+            cmd::rekey_library,
             cmd::unload_library,
             cmd::export_library,
+            cmd::export_archive,
+            cmd::import_archive,
+            cmd::initialize_library_from_archive,
             cmd::gen_statistics,
             cmd::change_library_name,
             cmd::import_assets,
+            cmd::import_eagle_library,
+            cmd::import_pureref_board,
+            cmd::get_missing_linked_assets,
+            cmd::relink_assets,
+            cmd::consolidate_assets,
             cmd::import_memory_asset,
+            cmd::import_from_clipboard,
             cmd::import_web_assets,
+            cmd::scrape_web_page,
+            cmd::cancel_download,
+            cmd::get_http_settings,
+            cmd::set_http_settings,
+            cmd::import_cookie_file,
+            cmd::get_bridge_settings,
+            cmd::set_bridge_settings,
+            cmd::regenerate_bridge_token,
+            cmd::get_metadata_mapping,
+            cmd::set_metadata_mapping,
+            cmd::refresh_embedded_metadata,
+            cmd::detect_aigc,
+            cmd::suggest_tags,
+            cmd::accept_tag_suggestions,
+            cmd::semantic_search,
+            cmd::find_similar_assets,
+            cmd::recognize_text,
+            cmd::get_watch_folders,
+            cmd::add_watch_folder,
+            cmd::remove_watch_folder,
             cmd::recover_items,
             cmd::get_recycle_bin,
+            cmd::get_recycle_bin_info,
+            cmd::get_recycle_bin_tree,
+            cmd::empty_recycle_bin,
             cmd::get_duplicated_assets,
             cmd::get_asset_abs_path,
             cmd::get_tag_virtual_path,
@@ -114,6 +217,13 @@ pub fn run() {
             cmd::save_render_cache,
             cmd::get_render_cache,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let RunEvent::Exit = event {
+                if let Ok(mut libraries) = app.state::<Mutex<Libraries>>().lock() {
+                    libraries.unload_all();
+                }
+            }
+        });
 }
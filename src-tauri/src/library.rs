@@ -0,0 +1,273 @@
+use std::{
+    fs::remove_file,
+    path::{Path, PathBuf},
+};
+
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    app::{AppError, AppResult, AssetId, DuplicateAssets, Storage, TagContainer, TagId},
+    lock::LibraryLock,
+};
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadedLibrary {
+    pub path: PathBuf,
+    pub name: String,
+    pub active: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum TransferMode {
+    #[default]
+    Copy,
+    /// Copies, then moves the originals into the recycle bin.
+    Move,
+}
+
+/// Every library loaded at the moment, keyed by their root folder.
+#[derive(Default)]
+pub struct Libraries {
+    storages: HashMap<PathBuf, Storage>,
+    /// The library addressed by commands which don't name one.
+    active: Option<PathBuf>,
+}
+
+fn tags_of(tags: &TagContainer) -> impl Iterator<Item = TagId> + '_ {
+    tags.grouped.values().chain(&tags.ungrouped).copied()
+}
+
+/// Recreates the collections along a virtual path, then the tag at its end.
+fn find_or_create_tag_path(storage: &mut Storage, path: &[String]) -> Option<TagId> {
+    let (name, collections) = path.split_last()?;
+    let mut parent = storage.sp_collections.root;
+    for collection in collections {
+        parent = storage.find_or_create_collection(parent, collection);
+    }
+    Some(storage.find_or_create_tag(parent, name))
+}
+
+impl Libraries {
+    fn resolve<'a>(&'a self, library: Option<&'a Path>) -> Option<&'a Path> {
+        library.or(self.active.as_deref())
+    }
+
+    /// Pins `None` to the library active right now, for commands which lock
+    /// the libraries again later when another one may be active.
+    pub fn resolve_root(&self, library: Option<&Path>) -> Option<PathBuf> {
+        self.resolve(library).map(Path::to_path_buf)
+    }
+
+    pub fn get(&self, library: Option<&Path>) -> Option<&Storage> {
+        self.storages.get(self.resolve(library)?)
+    }
+
+    pub fn get_mut(&mut self, library: Option<&Path>) -> Option<&mut Storage> {
+        let root = self.resolve(library)?.to_path_buf();
+        self.storages.get_mut(&root)
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut Storage> {
+        self.storages.values_mut()
+    }
+
+    /// Adds the library, replacing the one with the same root, and activates it.
+    pub fn insert(&mut self, storage: Storage) {
+        let root = storage.cache.root.clone();
+        self.active = Some(root.clone());
+        self.storages.insert(root, storage);
+    }
+
+    /// Swaps a loaded library for a fresh copy, leaving the active one alone.
+    pub fn replace(&mut self, storage: Storage) {
+        self.storages.insert(storage.cache.root.clone(), storage);
+    }
+
+    pub fn remove(&mut self, library: Option<&Path>) -> Option<Storage> {
+        let root = self.resolve(library)?.to_path_buf();
+        if self.active.as_ref() == Some(&root) {
+            self.active = None;
+        }
+        self.storages.remove(&root)
+    }
+
+    /// Saves every library and releases its lock, as the app exits.
+    pub fn unload_all(&mut self) {
+        self.active = None;
+        for (root, mut storage) in self.storages.drain() {
+            if let Err(err) = storage.save() {
+                log::error!("Failed to save library {:?}: {}", root, err);
+            }
+            if let Err(err) = storage.remove_plain_copies() {
+                log::warn!("Failed to remove decrypted copies of {:?}: {}", root, err);
+            }
+            if let Err(err) = LibraryLock::release(&root) {
+                log::error!("Failed to release library {:?}: {}", root, err);
+            }
+        }
+    }
+
+    pub fn activate(&mut self, library: &Path) -> AppResult<()> {
+        if !self.storages.contains_key(library) {
+            return Err(AppError::LibraryNotLoaded(library.to_path_buf()));
+        }
+        self.active = Some(library.to_path_buf());
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<LoadedLibrary> {
+        self.storages
+            .iter()
+            .map(|(path, storage)| LoadedLibrary {
+                path: path.clone(),
+                name: storage.lib_meta.name.clone(),
+                active: self.active.as_ref() == Some(path),
+            })
+            .collect()
+    }
+
+    fn pair(&self, from: &Path, to: &Path) -> AppResult<&Storage> {
+        if from == to {
+            return Err(AppError::SameLibrary(from.to_path_buf()));
+        }
+        if !self.storages.contains_key(to) {
+            return Err(AppError::LibraryNotLoaded(to.to_path_buf()));
+        }
+        self.storages
+            .get(from)
+            .ok_or_else(|| AppError::LibraryNotLoaded(from.to_path_buf()))
+    }
+
+    /// Maps the tags of the source library onto the target one. Tags missing
+    /// from `tag_map` are matched by their virtual path, created if needed.
+    fn remap_tags(
+        &mut self,
+        from: &Path,
+        to: &Path,
+        tags: impl IntoIterator<Item = TagId>,
+        tag_map: &mut HashMap<TagId, TagId>,
+    ) -> AppResult<()> {
+        let source = self.pair(from, to)?;
+        let mut paths = Vec::new();
+        for tag in tags {
+            if !tag_map.contains_key(&tag) {
+                paths.push((tag, source.get_tag_virtual_path(tag)?));
+            }
+        }
+
+        let target = self.storages.get_mut(to).unwrap();
+        for (tag, path) in paths {
+            if let Some(new) = find_or_create_tag_path(target, &path) {
+                tag_map.insert(tag, new);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn transfer_assets(
+        &mut self,
+        from: &Path,
+        to: &Path,
+        assets: &[AssetId],
+        mut tag_map: HashMap<TagId, TagId>,
+        mode: TransferMode,
+    ) -> AppResult<DuplicateAssets> {
+        let source = self.pair(from, to)?;
+        let mut copies = Vec::with_capacity(assets.len());
+        let mut tags = Vec::new();
+        for id in assets {
+            let Some(asset) = source.assets.get(id) else {
+                return Err(AppError::AssetNotFound(*id));
+            };
+
+            tags.extend(tags_of(&asset.tags));
+            copies.push((asset.clone(), source.cache.read_asset(asset)?));
+        }
+
+        self.remap_tags(from, to, tags, &mut tag_map)?;
+
+        // The records are carried over as they are, only the id and what
+        // refers to the tags of the source library change.
+        let target = self.storages.get_mut(to).unwrap();
+        let mut collected = HashMap::default();
+        let mut asset_crc = HashMap::default();
+        let mut written = Vec::new();
+        for (mut asset, bytes) in copies {
+            let source_tags = tags_of(&asset.tags).collect::<Vec<_>>();
+            asset.id = AssetId(Uuid::new_v4());
+            asset.is_deleted = false;
+            asset.tags = Default::default();
+            for tag in source_tags
+                .iter()
+                .filter_map(|t| tag_map.get(t))
+                .filter_map(|t| target.tags.get(t))
+            {
+                asset.tags.insert_unchecked(tag);
+            }
+            if let Some(suggestions) = &mut asset.tag_suggestions {
+                suggestions.retain_mut(|s| match tag_map.get(&s.tag) {
+                    Some(tag) => {
+                        s.tag = *tag;
+                        true
+                    }
+                    None => false,
+                });
+            }
+
+            // Linked assets keep referring to their file in place.
+            if asset.link.is_none() {
+                let path = asset.get_storage_path(&target.cache.root);
+                if let Err(err) = target.cache.write_file(&path, &bytes) {
+                    for path in written {
+                        let _ = remove_file(path);
+                    }
+                    return Err(err);
+                }
+                written.push(path);
+            }
+            asset_crc.insert(asset.id, crc32fast::hash(&bytes));
+            collected.insert(asset.id, asset);
+        }
+        let duplication = target.insert_collected_assets(collected, asset_crc)?;
+        target.save()?;
+
+        if let TransferMode::Move = mode {
+            let source = self.storages.get_mut(from).unwrap();
+            for id in assets {
+                source.move_asset_to_recycle_bin(*id)?;
+            }
+            source.save()?;
+        }
+
+        Ok(duplication)
+    }
+
+    /// Recreates the tags with their collections in the target library,
+    /// returning the ids they got there.
+    pub fn transfer_tags(
+        &mut self,
+        from: &Path,
+        to: &Path,
+        tags: &[TagId],
+        mode: TransferMode,
+    ) -> AppResult<HashMap<TagId, TagId>> {
+        let mut tag_map = HashMap::default();
+        self.remap_tags(from, to, tags.iter().copied(), &mut tag_map)?;
+        self.storages.get_mut(to).unwrap().save()?;
+
+        if let TransferMode::Move = mode {
+            let source = self.storages.get_mut(from).unwrap();
+            for tag in tags {
+                source.move_tag_to_recycle_bin(*tag)?;
+            }
+            source.save()?;
+        }
+
+        Ok(tag_map)
+    }
+}
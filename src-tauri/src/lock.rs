@@ -0,0 +1,202 @@
+use std::{
+    fs::{metadata, read, read_to_string, remove_file, write},
+    path::Path,
+    time::SystemTime,
+};
+
+use chrono::{DateTime, FixedOffset, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::app::{AppError, AppResult, LIBRARY_STORAGE};
+
+pub const LIBRARY_LOCK: &str = "snowflake.lock";
+
+/// Who holds a library open, written next to `snowflake.json` so that other
+/// machines sharing the folder know about it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryLock {
+    pub owner: String,
+    pub host: String,
+    pub pid: u32,
+    pub acquired_at: DateTime<FixedOffset>,
+}
+
+fn env_any(keys: &[&str]) -> Option<String> {
+    keys.iter()
+        .find_map(|key| std::env::var(key).ok())
+        .filter(|value| !value.is_empty())
+}
+
+impl LibraryLock {
+    pub fn current() -> Self {
+        Self {
+            owner: env_any(&["USER", "USERNAME"]).unwrap_or_default(),
+            host: env_any(&["COMPUTERNAME", "HOSTNAME"])
+                .or_else(|| {
+                    read_to_string("/etc/hostname")
+                        .ok()
+                        .map(|h| h.trim().to_string())
+                })
+                .unwrap_or_default(),
+            pid: std::process::id(),
+            acquired_at: Local::now().into(),
+        }
+    }
+
+    pub fn read(root: &Path) -> Option<Self> {
+        serde_json::from_slice(&read(root.join(LIBRARY_LOCK)).ok()?).ok()
+    }
+
+    /// Whether this process holds the lock.
+    fn is_ours(&self) -> bool {
+        let current = Self::current();
+        self.owner == current.owner && self.host == current.host && self.pid == current.pid
+    }
+
+    /// Locks left by the same user on the same machine by a process which is
+    /// gone came from a crashed session and are taken over. Other machines'
+    /// processes can't be checked.
+    fn is_stale(&self) -> bool {
+        let current = Self::current();
+        self.owner == current.owner && self.host == current.host && !is_running(self.pid)
+    }
+
+    /// Writes the lock, failing if someone else holds the library unless
+    /// `force` is set.
+    pub fn acquire(root: &Path, force: bool) -> AppResult<Self> {
+        if let Some(lock) = Self::read(root) {
+            if !force && !lock.is_ours() && !lock.is_stale() {
+                return Err(AppError::LibraryLocked {
+                    owner: lock.owner,
+                    host: lock.host,
+                    since: lock.acquired_at,
+                });
+            }
+        }
+
+        let lock = Self::current();
+        write(root.join(LIBRARY_LOCK), serde_json::to_vec(&lock)?)?;
+        Ok(lock)
+    }
+
+    /// Removes the lock if it is still ours, another session may have forced
+    /// its way in meanwhile.
+    pub fn release(root: &Path) -> AppResult<()> {
+        if Self::read(root).is_some_and(|lock| lock.is_ours()) {
+            remove_file(root.join(LIBRARY_LOCK))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    // Signal 0 only checks the process exists, EPERM means it belongs to
+    // another user.
+    let signaled = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    signaled == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+fn is_running(pid: u32) -> bool {
+    use windows_sys::Win32::{
+        Foundation::{CloseHandle, STILL_ACTIVE},
+        System::Threading::{GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION},
+    };
+
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle.is_null() {
+            return false;
+        }
+        let mut code = 0;
+        let queried = GetExitCodeProcess(handle, &mut code) != 0;
+        CloseHandle(handle);
+        queried && code == STILL_ACTIVE as u32
+    }
+}
+
+/// The state of `snowflake.json` when it was last read or written by us.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StorageFingerprint {
+    pub modified: Option<SystemTime>,
+    pub crc: u32,
+}
+
+impl StorageFingerprint {
+    pub fn of_bytes(root: &Path, bytes: &[u8]) -> Self {
+        Self {
+            modified: metadata(root.join(LIBRARY_STORAGE))
+                .and_then(|m| m.modified())
+                .ok(),
+            crc: crc32fast::hash(bytes),
+        }
+    }
+
+    pub fn read(root: &Path) -> AppResult<Option<Self>> {
+        match read(root.join(LIBRARY_STORAGE)) {
+            Ok(bytes) => Ok(Some(Self::of_bytes(root, &bytes))),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Whether the file on disk was written by someone else since. The
+    /// modification time is checked first to avoid hashing on every save.
+    pub fn is_stale(&self, root: &Path) -> AppResult<bool> {
+        let modified = metadata(root.join(LIBRARY_STORAGE))
+            .and_then(|m| m.modified())
+            .ok();
+        if modified.is_some() && modified == self.modified {
+            return Ok(false);
+        }
+
+        Ok(Self::read(root)?.is_none_or(|current| current.crc != self.crc))
+    }
+}
+
+/// Sent to the frontend so it can prompt for a merge or reload.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalModification {
+    pub modified: bool,
+    /// Whoever holds the lock at the moment, if not us.
+    pub holder: Option<LibraryLock>,
+}
+
+impl ExternalModification {
+    pub fn check(root: &Path, fingerprint: Option<&StorageFingerprint>) -> AppResult<Self> {
+        Ok(Self {
+            modified: match fingerprint {
+                Some(fingerprint) => fingerprint.is_stale(root)?,
+                None => false,
+            },
+            holder: LibraryLock::read(root).filter(|lock| !lock.is_ours()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locks_of_gone_processes_are_stale() {
+        let ours = LibraryLock::current();
+        assert!(ours.is_ours() && !ours.is_stale());
+
+        // Beyond the largest pid either platform hands out.
+        let crashed = LibraryLock {
+            pid: 0x3fff_fff0,
+            ..LibraryLock::current()
+        };
+        assert!(!crashed.is_ours() && crashed.is_stale());
+
+        let elsewhere = LibraryLock {
+            host: format!("{}-elsewhere", crashed.host),
+            ..crashed
+        };
+        assert!(!elsewhere.is_stale());
+    }
+}
@@ -0,0 +1,537 @@
+use std::{
+    fs::read_to_string,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const XMP_BEGIN: &str = "<x:xmpmeta";
+const XMP_END: &str = "</x:xmpmeta>";
+const PHOTOSHOP_SIGNATURE: &[u8] = b"Photoshop 3.0\0";
+const IPTC_RESOURCE: u16 = 0x0404;
+const XMP_JPEG_NAMESPACE: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const XMP_PNG_KEYWORD: &str = "XML:com.adobe.xmp";
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// Separator of `lr:hierarchicalSubject` entries.
+const HIERARCHY_SEPARATOR: char = '|';
+
+/// Metadata embedded in the asset file itself, as opposed to the filesystem
+/// `Metadata`. Field keys are prefixed by their source, like `Exif.Model`,
+/// `Iptc.Caption`, `Xmp.dc:title` or `Png.parameters`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct EmbeddedMetadata {
+    #[serde(default)]
+    pub fields: HashMap<String, String>,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// Keyword paths from `lr:hierarchicalSubject`, root first.
+    #[serde(default)]
+    pub hierarchical_keywords: Vec<Vec<String>>,
+    /// The generation prompt left by Stable Diffusion or ComfyUI.
+    #[serde(default)]
+    pub prompt: Option<String>,
+}
+
+impl EmbeddedMetadata {
+    pub fn extract(bytes: &[u8]) -> Option<Self> {
+        let mut meta = Self::default();
+
+        meta.read_exif(bytes);
+        meta.read_png_text(bytes);
+        meta.read_iptc(bytes);
+        if let Some(xmp) = find_xmp_packet(bytes) {
+            meta.read_xmp(&xmp);
+        }
+        meta.prompt = meta.find_prompt();
+        meta.dedup_keywords();
+
+        (!meta.is_empty()).then_some(meta)
+    }
+
+    /// Merges the content of a standalone xmp packet, like a sidecar.
+    pub fn merge_xmp(&mut self, xmp: &str) {
+        self.read_xmp(xmp);
+        self.dedup_keywords();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.keywords.is_empty() && self.hierarchical_keywords.is_empty()
+    }
+
+    fn dedup_keywords(&mut self) {
+        self.keywords.sort();
+        self.keywords.dedup();
+        self.hierarchical_keywords.sort();
+        self.hierarchical_keywords.dedup();
+    }
+
+    pub fn camera(&self) -> Option<String> {
+        let model = self.fields.get("Exif.Model")?.trim_matches('"').trim();
+        match self
+            .fields
+            .get("Exif.Make")
+            .map(|m| m.trim_matches('"').trim())
+        {
+            Some(make) if !model.starts_with(make) => Some(format!("{} {}", make, model)),
+            _ => Some(model.to_string()),
+        }
+    }
+
+    pub fn contains(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        self.fields
+            .values()
+            .chain(&self.keywords)
+            .any(|value| value.to_lowercase().contains(&query))
+    }
+
+    fn read_exif(&mut self, bytes: &[u8]) {
+        let Ok(exif) = exif::Reader::new().read_from_container(&mut Cursor::new(bytes)) else {
+            return;
+        };
+
+        for field in exif.fields() {
+            if field.ifd_num != exif::In::PRIMARY || field.tag == exif::Tag::MakerNote {
+                continue;
+            }
+
+            let value = if field.tag == exif::Tag::UserComment {
+                // Stable Diffusion webui stores its parameters here for jpeg and webp.
+                match &field.value {
+                    exif::Value::Undefined(data, _) if data.len() > 8 => {
+                        decode_user_comment(&data[..8], &data[8..])
+                    }
+                    _ => continue,
+                }
+            } else {
+                field.display_value().with_unit(&exif).to_string()
+            };
+
+            self.fields.insert(format!("Exif.{}", field.tag), value);
+        }
+    }
+
+    fn read_png_text(&mut self, bytes: &[u8]) {
+        let Ok(reader) = png::Decoder::new(Cursor::new(bytes)).read_info() else {
+            return;
+        };
+        let info = reader.info();
+
+        for chunk in &info.uncompressed_latin1_text {
+            self.fields
+                .insert(format!("Png.{}", chunk.keyword), chunk.text.clone());
+        }
+        for chunk in &info.compressed_latin1_text {
+            if let Ok(text) = chunk.get_text() {
+                self.fields.insert(format!("Png.{}", chunk.keyword), text);
+            }
+        }
+        for chunk in &info.utf8_text {
+            if let Ok(text) = chunk.get_text() {
+                if chunk.keyword == XMP_PNG_KEYWORD {
+                    self.read_xmp(&text);
+                } else {
+                    self.fields.insert(format!("Png.{}", chunk.keyword), text);
+                }
+            }
+        }
+    }
+
+    fn read_iptc(&mut self, bytes: &[u8]) {
+        let Some(iim) = find_iptc_block(bytes) else {
+            return;
+        };
+
+        let mut offset = 0;
+        while offset + 5 <= iim.len() && iim[offset] == 0x1c {
+            let record = iim[offset + 1];
+            let dataset = iim[offset + 2];
+            let len = u16::from_be_bytes([iim[offset + 3], iim[offset + 4]]) as usize;
+            let Some(data) = iim.get(offset + 5..offset + 5 + len) else {
+                break;
+            };
+            offset += 5 + len;
+
+            if record != 2 {
+                continue;
+            }
+            let value = String::from_utf8_lossy(data).to_string();
+            let name = match dataset {
+                5 => "ObjectName",
+                25 => {
+                    self.keywords.push(value);
+                    continue;
+                }
+                80 => "Byline",
+                105 => "Headline",
+                116 => "Copyright",
+                120 => "Caption",
+                _ => continue,
+            };
+            self.fields.insert(format!("Iptc.{}", name), value);
+        }
+    }
+
+    fn read_xmp(&mut self, xmp: &str) {
+        self.keywords.extend(xmp_items(xmp, "dc:subject"));
+        self.hierarchical_keywords.extend(
+            xmp_items(xmp, "lr:hierarchicalSubject")
+                .into_iter()
+                .map(|path| {
+                    path.split(HIERARCHY_SEPARATOR)
+                        .map(|name| name.trim().to_string())
+                        .filter(|name| !name.is_empty())
+                        .collect::<Vec<_>>()
+                })
+                .filter(|path| !path.is_empty()),
+        );
+
+        for prop in ["dc:title", "dc:description", "dc:creator", "dc:rights"] {
+            let items = xmp_items(xmp, prop);
+            if !items.is_empty() {
+                self.fields
+                    .insert(format!("Xmp.{}", prop), items.join("; "));
+            }
+        }
+        for prop in ["xmp:CreatorTool", "photoshop:Headline", "tiff:Model"] {
+            if let Some(value) = xmp_value(xmp, prop) {
+                self.fields.insert(format!("Xmp.{}", prop), value);
+            }
+        }
+    }
+
+    fn find_prompt(&self) -> Option<String> {
+        let parameters = self
+            .fields
+            .get("Png.parameters")
+            .or_else(|| self.fields.get("Exif.UserComment"))
+            .filter(|p| p.contains("Steps:"));
+        if let Some(parameters) = parameters {
+            let end = ["\nNegative prompt:", "\nSteps:"]
+                .iter()
+                .filter_map(|marker| parameters.find(marker))
+                .min()
+                .unwrap_or(parameters.len());
+            return Some(parameters[..end].trim().to_string());
+        }
+
+        // ComfyUI stores the whole node graph, the prompts live in the text encoders.
+        let graph = serde_json::from_str::<Value>(self.fields.get("Png.prompt")?).ok()?;
+        let prompts = graph
+            .as_object()?
+            .values()
+            .filter(|node| {
+                node.get("class_type")
+                    .and_then(Value::as_str)
+                    .is_some_and(|ty| ty.contains("CLIPTextEncode"))
+            })
+            .filter_map(|node| node.get("inputs")?.get("text")?.as_str())
+            .collect::<Vec<_>>();
+
+        (!prompts.is_empty()).then(|| prompts.join("\n"))
+    }
+}
+
+fn decode_user_comment(charset: &[u8], data: &[u8]) -> String {
+    let text = match charset {
+        b"UNICODE\0" => {
+            // Written big endian by most tools, but some use the byte order of the file.
+            let big_endian = data.first() == Some(&0);
+            let units = data
+                .chunks_exact(2)
+                .map(|c| {
+                    if big_endian {
+                        u16::from_be_bytes([c[0], c[1]])
+                    } else {
+                        u16::from_le_bytes([c[0], c[1]])
+                    }
+                })
+                .collect::<Vec<_>>();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(data).to_string(),
+    };
+
+    text.trim_end_matches('\0').to_string()
+}
+
+pub fn find_xmp_packet(bytes: &[u8]) -> Option<String> {
+    let begin = find_bytes(bytes, XMP_BEGIN.as_bytes())?;
+    let end = find_bytes(&bytes[begin..], XMP_END.as_bytes())? + begin + XMP_END.len();
+    Some(String::from_utf8_lossy(&bytes[begin..end]).to_string())
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Finds the IPTC-IIM block inside the Photoshop resources of a jpeg file.
+fn find_iptc_block(bytes: &[u8]) -> Option<&[u8]> {
+    if !bytes.starts_with(&[0xff, 0xd8]) {
+        return None;
+    }
+
+    let mut offset = 2;
+    while offset + 4 <= bytes.len() && bytes[offset] == 0xff {
+        let marker = bytes[offset + 1];
+        let len = u16::from_be_bytes([bytes[offset + 2], bytes[offset + 3]]) as usize;
+        let segment = bytes.get(offset + 4..offset + 2 + len)?;
+
+        // APP13
+        if marker == 0xed && segment.starts_with(PHOTOSHOP_SIGNATURE) {
+            return find_photoshop_resource(&segment[PHOTOSHOP_SIGNATURE.len()..], IPTC_RESOURCE);
+        }
+        // Start of scan, no more metadata segments.
+        if marker == 0xda {
+            return None;
+        }
+        offset += 2 + len;
+    }
+
+    None
+}
+
+fn find_photoshop_resource(mut data: &[u8], id: u16) -> Option<&[u8]> {
+    while data.len() >= 12 && data.starts_with(b"8BIM") {
+        let resource = u16::from_be_bytes([data[4], data[5]]);
+        // Pascal string padded to an even length.
+        let name_len = data[6] as usize;
+        let name_len = (name_len + 1 + 1) & !1;
+        let size_offset = 6 + name_len;
+        let size =
+            u32::from_be_bytes(data.get(size_offset..size_offset + 4)?.try_into().ok()?) as usize;
+        let content = data.get(size_offset + 4..size_offset + 4 + size)?;
+
+        if resource == id {
+            return Some(content);
+        }
+        data = data.get(size_offset + 4 + ((size + 1) & !1)..)?;
+    }
+
+    None
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Items of an rdf container (`rdf:Bag`, `rdf:Seq` or `rdf:Alt`) property.
+pub fn xmp_items(xmp: &str, prop: &str) -> Vec<String> {
+    let open = format!("<{}>", prop);
+    let close = format!("</{}>", prop);
+    let Some(begin) = xmp.find(&open) else {
+        return Vec::new();
+    };
+    let Some(end) = xmp[begin..].find(&close) else {
+        return Vec::new();
+    };
+    let mut content = &xmp[begin + open.len()..begin + end];
+
+    let mut items = Vec::new();
+    while let Some(start) = content.find("<rdf:li") {
+        let Some(value_start) = content[start..].find('>').map(|i| start + i + 1) else {
+            break;
+        };
+        let Some(value_end) = content[value_start..]
+            .find("</rdf:li>")
+            .map(|i| value_start + i)
+        else {
+            break;
+        };
+        items.push(unescape_xml(content[value_start..value_end].trim()));
+        content = &content[value_end..];
+    }
+
+    items
+}
+
+/// A simple property, written either as an attribute or as an element.
+pub fn xmp_value(xmp: &str, prop: &str) -> Option<String> {
+    let attr = format!("{}=\"", prop);
+    if let Some(begin) = xmp.find(&attr).map(|i| i + attr.len()) {
+        let end = xmp[begin..].find('"')? + begin;
+        return Some(unescape_xml(&xmp[begin..end]));
+    }
+
+    let open = format!("<{}>", prop);
+    let begin = xmp.find(&open)? + open.len();
+    let end = xmp[begin..].find(&format!("</{}>", prop))? + begin;
+    let value = xmp[begin..end].trim();
+    (!value.starts_with('<')).then(|| unescape_xml(value))
+}
+
+/// Builds an xmp packet carrying tag virtual paths, both as flat
+/// `dc:subject` keywords and as `lr:hierarchicalSubject` entries.
+pub fn build_xmp_packet(paths: &[Vec<String>]) -> String {
+    let bag = |items: Vec<String>| {
+        items
+            .iter()
+            .map(|item| format!("     <rdf:li>{}</rdf:li>", escape_xml(item)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let mut subjects = paths
+        .iter()
+        .filter_map(|path| path.last().cloned())
+        .collect::<Vec<_>>();
+    subjects.sort();
+    subjects.dedup();
+    let hierarchy = paths
+        .iter()
+        .map(|path| {
+            path.iter()
+                .map(|name| name.replace(HIERARCHY_SEPARATOR, "/"))
+                .collect::<Vec<_>>()
+                .join(&HIERARCHY_SEPARATOR.to_string())
+        })
+        .collect();
+
+    [
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>",
+        "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">",
+        " <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">",
+        "  <rdf:Description rdf:about=\"\"",
+        "    xmlns:dc=\"http://purl.org/dc/elements/1.1/\"",
+        "    xmlns:lr=\"http://ns.adobe.com/lightroom/1.0/\">",
+        "   <dc:subject>",
+        "    <rdf:Bag>",
+        bag(subjects).as_str(),
+        "    </rdf:Bag>",
+        "   </dc:subject>",
+        "   <lr:hierarchicalSubject>",
+        "    <rdf:Bag>",
+        bag(hierarchy).as_str(),
+        "    </rdf:Bag>",
+        "   </lr:hierarchicalSubject>",
+        "  </rdf:Description>",
+        " </rdf:RDF>",
+        "</x:xmpmeta>",
+        "<?xpacket end=\"w\"?>",
+    ]
+    .join("\n")
+}
+
+/// Sidecars are named `name.ext.xmp`, so `a.jpg` and `a.png` exported side
+/// by side don't share one.
+pub fn xmp_sidecar_path(file: &Path) -> PathBuf {
+    let mut path = file.as_os_str().to_owned();
+    path.push(".xmp");
+    PathBuf::from(path)
+}
+
+/// Reads the sidecar of a file, either `name.ext.xmp` or Adobe's `name.xmp`.
+pub fn read_xmp_sidecar(file: &Path) -> Option<String> {
+    [xmp_sidecar_path(file), file.with_extension("xmp")]
+        .into_iter()
+        .find_map(|path| read_to_string(path).ok())
+        .and_then(|content| find_xmp_packet(content.as_bytes()))
+}
+
+/// Embeds the packet into jpeg and png files, replacing any existing one.
+/// Returns `None` for formats that can't carry it.
+pub fn embed_xmp(bytes: &[u8], packet: &str) -> Option<Vec<u8>> {
+    if bytes.starts_with(&[0xff, 0xd8]) {
+        embed_xmp_jpeg(bytes, packet)
+    } else if bytes.starts_with(PNG_SIGNATURE) {
+        embed_xmp_png(bytes, packet)
+    } else {
+        None
+    }
+}
+
+fn embed_xmp_jpeg(bytes: &[u8], packet: &str) -> Option<Vec<u8>> {
+    let payload_len = XMP_JPEG_NAMESPACE.len() + packet.len() + 2;
+    if payload_len > u16::MAX as usize {
+        return None;
+    }
+
+    let mut result = bytes[..2].to_vec();
+    let mut segment = vec![0xff, 0xe1];
+    segment.extend((payload_len as u16).to_be_bytes());
+    segment.extend(XMP_JPEG_NAMESPACE);
+    segment.extend(packet.as_bytes());
+
+    let mut offset = 2;
+    let mut inserted = false;
+    while offset + 4 <= bytes.len() && bytes[offset] == 0xff {
+        let marker = bytes[offset + 1];
+        // Keep JFIF or EXIF headers first, as some readers expect them there.
+        if !inserted && !matches!(marker, 0xe0 | 0xe1) {
+            result.extend(&segment);
+            inserted = true;
+        }
+        if marker == 0xda {
+            break;
+        }
+
+        let len = u16::from_be_bytes([bytes[offset + 2], bytes[offset + 3]]) as usize;
+        let end = offset + 2 + len;
+        let content = bytes.get(offset + 4..end)?;
+        if !(marker == 0xe1 && content.starts_with(XMP_JPEG_NAMESPACE)) {
+            result.extend(&bytes[offset..end]);
+        }
+        offset = end;
+    }
+
+    if !inserted {
+        result.extend(&segment);
+    }
+    result.extend(&bytes[offset..]);
+    Some(result)
+}
+
+fn embed_xmp_png(bytes: &[u8], packet: &str) -> Option<Vec<u8>> {
+    let mut chunk_data = Vec::new();
+    chunk_data.extend(XMP_PNG_KEYWORD.as_bytes());
+    // Null separator, no compression, empty language tag and translated keyword.
+    chunk_data.extend([0, 0, 0, 0, 0]);
+    chunk_data.extend(packet.as_bytes());
+
+    let mut chunk = Vec::new();
+    chunk.extend((chunk_data.len() as u32).to_be_bytes());
+    let mut hashed = b"iTXt".to_vec();
+    hashed.extend(&chunk_data);
+    chunk.extend(&hashed);
+    chunk.extend(crc32fast::hash(&hashed).to_be_bytes());
+
+    let mut result = PNG_SIGNATURE.to_vec();
+    let mut offset = PNG_SIGNATURE.len();
+    while offset + 8 <= bytes.len() {
+        let len = u32::from_be_bytes(bytes[offset..offset + 4].try_into().ok()?) as usize;
+        let ty = &bytes[offset + 4..offset + 8];
+        let end = offset + 12 + len;
+        let data = bytes.get(offset + 8..end - 4)?;
+
+        let is_xmp = ty == b"iTXt"
+            && data.starts_with(XMP_PNG_KEYWORD.as_bytes())
+            && data.get(XMP_PNG_KEYWORD.len()) == Some(&0);
+        if !is_xmp {
+            result.extend(&bytes[offset..end]);
+        }
+        if ty == b"IHDR" {
+            result.extend(&chunk);
+        }
+        offset = end;
+    }
+
+    Some(result)
+}
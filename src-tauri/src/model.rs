@@ -0,0 +1,49 @@
+use std::{path::PathBuf, sync::Arc};
+
+use crate::{
+    app::AppResult,
+    clip::{ClipModel, CLIP},
+    ocr::{OcrModel, OCR},
+};
+
+/// Folder under the app data directory holding the on-device models.
+pub const MODELS: &str = "models";
+
+/// Lazily loads the models from disk, they are only paid for when used.
+pub struct ModelCache {
+    dir: PathBuf,
+    clip: Option<Arc<ClipModel>>,
+    ocr: Option<Arc<OcrModel>>,
+}
+
+impl ModelCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            clip: None,
+            ocr: None,
+        }
+    }
+
+    pub fn clip(&mut self) -> AppResult<Arc<ClipModel>> {
+        if let Some(clip) = &self.clip {
+            return Ok(clip.clone());
+        }
+
+        log::info!("Loading CLIP model from {:?}.", self.dir.join(CLIP));
+        let clip = Arc::new(ClipModel::load(&self.dir.join(CLIP))?);
+        self.clip = Some(clip.clone());
+        Ok(clip)
+    }
+
+    pub fn ocr(&mut self) -> AppResult<Arc<OcrModel>> {
+        if let Some(ocr) = &self.ocr {
+            return Ok(ocr.clone());
+        }
+
+        log::info!("Loading OCR model from {:?}.", self.dir.join(OCR));
+        let ocr = Arc::new(OcrModel::load(&self.dir.join(OCR))?);
+        self.ocr = Some(ocr.clone());
+        Ok(ocr)
+    }
+}
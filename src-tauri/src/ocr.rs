@@ -0,0 +1,59 @@
+use std::path::Path;
+
+use ocrs::{ImageSource, OcrEngine, OcrEngineParams};
+use rten::Model;
+
+use crate::app::{AppError, AppResult, UserSettings};
+
+pub const OCR: &str = "ocr";
+const DETECTION_MODEL: &str = "text-detection.rten";
+const RECOGNITION_MODEL: &str = "text-recognition.rten";
+
+fn model_err(err: impl ToString) -> AppError {
+    AppError::Model(err.to_string())
+}
+
+fn load_model(path: &Path) -> AppResult<Model> {
+    if !path.is_file() {
+        return Err(AppError::ModelNotFound(path.to_path_buf()));
+    }
+
+    Model::load_file(path).map_err(model_err)
+}
+
+pub fn is_enabled(settings: &UserSettings) -> bool {
+    settings.get_as("experimental", "ocr").unwrap_or_default()
+}
+
+/// Text detection and recognition models running on the CPU.
+pub struct OcrModel {
+    engine: OcrEngine,
+}
+
+impl OcrModel {
+    pub fn load(dir: &Path) -> AppResult<Self> {
+        let engine = OcrEngine::new(OcrEngineParams {
+            detection_model: Some(load_model(&dir.join(DETECTION_MODEL))?),
+            recognition_model: Some(load_model(&dir.join(RECOGNITION_MODEL))?),
+            ..Default::default()
+        })
+        .map_err(model_err)?;
+
+        Ok(Self { engine })
+    }
+
+    /// Recognized lines, top to bottom, joined by new lines.
+    pub fn recognize(&self, bytes: &[u8]) -> AppResult<String> {
+        let image = image::load_from_memory(bytes)?.into_rgb8();
+        let source =
+            ImageSource::from_bytes(image.as_raw(), image.dimensions()).map_err(model_err)?;
+        let input = self.engine.prepare_input(source).map_err(model_err)?;
+
+        Ok(self
+            .engine
+            .get_text(&input)
+            .map_err(model_err)?
+            .trim()
+            .to_string())
+    }
+}
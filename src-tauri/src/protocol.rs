@@ -0,0 +1,428 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use file_format::FileFormat;
+use tauri::{
+    http::{header, HeaderValue, Method, Request, Response, StatusCode},
+    AppHandle, Manager, Url,
+};
+use uuid::Uuid;
+
+use crate::{
+    app::{AppError, AppResult, AssetId, AssetType, CACHE},
+    crypto::{self, LibraryCipher},
+    library::Libraries,
+    rendition::{Rendition, RenditionFormat, THUMBNAIL_WIDTH},
+};
+
+pub const SCHEME: &str = "snowflake";
+
+/// Clients revalidate every time with the ETag, the url doesn't change when
+/// the content does.
+const CACHE_CONTROL: &str = "private, no-cache";
+/// Bodies are read into memory, larger ranges are cut short and the webview
+/// asks for the rest.
+const MAX_BODY: u64 = 4 * 1024 * 1024;
+/// Requested widths are rounded up to one of these, so a handful of
+/// renditions per asset end up in the cache. Wider means the original size.
+const WIDTHS: &[u32] = &[64, 128, 256, 512, 1024, 2048];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Asset,
+    /// The render of models, a small rendition of images and the original
+    /// of anything else.
+    Thumbnail,
+}
+
+/// `snowflake://asset/<id>?w=512&fmt=webp` or `snowflake://thumbnail/<id>`,
+/// with `library=<root>` for libraries other than the active one. Windows
+/// webviews request `http://snowflake.localhost/asset/<id>` instead.
+#[derive(Debug)]
+struct AssetRequest {
+    kind: Kind,
+    asset: AssetId,
+    library: Option<PathBuf>,
+    width: Option<u32>,
+    format: Option<RenditionFormat>,
+}
+
+impl AssetRequest {
+    fn parse(uri: &str) -> Option<Self> {
+        let url = Url::parse(uri).ok()?;
+        let mut segments = url.path_segments()?.filter(|s| !s.is_empty());
+        let kind = match url.host_str() {
+            Some("asset") | Some("thumbnail") => url.host_str()?,
+            _ => segments.next()?,
+        };
+        let kind = match kind {
+            "asset" => Kind::Asset,
+            "thumbnail" => Kind::Thumbnail,
+            _ => return None,
+        };
+        let asset = AssetId(Uuid::parse_str(segments.next()?).ok()?);
+
+        let mut request = Self {
+            kind,
+            asset,
+            library: None,
+            width: None,
+            format: None,
+        };
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "library" => request.library = Some(PathBuf::from(value.as_ref())),
+                "w" => request.width = Some(snap_width(value.parse::<u32>().ok()?)),
+                "fmt" => request.format = Some(RenditionFormat::from_ext(&value)?),
+                _ => {}
+            }
+        }
+
+        Some(request)
+    }
+}
+
+fn snap_width(width: u32) -> u32 {
+    WIDTHS
+        .iter()
+        .copied()
+        .find(|w| *w >= width)
+        .unwrap_or(u32::MAX)
+}
+
+/// An asset file, decrypted chunk by chunk if it is sealed.
+enum Source {
+    Plain(File, u64),
+    Sealed(File, u64, Arc<LibraryCipher>),
+    Memory(Vec<u8>),
+}
+
+impl Source {
+    fn open(path: &Path, cipher: Option<Arc<LibraryCipher>>) -> AppResult<Self> {
+        let mut file = File::open(path)?;
+        let len = file.metadata()?.len();
+
+        let mut magic = [0u8; crypto::MAGIC.len()];
+        let sealed = file.read_exact(&mut magic).is_ok() && crypto::is_sealed(&magic);
+        match (sealed, cipher) {
+            (false, _) => Ok(Self::Plain(file, len)),
+            (true, Some(cipher)) => Ok(Self::Sealed(file, len, cipher)),
+            (true, None) => Err(AppError::LibraryEncrypted),
+        }
+    }
+
+    fn len(&self) -> u64 {
+        match self {
+            Self::Plain(_, len) => *len,
+            Self::Sealed(_, len, _) => crypto::plain_len(*len),
+            Self::Memory(bytes) => bytes.len() as u64,
+        }
+    }
+
+    fn read_range(&mut self, range: Range<u64>) -> AppResult<Vec<u8>> {
+        match self {
+            Self::Plain(file, _) => {
+                let mut bytes = vec![0u8; (range.end - range.start) as usize];
+                file.seek(SeekFrom::Start(range.start))?;
+                file.read_exact(&mut bytes)?;
+                Ok(bytes)
+            }
+            Self::Sealed(file, len, cipher) => cipher.decrypt_range(file, *len, range),
+            Self::Memory(bytes) => Ok(bytes[range.start as usize..range.end as usize].to_vec()),
+        }
+    }
+
+    fn media_type(&mut self) -> AppResult<String> {
+        let head = self.read_range(0..self.len().min(crypto::CHUNK as u64))?;
+        Ok(FileFormat::from_bytes(&head).media_type().to_string())
+    }
+}
+
+/// What the url resolves to, gathered while holding the library.
+struct Target {
+    path: PathBuf,
+    root: PathBuf,
+    ty: AssetType,
+    ext: String,
+    crc: Option<u32>,
+    cipher: Option<Arc<LibraryCipher>>,
+}
+
+/// Parses a `Range` header against a body of `len` bytes. Multiple ranges
+/// and other units are ignored and the whole body is served, `Err` means the
+/// range can't be satisfied.
+fn parse_range(value: &str, len: u64) -> Result<Option<Range<u64>>, ()> {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    if spec.contains(',') {
+        return Ok(None);
+    }
+
+    let (start, end) = spec.split_once('-').ok_or(())?;
+    let range = match (start.trim(), end.trim()) {
+        ("", suffix) => len.saturating_sub(suffix.parse::<u64>().map_err(|_| ())?)..len,
+        (start, "") => start.parse::<u64>().map_err(|_| ())?..len,
+        (start, end) => {
+            start.parse::<u64>().map_err(|_| ())?
+                ..end
+                    .parse::<u64>()
+                    .map_err(|_| ())?
+                    .saturating_add(1)
+                    .min(len)
+        }
+    };
+
+    if range.start >= range.end {
+        Err(())
+    } else {
+        Ok(Some(range))
+    }
+}
+
+fn error(status: StatusCode, message: String) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(message.into_bytes())
+        .unwrap_or_default()
+}
+
+/// Serves originals, thumbnails and renditions of assets to the webview,
+/// decrypted if the library is encrypted.
+pub fn handle(app: &AppHandle, request: Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let Some(parsed) = AssetRequest::parse(&request.uri().to_string()) else {
+        return error(StatusCode::BAD_REQUEST, "Invalid asset url.".to_string());
+    };
+
+    match serve(app, &parsed, &request) {
+        Ok(response) => response,
+        Err(err) => {
+            log::warn!("Failed to serve {:?}: {}", parsed, err);
+            let status = match &err {
+                AppError::AssetNotFound(_)
+                | AppError::StorageNotInitialized
+                | AppError::LibraryNotLoaded(_) => StatusCode::NOT_FOUND,
+                AppError::Io(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    StatusCode::NOT_FOUND
+                }
+                AppError::Decode(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            error(status, err.to_string())
+        }
+    }
+}
+
+fn resolve(app: &AppHandle, request: &AssetRequest) -> AppResult<Target> {
+    let libraries = app.state::<Mutex<Libraries>>();
+    let libraries = libraries
+        .lock()
+        .map_err(|_| AppError::StorageNotInitialized)?;
+    let Some(storage) = libraries.get(request.library.as_deref()) else {
+        return Err(AppError::StorageNotInitialized);
+    };
+    let Some(asset) = storage.assets.get(&request.asset) else {
+        return Err(AppError::AssetNotFound(request.asset));
+    };
+
+    Ok(Target {
+        path: asset.get_file_path(&storage.cache.root),
+        root: storage.cache.root.clone(),
+        ty: asset.ty,
+        ext: asset.ext.to_string(),
+        crc: storage.cache.asset_crc.get(&asset.id).copied(),
+        cipher: storage.cache.cipher.clone(),
+    })
+}
+
+fn serve(
+    app: &AppHandle,
+    request: &AssetRequest,
+    http: &Request<Vec<u8>>,
+) -> AppResult<Response<Vec<u8>>> {
+    // Reading and rendering may take a while, don't hold the library meanwhile.
+    let target = resolve(app, request)?;
+
+    let rendition = match (request.kind, target.ty) {
+        (Kind::Thumbnail, AssetType::RasterGraphics) => Some(Rendition {
+            width: request.width.unwrap_or(THUMBNAIL_WIDTH),
+            format: request.format.unwrap_or(RenditionFormat::Webp),
+        }),
+        (Kind::Asset, AssetType::RasterGraphics)
+            if request.width.is_some() || request.format.is_some() =>
+        {
+            Some(Rendition {
+                width: request.width.unwrap_or(u32::MAX),
+                format: request
+                    .format
+                    .or_else(|| RenditionFormat::from_ext(&target.ext))
+                    .unwrap_or(RenditionFormat::Png),
+            })
+        }
+        _ => None,
+    };
+    let render_cache = matches!(
+        (request.kind, target.ty),
+        (Kind::Thumbnail, AssetType::GltfModel)
+    );
+
+    let etag = match (&rendition, target.crc) {
+        _ if render_cache => None,
+        (Some(rendition), Some(crc)) => Some(format!(
+            "\"{:08x}-{}.{}\"",
+            crc,
+            rendition.width,
+            rendition.format.ext()
+        )),
+        (None, Some(crc)) => Some(format!("\"{:08x}\"", crc)),
+        (_, None) => None,
+    };
+    if let Some(etag) = &etag {
+        let matched = http
+            .headers()
+            .get(header::IF_NONE_MATCH)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| {
+                v.split(',')
+                    .any(|t| t.trim() == etag.as_str() || t.trim() == "*")
+            });
+        if matched {
+            return Ok(Response::builder()
+                .status(StatusCode::NOT_MODIFIED)
+                .header(header::ETAG, etag)
+                .header(header::CACHE_CONTROL, CACHE_CONTROL)
+                .body(Vec::new())
+                .unwrap_or_default());
+        }
+    }
+
+    let (mut source, media_type) = match rendition {
+        Some(rendition) => {
+            let Some(crc) = target.crc else {
+                return Err(AppError::AssetNotFound(request.asset));
+            };
+            let bytes = rendition.get(
+                &target.root,
+                request.asset,
+                crc,
+                target.cipher.as_deref(),
+                || crypto::read_file(&target.path, target.cipher.as_deref()),
+            )?;
+            (
+                Source::Memory(bytes),
+                rendition.format.media_type().to_string(),
+            )
+        }
+        None if render_cache => {
+            let path = target
+                .root
+                .join(CACHE)
+                .join(format!("{}.png", request.asset.0));
+            (
+                Source::open(&path, target.cipher.clone())?,
+                "image/png".to_string(),
+            )
+        }
+        None => {
+            let mut source = Source::open(&target.path, target.cipher.clone())?;
+            let media_type = source.media_type()?;
+            (source, media_type)
+        }
+    };
+
+    let len = source.len();
+    let range = match http
+        .headers()
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| parse_range(v, len))
+        .transpose()
+    {
+        Ok(range) => range.flatten(),
+        Err(()) => {
+            return Ok(Response::builder()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+                .body(Vec::new())
+                .unwrap_or_default());
+        }
+    };
+
+    // Media elements play along with a partial answer, images need all of it.
+    let streamed = media_type.starts_with("video/") || media_type.starts_with("audio/");
+    let range = match range {
+        Some(range) => Some(range.start..range.end.min(range.start + MAX_BODY)),
+        None if streamed && len > MAX_BODY => Some(0..MAX_BODY),
+        None => None,
+    };
+
+    let mut response = Response::builder()
+        .header(header::CONTENT_TYPE, media_type)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CACHE_CONTROL, CACHE_CONTROL);
+    if let Some(etag) = etag.and_then(|e| HeaderValue::from_str(&e).ok()) {
+        response = response.header(header::ETAG, etag);
+    }
+    let body_range = match &range {
+        Some(range) => {
+            response = response.status(StatusCode::PARTIAL_CONTENT).header(
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", range.start, range.end - 1, len),
+            );
+            range.clone()
+        }
+        None => 0..len,
+    };
+    let body = if *http.method() == Method::HEAD {
+        Vec::new()
+    } else {
+        source.read_range(body_range)?
+    };
+
+    Ok(response.body(body).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_are_parsed() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Ok(Some(0..100)));
+        assert_eq!(parse_range("bytes=500-", 1000), Ok(Some(500..1000)));
+        assert_eq!(parse_range("bytes=-100", 1000), Ok(Some(900..1000)));
+        assert_eq!(parse_range("bytes=900-5000", 1000), Ok(Some(900..1000)));
+        assert_eq!(parse_range("bytes=-5000", 1000), Ok(Some(0..1000)));
+    }
+
+    #[test]
+    fn other_ranges_serve_the_whole_body() {
+        assert_eq!(parse_range("items=0-99", 1000), Ok(None));
+        assert_eq!(parse_range("bytes=0-9,20-29", 1000), Ok(None));
+    }
+
+    #[test]
+    fn unsatisfiable_ranges_are_rejected() {
+        assert_eq!(parse_range("bytes=1000-", 1000), Err(()));
+        assert_eq!(parse_range("bytes=50-10", 1000), Err(()));
+        assert_eq!(parse_range("bytes=0-0", 0), Err(()));
+        assert_eq!(parse_range("bytes=-0", 1000), Err(()));
+        assert_eq!(parse_range("bytes=a-b", 1000), Err(()));
+        assert_eq!(parse_range("bytes=10", 1000), Err(()));
+    }
+
+    #[test]
+    fn widths_snap_up() {
+        assert_eq!(snap_width(0), 64);
+        assert_eq!(snap_width(64), 64);
+        assert_eq!(snap_width(300), 512);
+        assert_eq!(snap_width(2048), 2048);
+        assert_eq!(snap_width(2049), u32::MAX);
+    }
+}
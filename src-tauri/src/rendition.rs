@@ -0,0 +1,157 @@
+use std::{
+    fs::{create_dir_all, read_dir, remove_dir_all, remove_file, rename, write},
+    io::Cursor,
+    path::Path,
+};
+
+use image::{imageops::FilterType, DynamicImage, ImageFormat};
+use uuid::Uuid;
+
+use crate::{
+    app::{AppResult, AssetId, CACHE},
+    crypto::{self, LibraryCipher},
+};
+
+/// Resized and transcoded copies of raster assets, under `cache/`.
+pub const RENDITIONS: &str = "renditions";
+pub const THUMBNAIL_WIDTH: u32 = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenditionFormat {
+    Webp,
+    Png,
+    Jpeg,
+}
+
+impl RenditionFormat {
+    pub fn from_ext(ext: &str) -> Option<Self> {
+        match ext.to_ascii_lowercase().as_str() {
+            "webp" => Some(Self::Webp),
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            _ => None,
+        }
+    }
+
+    pub fn ext(self) -> &'static str {
+        match self {
+            Self::Webp => "webp",
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+        }
+    }
+
+    pub fn media_type(self) -> &'static str {
+        match self {
+            Self::Webp => "image/webp",
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rendition {
+    /// Images narrower than this keep their size.
+    pub width: u32,
+    pub format: RenditionFormat,
+}
+
+impl Rendition {
+    /// The crc is part of the name, so renditions of a file which changed
+    /// since are never served.
+    fn file_name(&self, asset: AssetId, crc: u32) -> String {
+        format!(
+            "{}-{:08x}-{}.{}",
+            asset.0,
+            crc,
+            self.width,
+            self.format.ext()
+        )
+    }
+
+    /// Reads the rendition from the cache, rendering it from `original` on
+    /// a miss. Renditions of encrypted libraries are encrypted as well.
+    pub fn get(
+        &self,
+        root: &Path,
+        asset: AssetId,
+        crc: u32,
+        cipher: Option<&LibraryCipher>,
+        original: impl FnOnce() -> AppResult<Vec<u8>>,
+    ) -> AppResult<Vec<u8>> {
+        let dir = root.join(CACHE).join(RENDITIONS);
+        let path = dir.join(self.file_name(asset, crc));
+        if path.is_file() {
+            match crypto::read_file(&path, cipher) {
+                Ok(bytes) => return Ok(bytes),
+                Err(err) => log::warn!("Failed to read rendition {:?}: {}", path, err),
+            }
+        }
+
+        let bytes = self.render(&original()?)?;
+        create_dir_all(&dir)?;
+        // Written aside first, another request may read it meanwhile.
+        let temp = dir.join(format!("{}.tmp", Uuid::new_v4()));
+        match cipher {
+            Some(cipher) => write(&temp, cipher.encrypt(&bytes)?)?,
+            None => write(&temp, &bytes)?,
+        }
+        rename(temp, path)?;
+
+        Ok(bytes)
+    }
+
+    pub fn render(&self, original: &[u8]) -> AppResult<Vec<u8>> {
+        let mut image = image::load_from_memory(original)?;
+        if image.width() > self.width {
+            image = image.resize(self.width, u32::MAX, FilterType::Lanczos3);
+        }
+
+        // Neither encoder takes every pixel type the decoders produce.
+        let image = match self.format {
+            RenditionFormat::Webp => DynamicImage::ImageRgba8(image.into_rgba8()),
+            RenditionFormat::Jpeg => DynamicImage::ImageRgb8(image.into_rgb8()),
+            RenditionFormat::Png => image,
+        };
+        let format = match self.format {
+            RenditionFormat::Webp => ImageFormat::WebP,
+            RenditionFormat::Png => ImageFormat::Png,
+            RenditionFormat::Jpeg => ImageFormat::Jpeg,
+        };
+
+        let mut bytes = Cursor::new(Vec::new());
+        image.write_to(&mut bytes, format)?;
+        Ok(bytes.into_inner())
+    }
+}
+
+/// Removes every rendition, they are rendered again on demand.
+pub fn clear_renditions(root: &Path) -> AppResult<()> {
+    let dir = root.join(CACHE).join(RENDITIONS);
+    if dir.exists() {
+        remove_dir_all(dir)?;
+    }
+    Ok(())
+}
+
+pub fn remove_renditions(root: &Path, asset: AssetId) -> AppResult<()> {
+    let dir = root.join(CACHE).join(RENDITIONS);
+    if !dir.exists() {
+        return Ok(());
+    }
+
+    let prefix = asset.0.to_string();
+    for entry in read_dir(dir)? {
+        let path = entry?.path();
+        if path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with(&prefix))
+        {
+            remove_file(path)?;
+        }
+    }
+
+    Ok(())
+}
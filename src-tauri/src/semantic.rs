@@ -0,0 +1,191 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use hashbrown::HashMap;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    app::{AppError, AppResult, AssetId, AssetType, CACHE},
+    clip::{self, ClipModel},
+    crypto::{self, LibraryCipher},
+    library::Libraries,
+    model::ModelCache,
+};
+
+const EMBEDDING_INDEX: &str = "embeddings.bin";
+const MAGIC: &[u8; 4] = b"SFEI";
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticMatch {
+    pub asset: AssetId,
+    pub score: f32,
+}
+
+/// CLIP embeddings of raster assets, persisted in `cache/` as the magic,
+/// the dimension and then the uuid and little endian floats of each asset.
+/// Sealed like the assets in encrypted libraries.
+#[derive(Default)]
+pub struct EmbeddingIndex {
+    embeddings: HashMap<AssetId, Vec<f32>>,
+}
+
+impl EmbeddingIndex {
+    pub fn path(root: &Path) -> PathBuf {
+        root.join(CACHE).join(EMBEDDING_INDEX)
+    }
+
+    pub fn load(root: &Path, cipher: Option<&LibraryCipher>) -> Self {
+        let Ok(bytes) = crypto::read_file(&Self::path(root), cipher) else {
+            return Self::default();
+        };
+
+        match Self::parse(&bytes) {
+            Some(index) => index,
+            None => {
+                log::warn!("Discarding corrupted embedding index.");
+                Self::default()
+            }
+        }
+    }
+
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.get(..4)? != MAGIC {
+            return None;
+        }
+        let dim = u32::from_le_bytes(bytes.get(4..8)?.try_into().ok()?) as usize;
+
+        let mut embeddings = HashMap::default();
+        for entry in bytes[8..].chunks(16 + dim * 4) {
+            let id = Uuid::from_slice(entry.get(..16)?).ok()?;
+            let embedding = entry
+                .get(16..16 + dim * 4)?
+                .chunks_exact(4)
+                .map(|f| f32::from_le_bytes([f[0], f[1], f[2], f[3]]))
+                .collect();
+            embeddings.insert(AssetId(id), embedding);
+        }
+
+        Some(Self { embeddings })
+    }
+
+    pub fn save(&self, root: &Path, cipher: Option<&LibraryCipher>) -> AppResult<()> {
+        let dim = self.embeddings.values().next().map(Vec::len).unwrap_or(0);
+        let mut bytes = Vec::with_capacity(8 + self.embeddings.len() * (16 + dim * 4));
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&(dim as u32).to_le_bytes());
+
+        for (id, embedding) in &self.embeddings {
+            bytes.extend_from_slice(id.0.as_bytes());
+            for value in embedding {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        let bytes = match cipher {
+            Some(cipher) => cipher.encrypt(&bytes)?,
+            None => bytes,
+        };
+        Ok(std::fs::write(Self::path(root), bytes)?)
+    }
+
+    pub fn get(&self, asset: AssetId) -> Option<&[f32]> {
+        self.embeddings.get(&asset).map(Vec::as_slice)
+    }
+
+    pub fn rank(
+        &self,
+        query: &[f32],
+        limit: usize,
+        exclude: Option<AssetId>,
+    ) -> Vec<SemanticMatch> {
+        let mut matches = self
+            .embeddings
+            .iter()
+            .filter(|(id, _)| Some(**id) != exclude)
+            .map(|(id, embedding)| SemanticMatch {
+                asset: *id,
+                score: clip::similarity(query, embedding),
+            })
+            .collect::<Vec<_>>();
+        matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+        matches.truncate(limit);
+        matches
+    }
+}
+
+/// Embeds the raster assets missing from the index and drops the ones which
+/// are gone. The library is only locked while reading and merging.
+pub fn sync_embedding_index(
+    libraries: &Mutex<Libraries>,
+    library: Option<&Path>,
+    models: &Mutex<ModelCache>,
+) -> AppResult<Arc<ClipModel>> {
+    let (targets, cipher) = {
+        let mut libraries = libraries
+            .lock()
+            .map_err(|_| AppError::StorageNotInitialized)?;
+        let Some(storage) = libraries.get_mut(library) else {
+            return Err(AppError::StorageNotInitialized);
+        };
+
+        let root = storage.cache.root.clone();
+        let cipher = storage.cache.cipher.clone();
+        let index = storage
+            .cache
+            .embeddings
+            .get_or_insert_with(|| EmbeddingIndex::load(&root, cipher.as_deref()));
+        index
+            .embeddings
+            .retain(|id, _| storage.assets.get(id).is_some_and(|a| !a.is_deleted));
+
+        let targets = storage
+            .assets
+            .values()
+            .filter(|asset| {
+                !asset.is_deleted
+                    && matches!(asset.ty, AssetType::RasterGraphics)
+                    && !index.embeddings.contains_key(&asset.id)
+            })
+            .map(|asset| (asset.id, asset.get_file_path(&root)))
+            .collect::<Vec<_>>();
+        (targets, storage.cache.cipher.clone())
+    };
+
+    let model = models
+        .lock()
+        .map_err(|e| AppError::Model(e.to_string()))?
+        .clip()?;
+    if targets.is_empty() {
+        return Ok(model);
+    }
+
+    log::info!("Embedding {} assets.", targets.len());
+    let mut computed = Vec::with_capacity(targets.len());
+    for (asset, path) in targets {
+        match crypto::read_file(&path, cipher.as_deref())
+            .and_then(|bytes| model.embed_image(&bytes))
+        {
+            Ok(embedding) => computed.push((asset, embedding)),
+            Err(err) => log::warn!("Failed to embed asset {:?}: {}", asset, err),
+        }
+    }
+
+    let mut libraries = libraries
+        .lock()
+        .map_err(|_| AppError::StorageNotInitialized)?;
+    let Some(storage) = libraries.get_mut(library) else {
+        return Err(AppError::StorageNotInitialized);
+    };
+    let index = storage
+        .cache
+        .embeddings
+        .get_or_insert_with(Default::default);
+    index.embeddings.extend(computed);
+    index.save(&storage.cache.root, storage.cache.cipher.as_deref())?;
+
+    Ok(model)
+}
@@ -0,0 +1,536 @@
+use std::{
+    fs::{create_dir_all, read_dir, remove_file, write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, FixedOffset, Local, TimeZone};
+use hashbrown::HashSet;
+use serde::Serialize;
+
+use crate::{
+    app::{
+        AppError, AppResult, AssetId, CollectionId, ItemIds, Storage, StorageCache,
+        TagGroupConflictResolve, TagId, UserSettings,
+    },
+    crypto,
+};
+
+pub const SNAPSHOTS: &str = "snapshots";
+
+/// How often `snowflake.json` is copied into `snapshots/` on save, and how
+/// many copies are kept. Either being zero disables automatic snapshots.
+#[derive(Debug, Clone, Copy)]
+pub struct SnapshotPolicy {
+    pub interval_minutes: u32,
+    pub retention: usize,
+}
+
+impl Default for SnapshotPolicy {
+    fn default() -> Self {
+        Self {
+            interval_minutes: 30,
+            retention: 20,
+        }
+    }
+}
+
+impl SnapshotPolicy {
+    pub fn from_user_settings(settings: &UserSettings) -> Self {
+        let default = Self::default();
+        Self {
+            interval_minutes: settings
+                .get_as::<f32>("library", "snapshotInterval")
+                .map_or(default.interval_minutes, |m| m as u32),
+            retention: settings
+                .get_as::<f32>("library", "snapshotRetention")
+                .map_or(default.retention, |r| r as usize),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    /// Milliseconds since the epoch, also the file name.
+    pub id: String,
+    pub created_at: DateTime<FixedOffset>,
+    pub byte_size: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RetaggedAsset {
+    pub asset: AssetId,
+    pub added: Vec<TagId>,
+    pub removed: Vec<TagId>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotDiff {
+    pub added_assets: Vec<AssetId>,
+    pub removed_assets: Vec<AssetId>,
+    pub retagged_assets: Vec<RetaggedAsset>,
+    pub added_tags: Vec<TagId>,
+    pub removed_tags: Vec<TagId>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreReport {
+    pub restored: usize,
+    /// Assets whose file was deleted for good since, they can't come back.
+    pub missing_files: Vec<AssetId>,
+    /// Assets imported after the snapshot, moved into the recycle bin.
+    pub recycled: Vec<AssetId>,
+}
+
+pub fn snapshot_path(root: &Path, id: &str) -> PathBuf {
+    root.join(SNAPSHOTS).join(format!("{}.json", id))
+}
+
+/// Newest first.
+pub fn list_snapshots(root: &Path) -> AppResult<Vec<Snapshot>> {
+    let dir = root.join(SNAPSHOTS);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut snapshots = Vec::new();
+    for entry in read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let Some(millis) = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.parse::<i64>().ok())
+        else {
+            continue;
+        };
+        let Some(created_at) = Local.timestamp_millis_opt(millis).single() else {
+            continue;
+        };
+
+        snapshots.push(Snapshot {
+            id: millis.to_string(),
+            created_at: created_at.into(),
+            byte_size: entry.metadata()?.len(),
+        });
+    }
+
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+    Ok(snapshots)
+}
+
+pub fn take_snapshot(root: &Path, bytes: &[u8]) -> AppResult<Snapshot> {
+    create_dir_all(root.join(SNAPSHOTS))?;
+
+    let created_at = Local::now();
+    let id = created_at.timestamp_millis().to_string();
+    write(snapshot_path(root, &id), bytes)?;
+
+    Ok(Snapshot {
+        id,
+        created_at: created_at.into(),
+        byte_size: bytes.len() as u64,
+    })
+}
+
+/// Takes a snapshot if the newest one is older than the interval, then
+/// drops the oldest ones beyond the retention.
+pub fn auto_snapshot(root: &Path, bytes: &[u8], policy: SnapshotPolicy) -> AppResult<()> {
+    if policy.interval_minutes == 0 || policy.retention == 0 {
+        return Ok(());
+    }
+
+    let mut snapshots = list_snapshots(root)?;
+    let due = snapshots.first().is_none_or(|latest| {
+        Local::now().signed_duration_since(latest.created_at)
+            >= chrono::Duration::minutes(policy.interval_minutes as i64)
+    });
+    if !due {
+        return Ok(());
+    }
+
+    snapshots.insert(0, take_snapshot(root, bytes)?);
+    for outdated in snapshots.iter().skip(policy.retention) {
+        remove_file(snapshot_path(root, &outdated.id))?;
+    }
+
+    Ok(())
+}
+
+/// Snapshots of an encrypted library are sealed like `snowflake.json`.
+pub fn load_snapshot(cache: &StorageCache, id: &str) -> AppResult<Storage> {
+    let path = snapshot_path(&cache.root, id);
+    if !path.is_file() {
+        return Err(AppError::SnapshotNotFound(id.to_string()));
+    }
+
+    Ok(serde_json::from_slice(&crypto::read_file(
+        &path,
+        cache.cipher.as_deref(),
+    )?)?)
+}
+
+fn live_assets(storage: &Storage) -> impl Iterator<Item = AssetId> + '_ {
+    storage
+        .assets
+        .values()
+        .filter(|a| !a.is_deleted)
+        .map(|a| a.id)
+}
+
+fn live_tags(storage: &Storage) -> impl Iterator<Item = TagId> + '_ {
+    storage
+        .tags
+        .values()
+        .filter(|t| !t.is_deleted)
+        .map(|t| t.id)
+}
+
+fn tags_of(storage: &Storage, asset: AssetId) -> HashSet<TagId> {
+    storage
+        .assets
+        .get(&asset)
+        .map(|a| {
+            a.tags
+                .grouped
+                .values()
+                .chain(&a.tags.ungrouped)
+                .copied()
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// What changed going from `from` to `to`, items in the recycle bin count
+/// as removed.
+pub fn diff(from: &Storage, to: &Storage) -> SnapshotDiff {
+    let from_assets = live_assets(from).collect::<HashSet<_>>();
+    let to_assets = live_assets(to).collect::<HashSet<_>>();
+    let from_tags = live_tags(from).collect::<HashSet<_>>();
+    let to_tags = live_tags(to).collect::<HashSet<_>>();
+
+    let retagged_assets = from_assets
+        .intersection(&to_assets)
+        .filter_map(|asset| {
+            let before = tags_of(from, *asset);
+            let after = tags_of(to, *asset);
+            (before != after).then(|| RetaggedAsset {
+                asset: *asset,
+                added: after.difference(&before).copied().collect(),
+                removed: before.difference(&after).copied().collect(),
+            })
+        })
+        .collect();
+
+    SnapshotDiff {
+        added_assets: to_assets.difference(&from_assets).copied().collect(),
+        removed_assets: from_assets.difference(&to_assets).copied().collect(),
+        retagged_assets,
+        added_tags: to_tags.difference(&from_tags).copied().collect(),
+        removed_tags: from_tags.difference(&to_tags).copied().collect(),
+    }
+}
+
+/// Puts the collection back under its old parent, or the root if that one is
+/// gone, along with everything it contained in the snapshot.
+fn restore_collection(storage: &mut Storage, snapshot: &Storage, id: CollectionId) -> usize {
+    let Some(mut collection) = snapshot.collections.get(&id).cloned() else {
+        return 0;
+    };
+    if storage.sp_collections.is_special(id) {
+        return 0;
+    }
+
+    let root = storage.sp_collections.root;
+    let parent = collection
+        .parent
+        .filter(|p| storage.collections.contains_key(p))
+        .unwrap_or(root);
+    if let Some(current) = storage.collections.get(&id).cloned() {
+        if let Some(old_parent) = current.parent.and_then(|p| storage.collections.get_mut(&p)) {
+            old_parent.children.remove(&id);
+        }
+        collection.children.extend(current.children);
+        collection.content.extend(current.content);
+    }
+    collection.parent = Some(parent);
+    let children = collection.children.clone();
+    let content = collection.content.clone();
+
+    if collection.is_deleted {
+        storage.recycle_bin.collections.insert(id);
+    } else {
+        storage.recycle_bin.collections.remove(&id);
+        storage
+            .collections
+            .get_mut(&parent)
+            .unwrap()
+            .children
+            .insert(id);
+    }
+    storage.collections.insert(id, collection);
+
+    let mut restored = 1;
+    for child in children {
+        restored += restore_collection(storage, snapshot, child);
+    }
+    for tag in content {
+        restored += restore_tag(storage, snapshot, tag, true);
+    }
+    restored
+}
+
+fn restore_tag(storage: &mut Storage, snapshot: &Storage, id: TagId, with_assets: bool) -> usize {
+    let Some(mut tag) = snapshot.tags.get(&id).cloned() else {
+        return 0;
+    };
+
+    if let Some(current) = storage.tags.get(&id) {
+        if let Some(old_parent) = storage.collections.get_mut(&current.parent) {
+            old_parent.content.remove(&id);
+        }
+    }
+    if !storage.collections.contains_key(&tag.parent) {
+        tag.parent = storage.sp_collections.root;
+    }
+    tag.group = tag.group.filter(|g| storage.collections.contains_key(g));
+
+    if tag.is_deleted {
+        storage.recycle_bin.tags.insert(id);
+    } else {
+        storage.recycle_bin.tags.remove(&id);
+        storage
+            .collections
+            .get_mut(&tag.parent)
+            .unwrap()
+            .content
+            .insert(id);
+    }
+    storage.tags.insert(id, tag);
+
+    if with_assets {
+        let assets = snapshot
+            .assets
+            .values()
+            .filter(|a| {
+                a.tags
+                    .grouped
+                    .values()
+                    .chain(&a.tags.ungrouped)
+                    .any(|t| *t == id)
+            })
+            .map(|a| a.id)
+            .collect::<Vec<_>>();
+        for asset in assets {
+            if storage.assets.contains_key(&asset) {
+                let _ = storage.add_tag_to_asset(asset, id, TagGroupConflictResolve::Override);
+            }
+        }
+    }
+
+    1
+}
+
+fn restore_asset(
+    storage: &mut Storage,
+    snapshot: &Storage,
+    id: AssetId,
+    report: &mut RestoreReport,
+) -> AppResult<()> {
+    let Some(mut asset) = snapshot.assets.get(&id).cloned() else {
+        return Err(AppError::AssetNotFound(id));
+    };
+
+    let Ok(crc) = storage.cache.compute_crc(&asset) else {
+        report.missing_files.push(id);
+        return Ok(());
+    };
+
+    for tag in asset
+        .tags
+        .grouped
+        .values()
+        .chain(&asset.tags.ungrouped)
+        .copied()
+        .collect::<Vec<_>>()
+    {
+        if !storage.tags.contains_key(&tag) {
+            report.restored += restore_tag(storage, snapshot, tag, false);
+        }
+    }
+    asset
+        .tags
+        .grouped
+        .retain(|_, t| storage.tags.contains_key(t));
+    asset
+        .tags
+        .ungrouped
+        .retain(|t| storage.tags.contains_key(t));
+
+    storage.cache.remove_asset(id);
+    if asset.is_deleted {
+        storage.recycle_bin.assets.insert(id);
+    } else {
+        storage.recycle_bin.assets.remove(&id);
+        storage.cache.add_asset(crc, id);
+    }
+    storage.assets.insert(id, asset);
+    report.restored += 1;
+
+    Ok(())
+}
+
+/// Brings back the selected items as they were in the snapshot, the rest of
+/// the library is left untouched.
+pub fn restore_items(
+    storage: &mut Storage,
+    snapshot: &Storage,
+    items: ItemIds,
+) -> AppResult<RestoreReport> {
+    let mut report = RestoreReport::default();
+
+    match items {
+        ItemIds::Asset(assets) => {
+            for asset in assets {
+                restore_asset(storage, snapshot, asset, &mut report)?;
+            }
+        }
+        ItemIds::Collection(collections) => {
+            for collection in collections {
+                if !snapshot.collections.contains_key(&collection) {
+                    return Err(AppError::CollectionNotFound(collection));
+                }
+                report.restored += restore_collection(storage, snapshot, collection);
+            }
+        }
+        ItemIds::Tag(tags) => {
+            for tag in tags {
+                if !snapshot.tags.contains_key(&tag) {
+                    return Err(AppError::TagNotFound(tag));
+                }
+                report.restored += restore_tag(storage, snapshot, tag, true);
+            }
+        }
+    }
+
+    storage.update_link_status();
+    Ok(report)
+}
+
+/// Replaces the whole library with the snapshot. Assets whose file is gone
+/// are left out, the ones imported since are recycled.
+pub fn restore_all(storage: &mut Storage, snapshot: Storage) -> AppResult<RestoreReport> {
+    let mut report = RestoreReport::default();
+
+    storage.cache.asset_crc.clear();
+    storage.cache.crc_lookup.clear();
+    let newer = std::mem::take(&mut storage.assets)
+        .into_values()
+        .filter(|asset| !snapshot.assets.contains_key(&asset.id))
+        .collect::<Vec<_>>();
+    for (id, asset) in snapshot.assets {
+        match storage.cache.compute_crc(&asset) {
+            Ok(crc) => {
+                if !asset.is_deleted {
+                    storage.cache.add_asset(crc, id);
+                }
+                storage.assets.insert(id, asset);
+                report.restored += 1;
+            }
+            Err(_) => report.missing_files.push(id),
+        }
+    }
+
+    report.restored += snapshot.tags.len() + snapshot.collections.len();
+    storage.sp_collections = snapshot.sp_collections;
+    storage.tags = snapshot.tags;
+    storage.collections = snapshot.collections;
+    storage.recycle_bin = snapshot.recycle_bin;
+    storage
+        .recycle_bin
+        .assets
+        .retain(|a| storage.assets.contains_key(a));
+    storage.lib_meta = snapshot.lib_meta;
+    storage.watch_folders = snapshot.watch_folders;
+    storage.http = snapshot.http;
+    storage.metadata_mapping = snapshot.metadata_mapping;
+
+    for mut asset in newer {
+        // Only the tags which the snapshot has as well stay.
+        let tags = std::mem::take(&mut asset.tags);
+        for tag in tags.grouped.values().chain(&tags.ungrouped) {
+            if let Some(tag) = storage.tags.get(tag) {
+                asset.tags.insert_unchecked(tag);
+            }
+        }
+
+        let id = asset.id;
+        storage.assets.insert(id, asset);
+        storage.move_asset_to_recycle_bin(id)?;
+        report.recycled.push(id);
+    }
+
+    storage.update_link_status();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::tests::{asset, collection, empty_storage, tag};
+
+    #[test]
+    fn diff_lists_what_changed() {
+        let mut before = empty_storage();
+        let root = before.sp_collections.root;
+        let parent = collection(&mut before, "parent", root);
+        let (kept, removed_tag) = (
+            tag(&mut before, "kept", parent),
+            tag(&mut before, "removed", parent),
+        );
+        let retagged = asset(&mut before, &[kept]);
+        let untouched = asset(&mut before, &[kept]);
+        let removed = asset(&mut before, &[]);
+
+        // Read back the way snapshots are.
+        let mut after: Storage =
+            serde_json::from_slice(&serde_json::to_vec(&before).unwrap()).unwrap();
+        let added_tag = tag(&mut after, "added", parent);
+        let added = asset(&mut after, &[]);
+        after.move_asset_to_recycle_bin(removed).unwrap();
+        after.move_tag_to_recycle_bin(removed_tag).unwrap();
+        let (kept_tag, new_tag) = (after.tags[&kept].clone(), after.tags[&added_tag].clone());
+        let asset = after.assets.get_mut(&retagged).unwrap();
+        asset.tags.remove(&kept_tag);
+        asset.tags.insert_unchecked(&new_tag);
+
+        let diff = diff(&before, &after);
+        assert_eq!(diff.added_assets, vec![added]);
+        assert_eq!(diff.removed_assets, vec![removed]);
+        assert_eq!(diff.added_tags, vec![added_tag]);
+        assert_eq!(diff.removed_tags, vec![removed_tag]);
+
+        assert_eq!(diff.retagged_assets.len(), 1);
+        let change = &diff.retagged_assets[0];
+        assert_eq!(change.asset, retagged);
+        assert_eq!(change.added, vec![added_tag]);
+        assert_eq!(change.removed, vec![kept]);
+        assert!(!diff.added_assets.contains(&untouched));
+    }
+
+    #[test]
+    fn diff_of_the_same_library_is_empty() {
+        let mut storage = empty_storage();
+        let root = storage.sp_collections.root;
+        let tag = tag(&mut storage, "tag", root);
+        asset(&mut storage, &[tag]);
+
+        let diff = diff(&storage, &storage);
+        assert!(diff.added_assets.is_empty() && diff.removed_assets.is_empty());
+        assert!(diff.added_tags.is_empty() && diff.removed_tags.is_empty());
+        assert!(diff.retagged_assets.is_empty());
+    }
+}
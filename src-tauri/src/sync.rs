@@ -0,0 +1,837 @@
+use std::{
+    fs::{copy, create_dir_all, read_dir, read_to_string, remove_file, write, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use hashbrown::{HashMap, HashSet};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::app::{
+    AppError, AppResult, Asset, AssetId, AssetLink, Collection, CollectionId, ItemId, LinkStatus,
+    Storage, Tag, TagContainer, TagGroupConflictResolve, TagId, CACHE,
+};
+
+const SYNC_STATE: &str = "sync.json";
+const SYNC_IDENTITY: &str = "library.json";
+const SYNC_OPS: &str = "ops";
+const SYNC_BLOBS: &str = "blobs";
+
+/// Orders concurrent writes to the same field, the lamport clock first and
+/// the replica to break ties, so that every replica picks the same winner.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub struct Stamp {
+    pub lamport: u64,
+    pub replica: Uuid,
+}
+
+/// A last-writer-wins register of the library. Existence registers hold
+/// `null` once the item is deleted for good.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase", tag = "field")]
+pub enum Field {
+    Asset { asset: AssetId },
+    Collection { collection: CollectionId },
+    Tag { tag: TagId },
+    Name { item: ItemId },
+    Deleted { item: ItemId },
+    Src { asset: AssetId },
+    CollectionParent { collection: CollectionId },
+    Color { collection: CollectionId },
+    TagParent { tag: TagId },
+    Group { tag: TagId },
+    AssetTag { asset: AssetId, tag: TagId },
+}
+
+/// A mutation made by one replica, appended to its log in the sync folder.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Operation {
+    /// Position in the log of the replica, starting from 1.
+    pub seq: u64,
+    pub stamp: Stamp,
+    #[serde(flatten)]
+    pub field: Field,
+    pub value: Value,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SyncIdentity {
+    root: CollectionId,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncStatus {
+    pub replica: Uuid,
+    pub dir: PathBuf,
+    pub clock: HashMap<Uuid, u64>,
+    pub pending: usize,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncReport {
+    pub published: usize,
+    pub applied: usize,
+}
+
+fn serialize_registers<S: Serializer>(
+    registers: &HashMap<Field, Operation>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(registers.values())
+}
+
+fn deserialize_registers<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<Field, Operation>, D::Error> {
+    Ok(Vec::<Operation>::deserialize(deserializer)?
+        .into_iter()
+        .map(|op| (op.field, op))
+        .collect())
+}
+
+/// Sync bookkeeping of one copy of a library, kept in `cache/` since it is
+/// local to the copy.
+///
+/// The library is projected onto [`Field`] registers. Saving diffs the
+/// projection against the registers, turning every change into an
+/// [`Operation`]. Syncing appends those to `ops/<replica>.jsonl` in the sync
+/// folder and applies the logs of the other replicas, the newest stamp of
+/// each field winning, so that all copies converge whatever the order.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncState {
+    pub replica: Uuid,
+    pub dir: PathBuf,
+    lamport: u64,
+    /// The last sequence number applied from each replica, ours included.
+    clock: HashMap<Uuid, u64>,
+    #[serde(
+        serialize_with = "serialize_registers",
+        deserialize_with = "deserialize_registers"
+    )]
+    registers: HashMap<Field, Operation>,
+    /// Recorded but not published yet, the sync folder may be offline.
+    pending: Vec<Operation>,
+}
+
+fn to_value(value: impl Serialize) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+/// The library as registers. The root collection is shared by all copies
+/// and left out.
+fn project(storage: &Storage) -> HashMap<Field, Value> {
+    let mut fields = HashMap::default();
+
+    for (id, collection) in &storage.collections {
+        if storage.sp_collections.is_special(*id) {
+            continue;
+        }
+        let item = ItemId::Collection(*id);
+        fields.insert(Field::Collection { collection: *id }, Value::Bool(true));
+        fields.insert(Field::Name { item }, to_value(collection.name.to_string()));
+        fields.insert(Field::Deleted { item }, Value::Bool(collection.is_deleted));
+        fields.insert(
+            Field::CollectionParent { collection: *id },
+            to_value(collection.parent),
+        );
+        fields.insert(Field::Color { collection: *id }, to_value(collection.color));
+    }
+
+    for (id, tag) in &storage.tags {
+        let item = ItemId::Tag(*id);
+        fields.insert(Field::Tag { tag: *id }, Value::Bool(true));
+        fields.insert(Field::Name { item }, to_value(tag.name.to_string()));
+        fields.insert(Field::Deleted { item }, Value::Bool(tag.is_deleted));
+        fields.insert(Field::TagParent { tag: *id }, to_value(tag.parent));
+        fields.insert(Field::Group { tag: *id }, to_value(tag.group));
+    }
+
+    for (id, asset) in &storage.assets {
+        let item = ItemId::Asset(*id);
+        // Fields synced on their own and the local link are left out.
+        let record = Asset {
+            is_deleted: false,
+            name: "".into(),
+            tags: Default::default(),
+            src: Default::default(),
+            link: None,
+            ..asset.clone()
+        };
+        fields.insert(Field::Asset { asset: *id }, to_value(record));
+        fields.insert(Field::Name { item }, to_value(asset.name.to_string()));
+        fields.insert(Field::Deleted { item }, Value::Bool(asset.is_deleted));
+        fields.insert(Field::Src { asset: *id }, to_value(&asset.src));
+        for tag in asset.tags.grouped.values().chain(&asset.tags.ungrouped) {
+            fields.insert(
+                Field::AssetTag {
+                    asset: *id,
+                    tag: *tag,
+                },
+                Value::Bool(true),
+            );
+        }
+    }
+
+    fields
+}
+
+/// Whether `ancestor` is `node` or one of its parents.
+fn is_within(storage: &Storage, node: CollectionId, ancestor: CollectionId) -> bool {
+    let mut current = Some(node);
+    for _ in 0..=storage.collections.len() {
+        match current {
+            Some(id) if id == ancestor => return true,
+            Some(id) => current = storage.collections.get(&id).and_then(|c| c.parent),
+            None => return false,
+        }
+    }
+    false
+}
+
+impl SyncState {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            replica: Uuid::new_v4(),
+            dir,
+            lamport: 0,
+            clock: Default::default(),
+            registers: Default::default(),
+            pending: Default::default(),
+        }
+    }
+
+    pub fn load(root: &Path) -> Option<Self> {
+        let state = read_to_string(root.join(CACHE).join(SYNC_STATE)).ok()?;
+        match serde_json::from_str(&state) {
+            Ok(state) => Some(state),
+            Err(err) => {
+                log::error!("Failed to read sync state: {}", err);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, root: &Path) -> AppResult<()> {
+        Ok(write(
+            root.join(CACHE).join(SYNC_STATE),
+            serde_json::to_vec(self)?,
+        )?)
+    }
+
+    pub fn remove(root: &Path) -> AppResult<()> {
+        let path = root.join(CACHE).join(SYNC_STATE);
+        if path.exists() {
+            remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    pub fn status(&self) -> SyncStatus {
+        SyncStatus {
+            replica: self.replica,
+            dir: self.dir.clone(),
+            clock: self.clock.clone(),
+            pending: self.pending.len(),
+        }
+    }
+
+    fn get<T: DeserializeOwned>(&self, field: Field) -> Option<T> {
+        self.registers
+            .get(&field)
+            .and_then(|op| serde_json::from_value(op.value.clone()).ok())
+    }
+
+    fn exists(&self, field: Field) -> bool {
+        self.registers
+            .get(&field)
+            .is_some_and(|op| !op.value.is_null())
+    }
+
+    /// Turns the changes made since the last call into operations.
+    pub fn record(&mut self, storage: &Storage) {
+        let mut fields = project(storage);
+        for (field, op) in &self.registers {
+            if !op.value.is_null() && !fields.contains_key(field) {
+                fields.insert(*field, Value::Null);
+            }
+        }
+
+        for (field, value) in fields {
+            if self
+                .registers
+                .get(&field)
+                .is_some_and(|op| op.value == value)
+            {
+                continue;
+            }
+
+            self.lamport += 1;
+            let seq = self.clock.entry(self.replica).or_default();
+            *seq += 1;
+            let op = Operation {
+                seq: *seq,
+                stamp: Stamp {
+                    lamport: self.lamport,
+                    replica: self.replica,
+                },
+                field,
+                value,
+            };
+            self.registers.insert(field, op.clone());
+            self.pending.push(op);
+        }
+    }
+
+    fn check_identity(&self, storage: &Storage) -> AppResult<()> {
+        create_dir_all(self.dir.join(SYNC_OPS))?;
+        create_dir_all(self.dir.join(SYNC_BLOBS))?;
+
+        let path = self.dir.join(SYNC_IDENTITY);
+        let root = storage.sp_collections.root;
+        match read_to_string(&path) {
+            Ok(identity) => {
+                if serde_json::from_str::<SyncIdentity>(&identity)?.root != root {
+                    return Err(AppError::SyncLibraryMismatch(self.dir.clone()));
+                }
+            }
+            Err(_) => write(&path, serde_json::to_vec(&SyncIdentity { root })?)?,
+        }
+
+        Ok(())
+    }
+
+    /// Uploads the files of new assets first, so that a published operation
+    /// never points to a missing blob.
+    fn publish(&mut self, storage: &Storage) -> AppResult<usize> {
+        if self.pending.is_empty() {
+            return Ok(0);
+        }
+
+        for op in &self.pending {
+            let Field::Asset { asset } = op.field else {
+                continue;
+            };
+            let Some(asset) = storage.assets.get(&asset) else {
+                continue;
+            };
+
+            let blob = self
+                .dir
+                .join(SYNC_BLOBS)
+                .join(asset.get_file_name().as_ref());
+            if !blob.exists() {
+                write(blob, storage.cache.read_asset(asset)?)?;
+            }
+        }
+
+        let mut lines = String::new();
+        for op in &self.pending {
+            lines.push_str(&serde_json::to_string(op)?);
+            lines.push('\n');
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(
+                self.dir
+                    .join(SYNC_OPS)
+                    .join(format!("{}.jsonl", self.replica)),
+            )?
+            .write_all(lines.as_bytes())?;
+
+        let published = self.pending.len();
+        self.pending.clear();
+        Ok(published)
+    }
+
+    /// Reads the logs of the other replicas, returning the operations which
+    /// won their register.
+    fn pull(&mut self) -> AppResult<Vec<Field>> {
+        let mut applied = Vec::new();
+
+        for entry in read_dir(self.dir.join(SYNC_OPS))? {
+            let path = entry?.path();
+            let Some(replica) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| Uuid::parse_str(s).ok())
+            else {
+                continue;
+            };
+            if replica == self.replica {
+                continue;
+            }
+
+            let known = self.clock.get(&replica).copied().unwrap_or_default();
+            for line in read_to_string(&path)?.lines() {
+                let Ok(op) = serde_json::from_str::<Operation>(line) else {
+                    // The other replica may still be writing this line.
+                    break;
+                };
+                if op.seq <= known {
+                    continue;
+                }
+
+                self.lamport = self.lamport.max(op.stamp.lamport);
+                self.clock.insert(replica, op.seq);
+                if self
+                    .registers
+                    .get(&op.field)
+                    .is_none_or(|current| current.stamp < op.stamp)
+                {
+                    applied.push(op.field);
+                    self.registers.insert(op.field, op);
+                }
+            }
+        }
+
+        Ok(applied)
+    }
+
+    /// Brings the items touched by remote operations in line with the
+    /// registers. Collections come first so tags and assets find them.
+    fn materialize(&self, storage: &mut Storage, fields: &[Field]) {
+        let mut collections = HashSet::<CollectionId>::default();
+        let mut tags = HashSet::<TagId>::default();
+        let mut assets = HashSet::<AssetId>::default();
+        for field in fields {
+            match *field {
+                Field::Collection { collection }
+                | Field::CollectionParent { collection }
+                | Field::Color { collection }
+                | Field::Name {
+                    item: ItemId::Collection(collection),
+                }
+                | Field::Deleted {
+                    item: ItemId::Collection(collection),
+                } => {
+                    collections.insert(collection);
+                }
+                Field::Tag { tag }
+                | Field::TagParent { tag }
+                | Field::Group { tag }
+                | Field::Name {
+                    item: ItemId::Tag(tag),
+                }
+                | Field::Deleted {
+                    item: ItemId::Tag(tag),
+                } => {
+                    tags.insert(tag);
+                }
+                Field::Asset { asset }
+                | Field::Src { asset }
+                | Field::AssetTag { asset, .. }
+                | Field::Name {
+                    item: ItemId::Asset(asset),
+                }
+                | Field::Deleted {
+                    item: ItemId::Asset(asset),
+                } => {
+                    assets.insert(asset);
+                }
+            }
+        }
+
+        let root = storage.sp_collections.root;
+        collections.retain(|c| *c != root);
+        for id in &collections {
+            if self.exists(Field::Collection { collection: *id }) {
+                storage
+                    .collections
+                    .entry(*id)
+                    .or_insert_with(|| Collection {
+                        id: *id,
+                        ..Collection::new(Some(root), None, "".into())
+                    });
+            }
+        }
+        for id in collections {
+            self.materialize_collection(storage, id);
+        }
+        for id in tags {
+            self.materialize_tag(storage, id);
+        }
+
+        let mut asset_tags = HashMap::<AssetId, Vec<(Stamp, TagId)>>::default();
+        for op in self.registers.values() {
+            if let Field::AssetTag { asset, tag } = op.field {
+                if op.value == Value::Bool(true) && assets.contains(&asset) {
+                    asset_tags.entry(asset).or_default().push((op.stamp, tag));
+                }
+            }
+        }
+        for id in assets {
+            let tags = asset_tags.remove(&id).unwrap_or_default();
+            if let Err(err) = self.materialize_asset(storage, id, tags) {
+                log::error!("Failed to sync asset {:?}: {}", id, err);
+            }
+        }
+    }
+
+    fn materialize_collection(&self, storage: &mut Storage, id: CollectionId) {
+        if !self.exists(Field::Collection { collection: id }) {
+            if storage.collections.contains_key(&id) {
+                let _ = storage.delete_collection(id);
+            }
+            storage.recycle_bin.collections.remove(&id);
+            return;
+        }
+
+        let item = ItemId::Collection(id);
+        let root = storage.sp_collections.root;
+        let deleted = self
+            .get::<bool>(Field::Deleted { item })
+            .unwrap_or_default();
+        let color = self.get(Field::Color { collection: id });
+        let old_parent = storage.collections[&id].parent;
+        // Concurrent moves may form a cycle, the later one is not applied.
+        let parent = self
+            .get::<CollectionId>(Field::CollectionParent { collection: id })
+            .filter(|p| storage.collections.contains_key(p) && !is_within(storage, *p, id))
+            .or(old_parent)
+            .unwrap_or(root);
+
+        if let Some(old_parent) = old_parent.and_then(|p| storage.collections.get_mut(&p)) {
+            old_parent.children.remove(&id);
+        }
+        if !deleted {
+            if let Some(parent) = storage.collections.get_mut(&parent) {
+                parent.children.insert(id);
+            }
+        }
+
+        let collection = storage.collections.get_mut(&id).unwrap();
+        if let Some(name) = self.get::<String>(Field::Name { item }) {
+            collection.name = name.into();
+        }
+        collection.parent = Some(parent);
+        collection.color = color;
+        collection.is_deleted = deleted;
+
+        for tag in storage.tags.values_mut() {
+            if tag.group == Some(id) {
+                tag.color = color;
+            }
+        }
+        if deleted {
+            storage.recycle_bin.collections.insert(id);
+        } else {
+            storage.recycle_bin.collections.remove(&id);
+        }
+    }
+
+    fn materialize_tag(&self, storage: &mut Storage, id: TagId) {
+        if !self.exists(Field::Tag { tag: id }) {
+            if storage.tags.contains_key(&id) {
+                let _ = storage.delete_tag(id);
+            }
+            storage.recycle_bin.tags.remove(&id);
+            return;
+        }
+
+        let item = ItemId::Tag(id);
+        let root = storage.sp_collections.root;
+        let parent = self
+            .get::<CollectionId>(Field::TagParent { tag: id })
+            .filter(|p| storage.collections.contains_key(p))
+            .unwrap_or(root);
+        let deleted = self
+            .get::<bool>(Field::Deleted { item })
+            .unwrap_or_default();
+
+        let tag = storage.tags.entry(id).or_insert_with(|| Tag {
+            id,
+            ..Tag::new("".into(), parent)
+        });
+        let old_parent = tag.parent;
+        tag.parent = parent;
+        tag.is_deleted = deleted;
+        if let Some(name) = self.get::<String>(Field::Name { item }) {
+            tag.name = name.into();
+        }
+        let old_group = tag.group;
+
+        if let Some(old_parent) = storage.collections.get_mut(&old_parent) {
+            old_parent.content.remove(&id);
+        }
+        if !deleted {
+            if let Some(parent) = storage.collections.get_mut(&parent) {
+                parent.content.insert(id);
+            }
+        }
+
+        let group = self
+            .get::<CollectionId>(Field::Group { tag: id })
+            .filter(|g| storage.collections.contains_key(g));
+        if group != old_group {
+            let _ = storage.regroup_tag(id, group, TagGroupConflictResolve::Override);
+        }
+
+        if deleted {
+            storage.recycle_bin.tags.insert(id);
+        } else {
+            storage.recycle_bin.tags.remove(&id);
+        }
+    }
+
+    fn materialize_asset(
+        &self,
+        storage: &mut Storage,
+        id: AssetId,
+        mut tags: Vec<(Stamp, TagId)>,
+    ) -> AppResult<()> {
+        let Some(record) = self.get::<Asset>(Field::Asset { asset: id }) else {
+            if storage.assets.contains_key(&id) {
+                storage.delete_asset(id)?;
+            }
+            storage.recycle_bin.assets.remove(&id);
+            return Ok(());
+        };
+
+        let item = ItemId::Asset(id);
+        let root = storage.cache.root.clone();
+        let was_deleted = match storage.assets.get(&id) {
+            Some(asset) => asset.is_deleted,
+            None => {
+                let blob = self
+                    .dir
+                    .join(SYNC_BLOBS)
+                    .join(record.get_file_name().as_ref());
+                let target = record.get_storage_path(&root);
+                let link = match copy(&blob, &target) {
+                    Ok(_) => {
+                        storage.cache.seal_file(&target)?;
+                        None
+                    }
+                    Err(err) => {
+                        log::warn!("Missing blob {:?}: {}", blob, err);
+                        Some(AssetLink {
+                            path: blob,
+                            crc: 0,
+                            status: LinkStatus::Missing,
+                        })
+                    }
+                };
+                let asset = Asset {
+                    link,
+                    ..record.clone()
+                };
+                if asset.link.is_none() {
+                    storage
+                        .cache
+                        .add_asset(storage.cache.compute_crc(&asset)?, id);
+                }
+                storage.assets.insert(id, asset);
+                false
+            }
+        };
+
+        let asset = storage.assets.get_mut(&id).unwrap();
+        let name = self
+            .get::<String>(Field::Name { item })
+            .map(Into::into)
+            .unwrap_or_else(|| asset.name.clone());
+        let src = self
+            .get(Field::Src { asset: id })
+            .unwrap_or_else(|| asset.src.clone());
+        *asset = Asset {
+            is_deleted: asset.is_deleted,
+            name,
+            src,
+            tags: TagContainer::default(),
+            link: asset.link.take(),
+            ..record
+        };
+
+        // The newest tag wins a group.
+        tags.sort_by_key(|(stamp, _)| *stamp);
+        for (_, tag) in tags {
+            if let Some(tag) = storage.tags.get(&tag) {
+                asset.tags.insert(tag, TagGroupConflictResolve::Override);
+            }
+        }
+
+        let deleted = self
+            .get::<bool>(Field::Deleted { item })
+            .unwrap_or_default();
+        if deleted && !was_deleted {
+            storage.move_asset_to_recycle_bin(id)?;
+        } else if !deleted && was_deleted {
+            storage.recover_assets(vec![id])?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Records the pending changes of a library with sync enabled.
+pub fn record(storage: &mut Storage) -> AppResult<()> {
+    let Some(mut state) = storage.cache.sync.take() else {
+        return Ok(());
+    };
+
+    state.record(storage);
+    let result = state.save(&storage.cache.root);
+    storage.cache.sync = Some(state);
+    result
+}
+
+/// Publishes the local operations, then merges the ones of the other
+/// replicas into the library.
+pub fn sync(storage: &mut Storage) -> AppResult<SyncReport> {
+    // Blobs are shared decrypted.
+    if storage.cache.cipher.is_some() {
+        return Err(AppError::EncryptedLibrarySync);
+    }
+    let Some(mut state) = storage.cache.sync.take() else {
+        return Err(AppError::SyncNotEnabled);
+    };
+
+    let result = (|| {
+        state.record(storage);
+        state.check_identity(storage)?;
+        let published = state.publish(storage)?;
+        let applied = state.pull()?;
+        state.materialize(storage, &applied);
+        state.save(&storage.cache.root)?;
+
+        Ok(SyncReport {
+            published,
+            applied: applied.len(),
+        })
+    })();
+
+    storage.cache.sync = Some(state);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::remove_dir_all;
+
+    use super::*;
+
+    fn sync_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("snowflake-sync-{}", Uuid::new_v4()));
+        create_dir_all(dir.join(SYNC_OPS)).unwrap();
+        dir
+    }
+
+    fn name(tag: TagId) -> Field {
+        Field::Name {
+            item: ItemId::Tag(tag),
+        }
+    }
+
+    fn op(replica: Uuid, seq: u64, lamport: u64, field: Field, value: &str) -> Operation {
+        Operation {
+            seq,
+            stamp: Stamp { lamport, replica },
+            field,
+            value: Value::from(value),
+        }
+    }
+
+    fn write_log(dir: &Path, replica: Uuid, ops: &[Operation]) {
+        let lines = ops
+            .iter()
+            .map(|op| serde_json::to_string(op).unwrap() + "\n")
+            .collect::<String>();
+        write(dir.join(SYNC_OPS).join(format!("{}.jsonl", replica)), lines).unwrap();
+    }
+
+    fn value(state: &SyncState, field: Field) -> Option<String> {
+        state.get(field)
+    }
+
+    #[test]
+    fn newest_stamp_wins() {
+        let dir = sync_dir();
+        let (old, new) = (TagId(Uuid::new_v4()), TagId(Uuid::new_v4()));
+        let mut state = SyncState::new(dir.clone());
+        state.lamport = 5;
+        for tag in [old, new] {
+            let op = op(state.replica, 1, 5, name(tag), "local");
+            state.registers.insert(op.field, op);
+        }
+
+        let remote = Uuid::new_v4();
+        write_log(
+            &dir,
+            remote,
+            &[
+                op(remote, 1, 3, name(old), "stale"),
+                op(remote, 2, 7, name(new), "remote"),
+            ],
+        );
+
+        assert_eq!(state.pull().unwrap(), vec![name(new)]);
+        assert_eq!(value(&state, name(old)).as_deref(), Some("local"));
+        assert_eq!(value(&state, name(new)).as_deref(), Some("remote"));
+        assert_eq!(state.lamport, 7);
+        assert_eq!(state.clock.get(&remote), Some(&2));
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn replicas_converge_on_ties() {
+        let dir = sync_dir();
+        let tag = TagId(Uuid::new_v4());
+        let mut a = SyncState::new(dir.clone());
+        let mut b = SyncState::new(dir.clone());
+        for (state, written) in [(&mut a, "a"), (&mut b, "b")] {
+            let op = op(state.replica, 1, 1, name(tag), written);
+            write_log(&dir, state.replica, std::slice::from_ref(&op));
+            state.registers.insert(op.field, op);
+        }
+
+        a.pull().unwrap();
+        b.pull().unwrap();
+        let winner = if a.replica > b.replica { "a" } else { "b" };
+        assert_eq!(value(&a, name(tag)).as_deref(), Some(winner));
+        assert_eq!(value(&b, name(tag)).as_deref(), Some(winner));
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn operations_are_applied_once() {
+        let dir = sync_dir();
+        let tag = TagId(Uuid::new_v4());
+        let mut state = SyncState::new(dir.clone());
+
+        let remote = Uuid::new_v4();
+        let first = op(remote, 1, 1, name(tag), "first");
+        let second = op(remote, 2, 2, name(tag), "second");
+        write_log(&dir, remote, std::slice::from_ref(&first));
+        // A line the other replica is still writing.
+        let log = dir.join(SYNC_OPS).join(format!("{}.jsonl", remote));
+        let line = serde_json::to_string(&second).unwrap();
+        OpenOptions::new()
+            .append(true)
+            .open(&log)
+            .unwrap()
+            .write_all(&line.as_bytes()[..line.len() / 2])
+            .unwrap();
+
+        assert_eq!(state.pull().unwrap(), vec![name(tag)]);
+        assert_eq!(value(&state, name(tag)).as_deref(), Some("first"));
+        assert!(state.pull().unwrap().is_empty());
+
+        write_log(&dir, remote, &[first, second]);
+        assert_eq!(state.pull().unwrap(), vec![name(tag)]);
+        assert_eq!(value(&state, name(tag)).as_deref(), Some("second"));
+        assert_eq!(state.clock.get(&remote), Some(&2));
+
+        remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,280 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{channel, Receiver, RecvTimeoutError},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use hashbrown::HashMap;
+use notify::{
+    event::{ModifyKind, RenameMode},
+    Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::{
+    app::{AppError, AppResult, Storage, WatchFolder, WatchedImport},
+    event::{
+        LibraryModifiedEvent, WatchFolderImportEvent, LIBRARY_MODIFIED_EXTERNALLY,
+        WATCH_FOLDER_IMPORT,
+    },
+    library::Libraries,
+};
+
+const DEBOUNCE: Duration = Duration::from_millis(1000);
+/// Files still being written or locked by another program fail to import.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Watchers of each loaded library, keyed by the library root.
+#[derive(Default)]
+pub struct FolderWatcher {
+    watchers: HashMap<PathBuf, RecommendedWatcher>,
+}
+
+impl FolderWatcher {
+    pub fn refresh(
+        &mut self,
+        app: &AppHandle,
+        library: &Path,
+        folders: &[WatchFolder],
+    ) -> AppResult<()> {
+        self.stop(library);
+
+        if folders.is_empty() {
+            return Ok(());
+        }
+
+        let (tx, rx) = channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+        for folder in folders {
+            if let Err(err) = watcher.watch(&folder.path, RecursiveMode::Recursive) {
+                log::error!("Failed to watch folder {:?}: {}", folder.path, err);
+            }
+        }
+
+        let app = app.clone();
+        let root = library.to_path_buf();
+        std::thread::spawn(move || process_events(app, root, rx));
+        self.watchers.insert(library.to_path_buf(), watcher);
+
+        Ok(())
+    }
+
+    pub fn stop(&mut self, library: &Path) {
+        self.watchers.remove(library);
+    }
+}
+
+fn process_events(app: AppHandle, library: PathBuf, rx: Receiver<notify::Result<Event>>) {
+    // Files dropped into the folder are usually written in several steps,
+    // so wait until a path stays quiet before importing it.
+    let mut pending = HashMap::<PathBuf, Instant>::default();
+    let mut attempts = HashMap::<PathBuf, u32>::default();
+    let mut renamed = Vec::<(PathBuf, PathBuf)>::new();
+    // Some platforms report both halves of a rename as separate events.
+    let mut rename_from = None::<PathBuf>;
+
+    loop {
+        match rx.recv_timeout(DEBOUNCE) {
+            Ok(Ok(event)) => match event.kind {
+                EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+                    let mut paths = event.paths.into_iter();
+                    let (from, to) = (paths.next().unwrap(), paths.next().unwrap());
+                    follow_rename(&mut pending, &mut renamed, from, to);
+                }
+                EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                    for path in event.paths {
+                        if let Some(from) = rename_from.replace(path) {
+                            pending.insert(from, Instant::now());
+                        }
+                    }
+                }
+                EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                    for path in event.paths {
+                        match rename_from.take() {
+                            Some(from) => follow_rename(&mut pending, &mut renamed, from, path),
+                            None => {
+                                pending.insert(path, Instant::now());
+                            }
+                        }
+                    }
+                }
+                EventKind::Create(_)
+                | EventKind::Remove(_)
+                | EventKind::Modify(ModifyKind::Name(_)) => {
+                    for path in event.paths {
+                        pending.insert(path, Instant::now());
+                    }
+                }
+                EventKind::Modify(_) => {
+                    for path in event.paths {
+                        if let Some(last) = pending.get_mut(&path) {
+                            *last = Instant::now();
+                        }
+                    }
+                }
+                _ => {}
+            },
+            Ok(Err(err)) => log::error!("Watcher error: {}", err),
+            Err(RecvTimeoutError::Timeout) => {
+                // Moved out of the watched folders.
+                if let Some(from) = rename_from.take() {
+                    pending.insert(from, Instant::now());
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        if !renamed.is_empty() {
+            for path in rename_paths(&app, &library, std::mem::take(&mut renamed)) {
+                pending.insert(path, Instant::now());
+            }
+        }
+
+        let ready = pending
+            .extract_if(|_, last| last.elapsed() >= DEBOUNCE)
+            .map(|(path, _)| path)
+            .collect::<Vec<_>>();
+        if ready.is_empty() {
+            continue;
+        }
+
+        // Folders moved in as a whole may also report their content.
+        let ready = ready
+            .iter()
+            .filter(|path| {
+                !ready
+                    .iter()
+                    .any(|other| other != *path && path.starts_with(other))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let failed = import_paths(&app, &library, ready.clone());
+        for path in ready {
+            if !failed.iter().any(|f| f.starts_with(&path)) {
+                attempts.remove(&path);
+            }
+        }
+        for path in failed {
+            let attempt = attempts.entry(path.clone()).or_default();
+            *attempt += 1;
+            if *attempt > MAX_ATTEMPTS {
+                log::error!("Giving up importing watched path {:?}", path);
+                attempts.remove(&path);
+                continue;
+            }
+            // Retried once the path has been quiet for a while longer each time.
+            pending.insert(path, Instant::now() + DEBOUNCE * *attempt);
+        }
+    }
+}
+
+/// Paths which weren't imported yet are simply imported under the new name.
+fn follow_rename(
+    pending: &mut HashMap<PathBuf, Instant>,
+    renamed: &mut Vec<(PathBuf, PathBuf)>,
+    from: PathBuf,
+    to: PathBuf,
+) {
+    if pending.remove(&from).is_some() {
+        pending.insert(to, Instant::now());
+    } else {
+        renamed.push((from, to));
+    }
+}
+
+/// Returns the paths of renames the library doesn't follow, to be handled
+/// as a removal and an addition.
+fn rename_paths(app: &AppHandle, library: &Path, renamed: Vec<(PathBuf, PathBuf)>) -> Vec<PathBuf> {
+    let libraries = app.state::<Mutex<Libraries>>();
+    let Ok(mut libraries) = libraries.lock() else {
+        return Vec::new();
+    };
+    let Some(storage) = libraries.get_mut(Some(library)) else {
+        return Vec::new();
+    };
+
+    let mut unhandled = Vec::new();
+    let mut followed = false;
+    for (from, to) in renamed {
+        if storage.rename_watched_path(&from, &to) {
+            log::info!("Followed watched path {:?} renamed to {:?}", from, to);
+            followed = true;
+        } else {
+            unhandled.push(from);
+            unhandled.push(to);
+        }
+    }
+
+    if followed {
+        if let Err(err) = save_in_background(app, storage) {
+            log::error!("Failed to save library: {}", err);
+        }
+    }
+
+    unhandled
+}
+
+/// Returns the paths which failed to import.
+fn import_paths(app: &AppHandle, library: &Path, paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    log::info!("Importing watched paths {:?} into {:?}", paths, library);
+
+    let libraries = app.state::<Mutex<Libraries>>();
+    let Ok(mut libraries) = libraries.lock() else {
+        return paths;
+    };
+    // Unloaded meanwhile, its watcher is gone as well.
+    let Some(storage) = libraries.get_mut(Some(library)) else {
+        return Vec::new();
+    };
+
+    let WatchedImport {
+        assets,
+        duplication,
+        failed,
+    } = match storage.handle_watched_paths(paths.clone()) {
+        Ok(result) => result,
+        Err(err) => {
+            log::error!("Failed to import watched paths: {}", err);
+            return paths;
+        }
+    };
+
+    if assets.is_empty() {
+        return failed;
+    }
+
+    if let Err(err) = save_in_background(app, storage) {
+        log::error!("Failed to save library: {}", err);
+    }
+
+    let _ = app.emit(
+        WATCH_FOLDER_IMPORT,
+        WatchFolderImportEvent {
+            library: library.to_path_buf(),
+            assets,
+            duplication: duplication.reduce(),
+        },
+    );
+
+    failed
+}
+
+/// Saves changes nobody is waiting on. If another session wrote the library
+/// meanwhile the frontend is told, so it can prompt for a merge or reload as
+/// it does for commands.
+pub fn save_in_background(app: &AppHandle, storage: &mut Storage) -> AppResult<()> {
+    let result = storage.save();
+    if let Err(AppError::LibraryModifiedExternally(library)) = &result {
+        let _ = app.emit(
+            LIBRARY_MODIFIED_EXTERNALLY,
+            LibraryModifiedEvent {
+                library: library.clone(),
+            },
+        );
+    }
+    result
+}
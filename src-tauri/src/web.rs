@@ -0,0 +1,118 @@
+use file_format::FileFormat;
+use hashbrown::HashSet;
+use reqwest::Url;
+use scraper::{Html, Selector};
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum WebMediaSource {
+    OpenGraph,
+    Image,
+    Link,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WebMediaCandidate {
+    pub url: String,
+    pub source: WebMediaSource,
+}
+
+pub fn is_html(content_type: Option<&str>, content: &[u8]) -> bool {
+    content_type
+        .is_some_and(|ty| ty.starts_with("text/html") || ty.starts_with("application/xhtml"))
+        || FileFormat::from_bytes(content).media_type() == "text/html"
+}
+
+fn largest_in_srcset(srcset: &str) -> Option<&str> {
+    srcset
+        .split(',')
+        .filter_map(|candidate| {
+            let mut parts = candidate.split_whitespace();
+            let url = parts.next()?;
+            let size = parts
+                .next()
+                .and_then(|desc| desc.trim_end_matches(['w', 'x']).parse::<f32>().ok())
+                .unwrap_or(1.0);
+            Some((url, size))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(url, _)| url)
+}
+
+fn push_candidate(
+    page: &Url,
+    url: &str,
+    source: WebMediaSource,
+    seen: &mut HashSet<String>,
+    candidates: &mut Vec<WebMediaCandidate>,
+) {
+    let Ok(url) = page.join(url.trim()) else {
+        return;
+    };
+
+    if !matches!(url.scheme(), "http" | "https") || !seen.insert(url.to_string()) {
+        return;
+    }
+
+    candidates.push(WebMediaCandidate {
+        url: url.to_string(),
+        source,
+    });
+}
+
+pub fn scrape_media(page: &Url, html: &str) -> Vec<WebMediaCandidate> {
+    let document = Html::parse_document(html);
+    let mut seen = HashSet::default();
+    let mut candidates = Vec::new();
+
+    let og = Selector::parse(
+        "meta[property='og:image'], meta[property='og:image:url'], \
+         meta[property='og:image:secure_url'], meta[name='twitter:image']",
+    )
+    .unwrap();
+    for element in document.select(&og) {
+        if let Some(url) = element.value().attr("content") {
+            push_candidate(
+                page,
+                url,
+                WebMediaSource::OpenGraph,
+                &mut seen,
+                &mut candidates,
+            );
+        }
+    }
+
+    let img = Selector::parse("img, picture source").unwrap();
+    for element in document.select(&img) {
+        let element = element.value();
+        let url = element
+            .attr("srcset")
+            .or_else(|| element.attr("data-srcset"))
+            .and_then(largest_in_srcset)
+            .or_else(|| element.attr("data-src"))
+            .or_else(|| element.attr("src"));
+
+        if let Some(url) = url {
+            push_candidate(page, url, WebMediaSource::Image, &mut seen, &mut candidates);
+        }
+    }
+
+    let link = Selector::parse("a[href], link[href]").unwrap();
+    for element in document.select(&link) {
+        let Some(href) = element.value().attr("href") else {
+            continue;
+        };
+        let is_media = page.join(href).is_ok_and(|url| {
+            let path = url.path().to_lowercase();
+            path.ends_with(".glb") || path.ends_with(".svg")
+        });
+
+        if is_media {
+            push_candidate(page, href, WebMediaSource::Link, &mut seen, &mut candidates);
+        }
+    }
+
+    candidates
+}
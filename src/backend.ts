@@ -12,7 +12,14 @@ export type DownloadEvent = {
     id: number,
     downloaded: number,
     total: number | undefined,
-    status: string | { Error: string },
+    status: string | { Retrying: number } | { Scraped: WebMediaCandidate[] } | { Error: string },
+}
+
+export type WebMediaSource = "openGraph" | "image" | "link"
+
+export type WebMediaCandidate = {
+    url: string,
+    source: WebMediaSource,
 }
 
 export type LibraryMeta = {
@@ -33,6 +40,7 @@ export type Collection = {
 export type AssetProperty = {
     width: number,
     height: number,
+    camera?: string | null,
 } | {
     width: number,
     height: number,
@@ -45,6 +53,14 @@ export type AssetProperty = {
     vertices: number,
 }
 
+export type LinkStatus = "available" | "missing" | "modified"
+
+export type AssetLink = {
+    path: string,
+    crc: number,
+    status: LinkStatus,
+}
+
 export type Asset = {
     parent: string,
     id: string,
@@ -54,6 +70,38 @@ export type Asset = {
     ext: string,
     meta: Metadata,
     src: string,
+    sourcePage: string | null,
+    embeddedMeta: EmbeddedMetadata | null,
+    aigc: AigcDetection | null,
+    tagSuggestions: TagSuggestion[] | null,
+    ocrText: string | null,
+    link: AssetLink | null,
+}
+
+export type TagSuggestion = {
+    tag: string,
+    confidence: number,
+}
+
+export type AigcVerdict = "generated" | "likely" | "notDetected"
+
+export type AigcEvidenceSource = "c2pa" | "digitalSourceType" | "generationParameters" | "software" | "label"
+
+export type AigcDetection = {
+    verdict: AigcVerdict,
+    evidence: { source: AigcEvidenceSource, detail: string }[],
+}
+
+export type EmbeddedMetadata = {
+    fields: { [key: string]: string },
+    keywords: string[],
+    hierarchicalKeywords: string[][],
+    prompt: string | null,
+}
+
+export type MetadataMapping = {
+    keywordCollection: string | null,
+    hierarchicalTags: boolean,
 }
 
 export type Tag = {
@@ -135,15 +183,48 @@ export type CollectionDesc = {
     color: string,
 }
 
+export type ImportMode = "copy" | "link"
+
 export type StorageConstructionSettings = {
     srcRoot: string,
     root: string,
     folderAsTag: boolean,
+    importMode?: ImportMode,
 }
 
+export type ExportScope = {
+    ty: "library",
+} | {
+    ty: "assets",
+    data: string[],
+} | {
+    ty: "tag",
+    data: string,
+} | {
+    ty: "collection",
+    data: string,
+} | {
+    ty: "query",
+    data: { ty: SearchQueryTy, query: string },
+}
+
+export type ExportLinkMode = "copy" | "hardlink" | "symlink"
+
+export type SidecarFormat = "json" | "csv"
+
+export type ExportSettings = {
+    scope?: ExportScope,
+    linkMode?: ExportLinkMode,
+    sidecar?: SidecarFormat,
+    includeDeleted?: boolean,
+    xmp?: XmpMode,
+}
+
+export type XmpMode = "sidecar" | "embedded"
+
 export type WindowTransparency = "none" | "blur" | "acrylic" | "mica" | "tabbed" | "vibrancy"
 
-export type SearchQueryTy = "assetName" | "tagName" | "assetId" | "tagId"
+export type SearchQueryTy = "assetName" | "assetMetadata" | "assetText" | "aigcVerdict" | "tagName" | "assetId" | "tagId"
 
 export type SearchQueryResult = {
     ty: "assets",
@@ -155,6 +236,29 @@ export type SearchQueryResult = {
 
 export type FilterStrategy = "all" | "unremovedOnly" | "removedOnly"
 
+export type FolderSync = {
+    writeBack: boolean,
+    files: { [path: string]: string },
+    written: string[],
+}
+
+export type WatchFolder = {
+    path: string,
+    initialTag: string | null,
+    folderAsTag: boolean,
+    sync: FolderSync | null,
+}
+
+export type WatchFolderImportEvent = {
+    library: string,
+    assets: string[],
+    duplication: DuplicateAssets | null,
+}
+
+export type LibraryModifiedEvent = {
+    library: string,
+}
+
 export function CrashTest(): Promise<void> {
     return invoke("crash_test")
 }
@@ -179,12 +283,12 @@ export function GetUserSetting(params: { category: string, item: string }): Prom
     return invoke("get_user_setting", params)
 }
 
-export function GetLibraryMeta(): Promise<LibraryMeta> {
-    return invoke("get_library_meta")
+export function GetLibraryMeta(params?: { library?: string }): Promise<LibraryMeta> {
+    return invoke("get_library_meta", params)
 }
 
-export function GetLibraryStatistics(): Promise<LibraryStatistics> {
-    return invoke("gen_statistics")
+export function GetLibraryStatistics(params?: { library?: string }): Promise<LibraryStatistics> {
+    return invoke("gen_statistics", params)
 }
 
 export function GetDefaultSettings(): Promise<DefaultSettings> {
@@ -199,7 +303,7 @@ export function SetUserSetting(params: { category: string, item: string, value:
     return invoke("set_user_setting", params)
 }
 
-export function LoadLibrary(params: { rootFolder: string }): Promise<DuplicateAssets | undefined> {
+export function LoadLibrary(params: { rootFolder: string, force?: boolean, password?: string }): Promise<DuplicateAssets | undefined> {
     return invoke("load_library", params)
 }
 
@@ -207,163 +311,499 @@ export function InitializeLibrary(params: { settings: StorageConstructionSetting
     return invoke("initialize_library", params)
 }
 
-export function SaveLibrary(): Promise<void> {
-    return invoke("save_library")
+export type LoadedLibrary = {
+    path: string,
+    name: string,
+    active: boolean,
+}
+
+export type TransferMode = "copy" | "move"
+
+export function GetLoadedLibraries(): Promise<LoadedLibrary[]> {
+    return invoke("get_loaded_libraries")
+}
+
+export function ActivateLibrary(params: { library: string }): Promise<void> {
+    return invoke("activate_library", params)
+}
+
+export function TransferAssets(params: { from: string, to: string, assets: string[], tagMap?: { [key: string]: string }, mode?: TransferMode }): Promise<DuplicateAssets | undefined> {
+    return invoke("transfer_assets", params)
+}
+
+export function TransferTags(params: { from: string, to: string, tags: string[], mode?: TransferMode }): Promise<{ [key: string]: string }> {
+    return invoke("transfer_tags", params)
+}
+
+export function SaveLibrary(params?: { force?: boolean, library?: string }): Promise<void> {
+    return invoke("save_library", params)
+}
+
+export type LibraryLock = {
+    owner: string,
+    host: string,
+    pid: number,
+    acquiredAt: string,
+}
+
+export type ExternalModification = {
+    modified: boolean,
+    holder: LibraryLock | null,
+}
+
+export function CheckExternalModification(params?: { library?: string }): Promise<ExternalModification> {
+    return invoke("check_external_modification", params)
+}
+
+export function ReloadLibrary(params?: { library?: string }): Promise<DuplicateAssets | undefined> {
+    return invoke("reload_library", params)
+}
+
+export function MergeLibrary(params?: { library?: string }): Promise<void> {
+    return invoke("merge_library", params)
+}
+
+export type Snapshot = {
+    id: string,
+    createdAt: string,
+    byteSize: number,
+}
+
+export type RetaggedAsset = {
+    asset: string,
+    added: string[],
+    removed: string[],
+}
+
+export type SnapshotDiff = {
+    addedAssets: string[],
+    removedAssets: string[],
+    retaggedAssets: RetaggedAsset[],
+    addedTags: string[],
+    removedTags: string[],
+}
+
+export type RestoreReport = {
+    restored: number,
+    missingFiles: string[],
+    recycled: string[],
+}
+
+export function ListSnapshots(params?: { library?: string }): Promise<Snapshot[]> {
+    return invoke("list_snapshots", params)
+}
+
+export function CreateSnapshot(params?: { library?: string }): Promise<Snapshot> {
+    return invoke("create_snapshot", params)
+}
+
+export function DiffSnapshots(params: { from: string, to?: string, library?: string }): Promise<SnapshotDiff> {
+    return invoke("diff_snapshots", params)
+}
+
+export function RestoreSnapshot(params: { snapshot: string, items?: ItemIds, library?: string }): Promise<RestoreReport> {
+    return invoke("restore_snapshot", params)
+}
+
+export type SyncStatus = {
+    replica: string,
+    dir: string,
+    clock: { [replica: string]: number },
+    pending: number,
+}
+
+export type SyncReport = {
+    published: number,
+    applied: number,
+}
+
+export function GetSyncStatus(params?: { library?: string }): Promise<SyncStatus | null> {
+    return invoke("get_sync_status", params)
+}
+
+export function EnableSync(params: { dir: string, library?: string }): Promise<SyncReport> {
+    return invoke("enable_sync", params)
+}
+
+export function DisableSync(params?: { library?: string }): Promise<void> {
+    return invoke("disable_sync", params)
+}
+
+export function SyncLibrary(params?: { library?: string }): Promise<SyncReport> {
+    return invoke("sync_library", params)
 }
 
-export function UnloadLibrary(): Promise<void> {
-    return invoke("unload_library")
+export function IsLibraryEncrypted(params: { rootFolder: string }): Promise<boolean> {
+    return invoke("is_library_encrypted", params)
 }
 
-export function ExportLibrary(params: { rootFolder: string }): Promise<void> {
+export function EncryptLibrary(params: { password: string, library?: string }): Promise<void> {
+    return invoke("encrypt_library", params)
+}
+
+export function DecryptLibrary(params: { password: string, library?: string }): Promise<void> {
+    return invoke("decrypt_library", params)
+}
+
+export function RekeyLibrary(params: { oldPassword: string, newPassword: string, rotate?: boolean, library?: string }): Promise<void> {
+    return invoke("rekey_library", params)
+}
+
+export function UnloadLibrary(params?: { library?: string }): Promise<void> {
+    return invoke("unload_library", params)
+}
+
+export function ExportLibrary(params: { rootFolder: string, settings?: ExportSettings, library?: string }): Promise<void> {
     return invoke("export_library", params)
 }
 
+export function ExportArchive(params: { path: string, scope?: ExportScope, library?: string }): Promise<void> {
+    return invoke("export_archive", params)
+}
+
+export function ImportArchive(params: { path: string, initialTag: string | null, library?: string }): Promise<DuplicateAssets | undefined> {
+    return invoke("import_archive", params)
+}
+
+export function ImportEagleLibrary(params: { path: string, initialTag: string | null, library?: string }): Promise<DuplicateAssets | undefined> {
+    return invoke("import_eagle_library", params)
+}
+
+export function ImportPureRefBoard(params: { path: string, initialTag: string | null, library?: string }): Promise<DuplicateAssets | undefined> {
+    return invoke("import_pureref_board", params)
+}
+
+export function InitializeLibraryFromArchive(params: { path: string, rootFolder: string }): Promise<DuplicateAssets | undefined> {
+    return invoke("initialize_library_from_archive", params)
+}
+
 export function GenStatistics(): Promise<LibraryStatistics> {
     return invoke("gen_statistics")
 }
 
-export function RecoverItems(params: { items: ItemId[], parentOverride?: string }): Promise<void> {
+export function RecoverItems(params: { items: ItemId[], parentOverride?: string, library?: string }): Promise<void> {
     return invoke("recover_items", params)
 }
 
-export function GetRecycleBin(params: { ty: IdTy }): Promise<ItemIds> {
+export function GetRecycleBin(params: { ty: IdTy, library?: string }): Promise<ItemIds> {
     return invoke("get_recycle_bin", params)
 }
 
-export function GetDuplicatedAssets(): Promise<DuplicateAssets> {
-    return invoke("get_duplicated_assets")
+export type RecycleBinEntry = {
+    item: ItemId,
+    deletedAt: string | null,
+}
+
+export type RecycleBinInfo = {
+    entries: RecycleBinEntry[],
+    reclaimableBytes: number,
+}
+
+export type RecycleBinPurge = {
+    assets: number,
+    collections: number,
+    tags: number,
+    freedBytes: number,
 }
 
-export function ChangeLibraryName(params: { name: string }): Promise<DuplicateAssets | undefined> {
+export function GetRecycleBinInfo(params?: { library?: string }): Promise<RecycleBinInfo> {
+    return invoke("get_recycle_bin_info", params)
+}
+
+export type RecycleBinNode = {
+    item: ItemId,
+    name: string,
+    deletedAt: string | null,
+    assignments: number,
+    children: RecycleBinNode[],
+}
+
+export function GetRecycleBinTree(params?: { library?: string }): Promise<RecycleBinNode[]> {
+    return invoke("get_recycle_bin_tree", params)
+}
+
+export function EmptyRecycleBin(params?: { olderThanDays?: number, library?: string }): Promise<RecycleBinPurge> {
+    return invoke("empty_recycle_bin", params)
+}
+
+export function GetDuplicatedAssets(params?: { library?: string }): Promise<DuplicateAssets> {
+    return invoke("get_duplicated_assets", params)
+}
+
+export function ChangeLibraryName(params: { name: string, library?: string }): Promise<DuplicateAssets | undefined> {
     return invoke("change_library_name", params)
 }
 
-export function ImportAssets(params: { path: string[], initialTag: string | null }): Promise<DuplicateAssets | undefined> {
+export function ImportAssets(params: { path: string[], initialTag: string | null, mode?: ImportMode, library?: string }): Promise<DuplicateAssets | undefined> {
     return invoke("import_assets", params)
 }
 
-export function ImportMemoryAssets(params: { data: Uint8Array, format: string, initialTag: string | null }): Promise<DuplicateAssets | undefined> {
+export function GetMissingLinkedAssets(params?: { library?: string }): Promise<string[]> {
+    return invoke("get_missing_linked_assets", params)
+}
+
+export function RelinkAssets(params: { searchRoot: string, library?: string }): Promise<string[]> {
+    return invoke("relink_assets", params)
+}
+
+export function ConsolidateAssets(params: { assets: string[], library?: string }): Promise<void> {
+    return invoke("consolidate_assets", params)
+}
+
+export function ImportFromClipboard(params: { initialTag: string | null, progress: Channel<DownloadEvent>, library?: string }): Promise<DuplicateAssets | undefined> {
+    return invoke("import_from_clipboard", params)
+}
+
+export function ImportMemoryAssets(params: { data: Uint8Array, format: string, initialTag: string | null, library?: string }): Promise<DuplicateAssets | undefined> {
     return invoke("import_memory_asset", params)
 }
 
-export function ImportWebAssets(params: { urls: string[], initialTag: string | null, progress: Channel<DownloadEvent> }): Promise<DuplicateAssets | undefined> {
+export function ImportWebAssets(params: { urls: string[], page?: string, initialTag: string | null, progress: Channel<DownloadEvent>, library?: string }): Promise<DuplicateAssets | undefined> {
     return invoke("import_web_assets", params)
 }
 
-export function GetAssetAbsPath(params: { asset: string }): Promise<string> {
+export function CancelDownload(params: { channel: number, id: number }): Promise<void> {
+    return invoke("cancel_download", params)
+}
+
+export type HttpCookie = {
+    includeSubdomains: boolean,
+    path: string,
+    secure: boolean,
+    expires: number | null,
+    name: string,
+    value: string,
+}
+
+export type HttpSettings = {
+    proxy: string | null,
+    userAgent: string | null,
+    headers: { [name: string]: string },
+    cookies: { [domain: string]: HttpCookie[] },
+}
+
+export function GetHttpSettings(params: { perLibrary: boolean, library?: string }): Promise<HttpSettings | null> {
+    return invoke("get_http_settings", params)
+}
+
+export function SetHttpSettings(params: { settings: HttpSettings | null, perLibrary: boolean, library?: string }): Promise<void> {
+    return invoke("set_http_settings", params)
+}
+
+export function ImportCookieFile(params: { path: string, perLibrary: boolean, library?: string }): Promise<number> {
+    return invoke("import_cookie_file", params)
+}
+
+export type BridgeSettings = {
+    enabled: boolean,
+    port: number,
+    token: string,
+}
+
+export function GetBridgeSettings(): Promise<BridgeSettings> {
+    return invoke("get_bridge_settings")
+}
+
+export function SetBridgeSettings(params: { enabled: boolean, port?: number }): Promise<void> {
+    return invoke("set_bridge_settings", params)
+}
+
+export function RegenerateBridgeToken(): Promise<string> {
+    return invoke("regenerate_bridge_token")
+}
+
+export function ScrapeWebPage(params: { url: string, library?: string }): Promise<WebMediaCandidate[]> {
+    return invoke("scrape_web_page", params)
+}
+
+export function GetMetadataMapping(params?: { library?: string }): Promise<MetadataMapping> {
+    return invoke("get_metadata_mapping", params)
+}
+
+export function SetMetadataMapping(params: { mapping: MetadataMapping, library?: string }): Promise<void> {
+    return invoke("set_metadata_mapping", params)
+}
+
+export function RefreshEmbeddedMetadata(params: { assets: string[], library?: string }): Promise<void> {
+    return invoke("refresh_embedded_metadata", params)
+}
+
+export function DetectAigc(params: { assets: string[], library?: string }): Promise<void> {
+    return invoke("detect_aigc", params)
+}
+
+export function SuggestTags(params: { assets: string[] | null, library?: string }): Promise<{ [key: string]: TagSuggestion[] }> {
+    return invoke("suggest_tags", params)
+}
+
+export function AcceptTagSuggestions(params: { asset: string, tags: string[], library?: string }): Promise<void> {
+    return invoke("accept_tag_suggestions", params)
+}
+
+export type SemanticMatch = {
+    asset: string,
+    score: number,
+}
+
+export function SemanticSearch(params: { query: string, limit: number, library?: string }): Promise<SemanticMatch[]> {
+    return invoke("semantic_search", params)
+}
+
+export function FindSimilarAssets(params: { asset: string, limit: number, library?: string }): Promise<SemanticMatch[]> {
+    return invoke("find_similar_assets", params)
+}
+
+export function RecognizeText(params: { assets: string[] | null, library?: string }): Promise<{ [key: string]: string }> {
+    return invoke("recognize_text", params)
+}
+
+export function GetWatchFolders(params?: { library?: string }): Promise<WatchFolder[]> {
+    return invoke("get_watch_folders", params)
+}
+
+export function AddWatchFolder(params: { path: string, initialTag: string | null, folderAsTag: boolean, sync?: { writeBack: boolean }, library?: string }): Promise<DuplicateAssets | undefined> {
+    return invoke("add_watch_folder", params)
+}
+
+export function RemoveWatchFolder(params: { path: string, library?: string }): Promise<void> {
+    return invoke("remove_watch_folder", params)
+}
+
+export function GetAssetAbsPath(params: { asset: string, library?: string }): Promise<string> {
     return invoke("get_asset_abs_path", params)
 }
 
-export function GetTagVirtualPath(params: { tag: string }): Promise<string[]> {
+export type RenditionFormat = "webp" | "png" | "jpeg"
+
+function snowflakeUrl(kind: "asset" | "thumbnail", params: { asset: string, width?: number, format?: RenditionFormat, library?: string }): string {
+    const base = navigator.userAgent.includes("Windows") ? `http://snowflake.localhost/${kind}/` : `snowflake://${kind}/`
+    const query = new URLSearchParams()
+    if (params.width) query.set("w", params.width.toString())
+    if (params.format) query.set("fmt", params.format)
+    if (params.library) query.set("library", params.library)
+    return base + params.asset + (query.size > 0 ? "?" + query.toString() : "")
+}
+
+// Served by the `snowflake` protocol, which decrypts assets of encrypted libraries.
+// Images are resized and transcoded when a width or format is given.
+export function AssetUrl(params: { asset: string, width?: number, format?: RenditionFormat, library?: string }): string {
+    return snowflakeUrl("asset", params)
+}
+
+// The render of models, a small webp of images and the original of anything else.
+export function ThumbnailUrl(params: { asset: string, width?: number, format?: RenditionFormat, library?: string }): string {
+    return snowflakeUrl("thumbnail", params)
+}
+
+export function GetTagVirtualPath(params: { tag: string, library?: string }): Promise<string[]> {
     return invoke("get_tag_virtual_path", params)
 }
 
-export async function GetCollectionTree(params: { noSpecial: boolean }): Promise<Map<string, Collection>> {
+export async function GetCollectionTree(params: { noSpecial: boolean, library?: string }): Promise<Map<string, Collection>> {
     return invoke("get_collection_tree", params)
         .then(map => new Map(Object.entries(map as { [s: string]: Collection; })))
 }
 
-export function GetSpecialCollections(): Promise<SpecialCollections> {
-    return invoke("get_special_collections")
+export function GetSpecialCollections(params?: { library?: string }): Promise<SpecialCollections> {
+    return invoke("get_special_collections", params)
 }
 
-export function GetAllTags(): Promise<Tag[]> {
-    return invoke("get_all_tags")
+export function GetAllTags(params?: { library?: string }): Promise<Tag[]> {
+    return invoke("get_all_tags", params)
 }
 
-export function GetAllAssets(): Promise<string[]> {
-    return invoke("get_all_assets")
+export function GetAllAssets(params?: { library?: string }): Promise<string[]> {
+    return invoke("get_all_assets", params)
 }
 
-export function GetAllUncategorizedAssets(): Promise<string[]> {
-    return invoke("get_all_uncategorized_assets")
+export function GetAllUncategorizedAssets(params?: { library?: string }): Promise<string[]> {
+    return invoke("get_all_uncategorized_assets", params)
 }
 
-export function GetTagsOnAsset(params: { asset: string }): Promise<string[]> {
+export function GetTagsOnAsset(params: { asset: string, library?: string }): Promise<string[]> {
     return invoke("get_tags_on_asset", params)
 }
 
-export function ModifySrcOf(params: { asset: string, src: string }): Promise<void> {
+export function ModifySrcOf(params: { asset: string, src: string, library?: string }): Promise<void> {
     return invoke("modify_src_of", params)
 }
 
-export function GetTagsWithoutConflict(params: { tags: string[] }): Promise<Tag[]> {
+export function GetTagsWithoutConflict(params: { tags: string[], library?: string }): Promise<Tag[]> {
     return invoke("get_tags_without_conflict", params)
 }
 
-export function GetItem(params: { item: ItemId }): Promise<Item> {
+export function GetItem(params: { item: ItemId, library?: string }): Promise<Item> {
     return invoke("get_item", params)
 }
 
-export function GetItems(params: { items: ItemId[], filter: FilterStrategy }): Promise<Item[]> {
+export function GetItems(params: { items: ItemId[], filter: FilterStrategy, library?: string }): Promise<Item[]> {
     return invoke("get_items", params)
 }
 
-export function AddTagToAssets(params: { assets: string[], tag: string }): Promise<void> {
+export function AddTagToAssets(params: { assets: string[], tag: string, library?: string }): Promise<void> {
     return invoke("add_tag_to_assets", params)
 }
 
-export function RemoveTagFromAssets(params: { assets: string[], tag: string }): Promise<void> {
+export function RemoveTagFromAssets(params: { assets: string[], tag: string, library?: string }): Promise<void> {
     return invoke("remove_tag_from_assets", params)
 }
 
-export function GetAssetsContainingTag(params: { tag: string }): Promise<string[]> {
+export function GetAssetsContainingTag(params: { tag: string, library?: string }): Promise<string[]> {
     return invoke("get_assets_containing_tag", params)
 }
 
-export function DeleteItems(params: { items: ItemId[], permanently: boolean }): Promise<void> {
+export function DeleteItems(params: { items: ItemId[], permanently: boolean, library?: string }): Promise<void> {
     return invoke("delete_items", params)
 }
 
-export function CreateTags(params: { tagNames: string[], parent: string }): Promise<void> {
+export function CreateTags(params: { tagNames: string[], parent: string, library?: string }): Promise<void> {
     return invoke("create_tags", params)
 }
 
-export function CreateCollections(params: { collectionNames: string[], parent: string }): Promise<void> {
+export function CreateCollections(params: { collectionNames: string[], parent: string, library?: string }): Promise<void> {
     return invoke("create_collections", params)
 }
 
-export function RenameItem(params: { item: ItemId, name: string }): Promise<void> {
+export function RenameItem(params: { item: ItemId, name: string, library?: string }): Promise<void> {
     return invoke("rename_item", params)
 }
 
-export function RecolorCollection(params: { collection: string, color: string | null }): Promise<void> {
+export function RecolorCollection(params: { collection: string, color: string | null, library?: string }): Promise<void> {
     return invoke("recolor_collection", params)
 }
 
-export function MoveCollectionsTo(params: { srcCollections: string[], dstCollection: string }): Promise<void> {
+export function MoveCollectionsTo(params: { srcCollections: string[], dstCollection: string, library?: string }): Promise<void> {
     return invoke("move_collections_to", params)
 }
 
-export function MoveTagsTo(params: { srcTags: string[], dstCollection: string }): Promise<void> {
+export function MoveTagsTo(params: { srcTags: string[], dstCollection: string, library?: string }): Promise<void> {
     return invoke("move_tags_to", params)
 }
 
-export function RegroupTag(params: { tag: string, group: string | null }): Promise<void> {
+export function RegroupTag(params: { tag: string, group: string | null, library?: string }): Promise<void> {
     return invoke("regroup_tag", params)
 }
 
-export function OpenWithDefaultApp(params: { asset: string }): Promise<void> {
+export function OpenWithDefaultApp(params: { asset: string, library?: string }): Promise<void> {
     return invoke("open_with_default_app", params)
 }
 
-export function GlobalSearch(params: { ty: SearchQueryTy, query: string }): Promise<SearchQueryResult> {
+export function GlobalSearch(params: { ty: SearchQueryTy, query: string, library?: string }): Promise<SearchQueryResult> {
     return invoke("global_search", params)
 }
 
-export function QuickRef(params: { ty: QuickRefSrcTy }): Promise<void> {
+export function QuickRef(params: { ty: QuickRefSrcTy, library?: string }): Promise<void> {
     return invoke("quick_ref", params)
 }
 
-export function ComputeCameraPos(params: { yFov: number, aspectRatio: number, asset: string }): Promise<[number, number, number]> {
+export function ComputeCameraPos(params: { yFov: number, aspectRatio: number, asset: string, library?: string }): Promise<[number, number, number]> {
     return invoke("compute_camera_pos", params)
 }
 
-export function SaveRenderCache(params: { asset: string, base64Data: string, camera: GltfPreviewCamera }): Promise<string | undefined> {
+export function SaveRenderCache(params: { asset: string, base64Data: string, camera: GltfPreviewCamera, library?: string }): Promise<string | undefined> {
     return invoke("save_render_cache", params)
 }
 
-export function GetRenderCache(params: { asset: string }): Promise<GltfPreviewCache | undefined> {
+export function GetRenderCache(params: { asset: string, library?: string }): Promise<GltfPreviewCache | undefined> {
     return invoke("get_render_cache", params)
 }
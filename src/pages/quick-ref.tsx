@@ -1,8 +1,7 @@
-import { convertFileSrc } from "@tauri-apps/api/core"
 import { getCurrentWindow, PhysicalSize } from "@tauri-apps/api/window"
 import { MouseEvent, useEffect, useState, WheelEvent } from "react"
 import { useParams } from "react-router-dom"
-import { Asset, GetAssetAbsPath, GetItem } from "../backend"
+import { Asset, AssetUrl, GetItem } from "../backend"
 import { useContextMenu } from "react-contexify"
 import QuickRefContextMenu, { QuickRefCtxMenuId } from "../widgets/context-menus/quick-ref-context-menu"
 import GraphicsReference from "../widgets/quick-ref/graphics-reference"
@@ -15,7 +14,6 @@ const appWindow = getCurrentWindow()
 
 export default function QuickRef() {
     const { id } = useParams()
-    const [absPath, setAbsPath] = useState<string | undefined>()
     const { show: showContextMenu } = useContextMenu({ id: QuickRefCtxMenuId })
     const [scaleFactor, setScaleFactor] = useState(1)
     const [asset, setAsset] = useState<Asset>()
@@ -24,12 +22,6 @@ export default function QuickRef() {
     useEffect(() => {
         async function fetch() {
             if (id) {
-                const path = await GetAssetAbsPath({ asset: id })
-                    .catch(err => dispatchToast(<ErrToast body={err} />, { intent: "error" }))
-                if (path) {
-                    setAbsPath(path)
-                }
-
                 const asset = await GetItem({ item: { id, ty: "asset" } })
                     .catch(err => dispatchToast(<ErrToast body={err} />, { intent: "error" }))
                 if (asset) {
@@ -54,7 +46,7 @@ export default function QuickRef() {
         }
     }, [])
 
-    if (!absPath || !asset) {
+    if (!asset) {
         return <></>
     }
 
@@ -83,7 +75,7 @@ export default function QuickRef() {
     }
 
     function getReference() {
-        if (!asset || !absPath) { return }
+        if (!asset) { return }
 
         switch (asset.ty) {
             case "rasterGraphics":
@@ -91,14 +83,14 @@ export default function QuickRef() {
                 return (
                     <GraphicsReference
                         {...props}
-                        src={convertFileSrc(absPath)}
+                        src={AssetUrl({ asset: asset.id })}
                     />
                 )
             case "gltfModel":
                 return (
                     <ModelReference
                         {...props}
-                        src={absPath}
+                        src={AssetUrl({ asset: asset.id })}
                         asset={asset.id}
                     />
                 )
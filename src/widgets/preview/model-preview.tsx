@@ -1,14 +1,13 @@
 import { useEffect, useState } from "react";
-import { Asset, GetRenderCache } from "../../backend";
+import { Asset, GetRenderCache, ThumbnailUrl } from "../../backend";
 import { Image, ImageProps, Text, useToastController } from "@fluentui/react-components";
-import { convertFileSrc } from "@tauri-apps/api/core";
 import { Cube48Regular } from "@fluentui/react-icons";
 import { t } from "../../i18n";
 import { GlobalToasterId } from "../../main";
 import ErrToast from "../toasts/err-toast";
 
 export default function ModelPreview({ asset, ...props }: { asset: Asset } & ImageProps) {
-    const [previewPath, setPreviewPath] = useState<string>()
+    const [rendered, setRendered] = useState(false)
     const { dispatchToast } = useToastController(GlobalToasterId)
 
     useEffect(() => {
@@ -16,14 +15,14 @@ export default function ModelPreview({ asset, ...props }: { asset: Asset } & Ima
             const cache = await GetRenderCache({ asset: asset.id })
                 .catch(err => dispatchToast(<ErrToast body={err} />, { intent: "error" }))
             if (cache) {
-                setPreviewPath(cache.path)
+                setRendered(true)
             }
         }
 
         fetch()
     }, [])
 
-    if (!previewPath) {
+    if (!rendered) {
         return (
             <div className="flex flex-col items-center">
                 <Cube48Regular />
@@ -35,7 +34,7 @@ export default function ModelPreview({ asset, ...props }: { asset: Asset } & Ima
     return (
         <Image
             {...props}
-            src={convertFileSrc(previewPath)}
+            src={ThumbnailUrl({ asset: asset.id })}
         />
     )
 }
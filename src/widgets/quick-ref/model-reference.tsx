@@ -1,7 +1,6 @@
 import { GLTFLoader } from "three/examples/jsm/loaders/GLTFLoader.js";
 import { Canvas, useLoader, useThree } from "@react-three/fiber";
 import { OrbitControls } from "@react-three/drei";
-import { convertFileSrc } from "@tauri-apps/api/core";
 import { MouseEvent, Suspense, useEffect, useRef, useState } from "react";
 import { getCurrentWindow } from "@tauri-apps/api/window";
 import { Euler, Quaternion } from "three";
@@ -11,7 +10,7 @@ import { GlobalToasterId } from "../../main";
 import ErrToast from "../toasts/err-toast";
 
 export default function ModelReference({ src, asset, onContextMenu }: { src: string, asset: string, onContextMenu: (ev: MouseEvent) => void }) {
-    const gltf = useLoader(GLTFLoader, convertFileSrc(src))
+    const gltf = useLoader(GLTFLoader, src)
     gltf.scene.castShadow = true
     gltf.scene.receiveShadow = true
 